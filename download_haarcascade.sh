@@ -1,17 +1,21 @@
 #!/bin/bash
 
-# Download haarcascade file for face detection
+# Download haarcascade files for face and eye detection
 # This is needed if OpenCV is not installed or haarcascade files are missing
+# haarcascade_eye.xml is optional and only used by the blink liveness challenge
 
-echo "Downloading haarcascade_frontalface_default.xml..."
+BASE_URL="https://raw.githubusercontent.com/opencv/opencv/master/data/haarcascades"
 
-curl -L -o haarcascade_frontalface_default.xml \
-  "https://raw.githubusercontent.com/opencv/opencv/master/data/haarcascades/haarcascade_frontalface_default.xml"
+for FILE in haarcascade_frontalface_default.xml haarcascade_eye.xml; do
+    echo "Downloading $FILE..."
 
-if [ -f haarcascade_frontalface_default.xml ]; then
-    echo "✅ Successfully downloaded haarcascade_frontalface_default.xml"
-    echo "File saved to: $(pwd)/haarcascade_frontalface_default.xml"
-else
-    echo "❌ Failed to download haarcascade file"
-    exit 1
-fi
+    curl -L -o "$FILE" "$BASE_URL/$FILE"
+
+    if [ -f "$FILE" ]; then
+        echo "✅ Successfully downloaded $FILE"
+        echo "File saved to: $(pwd)/$FILE"
+    else
+        echo "❌ Failed to download $FILE"
+        exit 1
+    fi
+done
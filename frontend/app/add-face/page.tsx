@@ -3,8 +3,6 @@
 import { useState } from 'react';
 import Camera from '../components/Camera';
 
-const API_URL = process.env.NEXT_PUBLIC_API_URL || 'http://localhost:8080';
-
 export default function AddFacePage() {
   const [userId, setUserId] = useState('');
   const [capturedImages, setCapturedImages] = useState<string[]>([]);
@@ -45,7 +43,7 @@ export default function AddFacePage() {
         formData.append('photos', blob, `photo_${i + 1}.jpg`);
       }
 
-      const response = await fetch(`${API_URL}/add-face`, {
+      const response = await fetch('/api/add-face', {
         method: 'POST',
         body: formData,
       });
@@ -0,0 +1,6 @@
+import { forwardToBackend } from '@/lib/backend';
+
+// Enrollment needs an admin key; keep this page behind your own login or network
+export async function POST(request: Request) {
+  return forwardToBackend(request, '/add-face', process.env.ENROLL_API_KEY);
+}
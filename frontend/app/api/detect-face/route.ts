@@ -0,0 +1,5 @@
+import { forwardToBackend } from '@/lib/backend';
+
+export async function POST(request: Request) {
+  return forwardToBackend(request, '/detect-face', process.env.DETECT_API_KEY);
+}
@@ -3,8 +3,6 @@
 import { useRef, useState, useEffect } from 'react';
 import Webcam from 'react-webcam';
 
-const API_URL = process.env.NEXT_PUBLIC_API_URL || 'http://localhost:8080';
-
 export default function DetectFacePage() {
   const webcamRef = useRef<Webcam>(null);
   const [isDetecting, setIsDetecting] = useState(false);
@@ -30,7 +28,7 @@ export default function DetectFacePage() {
       const formData = new FormData();
       formData.append('photo', blob, 'capture.jpg');
 
-      const response = await fetch(`${API_URL}/detect-face`, {
+      const response = await fetch('/api/detect-face', {
         method: 'POST',
         body: formData,
       });
@@ -0,0 +1,27 @@
+// Server-side only: the API keys live in runtime environment variables of the Next.js
+// server and are never compiled into the browser bundle. Pages call the routes under
+// app/api, which forward the upload to the backend with the key attached.
+const API_URL = process.env.API_URL || 'http://localhost:8080';
+
+export async function forwardToBackend(request: Request, path: string, apiKey: string | undefined) {
+  if (!apiKey) {
+    return Response.json(
+      { success: false, message: `No API key configured for ${path}` },
+      { status: 500 },
+    );
+  }
+
+  const response = await fetch(`${API_URL}${path}`, {
+    method: 'POST',
+    headers: {
+      'X-API-Key': apiKey,
+      'Content-Type': request.headers.get('content-type') ?? 'application/octet-stream',
+    },
+    body: await request.arrayBuffer(),
+  });
+
+  return new Response(response.body, {
+    status: response.status,
+    headers: { 'Content-Type': response.headers.get('content-type') ?? 'application/json' },
+  });
+}
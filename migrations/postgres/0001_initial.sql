@@ -0,0 +1,115 @@
+-- Same tables as migrations/sqlite/0001_initial.sql. Timestamps stay TEXT so both backends
+-- return them in the same format.
+
+-- Create users table
+CREATE TABLE users (
+    id BIGSERIAL PRIMARY KEY,
+    user_id TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    updated_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')
+);
+
+-- Create face_images table
+CREATE TABLE face_images (
+    id BIGSERIAL PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    image_path TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+
+-- Create detection_logs table
+CREATE TABLE detection_logs (
+    id BIGSERIAL PRIMARY KEY,
+    detected_user_id TEXT,
+    confidence DOUBLE PRECISION,
+    image_path TEXT,
+    detected_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')
+);
+
+-- Create indexes
+CREATE INDEX idx_users_user_id ON users(user_id);
+CREATE INDEX idx_face_images_user_id ON face_images(user_id);
+CREATE INDEX idx_detection_logs_detected_at ON detection_logs(detected_at);
+
+-- Create video_jobs table
+CREATE TABLE video_jobs (
+    id TEXT PRIMARY KEY,
+    source TEXT NOT NULL,
+    frame_stride BIGINT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'queued',
+    frames_read BIGINT NOT NULL DEFAULT 0,
+    frames_analyzed BIGINT NOT NULL DEFAULT 0,
+    faces_detected BIGINT NOT NULL DEFAULT 0,
+    unknown_faces BIGINT NOT NULL DEFAULT 0,
+    output_path TEXT,
+    error TEXT,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    finished_at TEXT
+);
+
+-- Create video_appearances table
+CREATE TABLE video_appearances (
+    id BIGSERIAL PRIMARY KEY,
+    job_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    first_frame BIGINT NOT NULL,
+    last_frame BIGINT NOT NULL,
+    first_seen_ms BIGINT NOT NULL,
+    last_seen_ms BIGINT NOT NULL,
+    detections BIGINT NOT NULL,
+    best_confidence DOUBLE PRECISION NOT NULL,
+    FOREIGN KEY (job_id) REFERENCES video_jobs(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_video_appearances_job_id ON video_appearances(job_id);
+
+-- Create detection_feedback table
+CREATE TABLE detection_feedback (
+    id BIGSERIAL PRIMARY KEY,
+    detection_id BIGINT NOT NULL UNIQUE,
+    verdict TEXT NOT NULL,
+    actual_user_id TEXT,
+    note TEXT,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    FOREIGN KEY (detection_id) REFERENCES detection_logs(id) ON DELETE CASCADE
+);
+
+-- Create unknown_clusters table
+CREATE TABLE unknown_clusters (
+    id BIGSERIAL PRIMARY KEY,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')
+);
+
+-- Create unknown_faces table
+CREATE TABLE unknown_faces (
+    id BIGSERIAL PRIMARY KEY,
+    image_path TEXT NOT NULL,
+    features BYTEA NOT NULL,
+    detection_id BIGINT,
+    confidence DOUBLE PRECISION,
+    cluster_id BIGINT,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    FOREIGN KEY (cluster_id) REFERENCES unknown_clusters(id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_unknown_faces_cluster_id ON unknown_faces(cluster_id);
+
+-- Create settings table
+CREATE TABLE settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')
+);
+
+-- Create api_keys table
+CREATE TABLE api_keys (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    role TEXT NOT NULL,
+    key_prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS'),
+    last_used_at TEXT,
+    revoked_at TEXT
+);
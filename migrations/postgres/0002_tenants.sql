@@ -0,0 +1,18 @@
+-- Scope users, face images and detections by tenant. User IDs become unique per tenant;
+-- existing rows belong to the `default` tenant.
+
+ALTER TABLE face_images DROP CONSTRAINT face_images_user_id_fkey;
+ALTER TABLE users DROP CONSTRAINT users_user_id_key;
+
+ALTER TABLE users ADD COLUMN tenant_id TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE users ADD CONSTRAINT users_tenant_id_user_id_key UNIQUE (tenant_id, user_id);
+
+ALTER TABLE face_images ADD COLUMN tenant_id TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE face_images ADD CONSTRAINT face_images_tenant_id_user_id_fkey
+    FOREIGN KEY (tenant_id, user_id) REFERENCES users(tenant_id, user_id) ON DELETE CASCADE;
+
+DROP INDEX idx_face_images_user_id;
+CREATE INDEX idx_face_images_user_id ON face_images(tenant_id, user_id);
+
+ALTER TABLE detection_logs ADD COLUMN tenant_id TEXT NOT NULL DEFAULT 'default';
+CREATE INDEX idx_detection_logs_tenant_id ON detection_logs(tenant_id);
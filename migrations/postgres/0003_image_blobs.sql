@@ -0,0 +1,8 @@
+-- Enrollment images kept in the database by the `database` image backend, keyed like
+-- `face_images.image_path`
+
+CREATE TABLE image_blobs (
+    key TEXT PRIMARY KEY,
+    data BYTEA NOT NULL,
+    created_at TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')
+);
@@ -0,0 +1,20 @@
+-- Proof that a user's biometric data was erased: who asked, when, and what was removed from
+-- each store. `image_keys` is a JSON array of the deleted image store keys.
+
+CREATE TABLE erasure_certificates (
+    id BIGSERIAL PRIMARY KEY,
+    tenant_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    erased_by TEXT NOT NULL,
+    detection_action TEXT NOT NULL,
+    pseudonym TEXT,
+    image_keys TEXT NOT NULL,
+    face_images BIGINT NOT NULL,
+    detections BIGINT NOT NULL,
+    feedback BIGINT NOT NULL,
+    video_appearances BIGINT NOT NULL,
+    model_users BIGINT NOT NULL,
+    erased_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_erasure_certificates_tenant_user ON erasure_certificates(tenant_id, user_id);
@@ -0,0 +1,15 @@
+-- Tenants the service serves; requests for any other tenant are rejected. `default` and every
+-- tenant that already has users or detections are registered.
+
+CREATE TABLE tenants (
+    tenant_id TEXT PRIMARY KEY,
+    created_at TEXT NOT NULL
+);
+
+INSERT INTO tenants (tenant_id, created_at)
+    SELECT tenant_id, to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS"Z"')
+    FROM (
+        SELECT 'default' AS tenant_id
+        UNION SELECT tenant_id FROM users
+        UNION SELECT tenant_id FROM detection_logs
+    ) AS known;
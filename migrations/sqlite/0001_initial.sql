@@ -0,0 +1,115 @@
+-- SQLite schema as created by `init_schema` before versioned migrations. Every statement is
+-- IF NOT EXISTS so databases created by `init_schema` can be brought under version control.
+
+-- Create users table
+CREATE TABLE IF NOT EXISTS users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Create face_images table
+CREATE TABLE IF NOT EXISTS face_images (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id TEXT NOT NULL,
+    image_path TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+
+-- Create detection_logs table
+CREATE TABLE IF NOT EXISTS detection_logs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    detected_user_id TEXT,
+    confidence REAL,
+    image_path TEXT,
+    detected_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Create indexes
+CREATE INDEX IF NOT EXISTS idx_users_user_id ON users(user_id);
+CREATE INDEX IF NOT EXISTS idx_face_images_user_id ON face_images(user_id);
+CREATE INDEX IF NOT EXISTS idx_detection_logs_detected_at ON detection_logs(detected_at);
+
+-- Create video_jobs table
+CREATE TABLE IF NOT EXISTS video_jobs (
+    id TEXT PRIMARY KEY,
+    source TEXT NOT NULL,
+    frame_stride INTEGER NOT NULL,
+    status TEXT NOT NULL DEFAULT 'queued',
+    frames_read INTEGER NOT NULL DEFAULT 0,
+    frames_analyzed INTEGER NOT NULL DEFAULT 0,
+    faces_detected INTEGER NOT NULL DEFAULT 0,
+    unknown_faces INTEGER NOT NULL DEFAULT 0,
+    output_path TEXT,
+    error TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    finished_at TEXT
+);
+
+-- Create video_appearances table
+CREATE TABLE IF NOT EXISTS video_appearances (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    job_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    first_frame INTEGER NOT NULL,
+    last_frame INTEGER NOT NULL,
+    first_seen_ms INTEGER NOT NULL,
+    last_seen_ms INTEGER NOT NULL,
+    detections INTEGER NOT NULL,
+    best_confidence REAL NOT NULL,
+    FOREIGN KEY (job_id) REFERENCES video_jobs(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_video_appearances_job_id ON video_appearances(job_id);
+
+-- Create detection_feedback table
+CREATE TABLE IF NOT EXISTS detection_feedback (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    detection_id INTEGER NOT NULL UNIQUE,
+    verdict TEXT NOT NULL,
+    actual_user_id TEXT,
+    note TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (detection_id) REFERENCES detection_logs(id) ON DELETE CASCADE
+);
+
+-- Create unknown_clusters table
+CREATE TABLE IF NOT EXISTS unknown_clusters (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Create unknown_faces table
+CREATE TABLE IF NOT EXISTS unknown_faces (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    image_path TEXT NOT NULL,
+    features BLOB NOT NULL,
+    detection_id INTEGER,
+    confidence REAL,
+    cluster_id INTEGER,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (cluster_id) REFERENCES unknown_clusters(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_unknown_faces_cluster_id ON unknown_faces(cluster_id);
+
+-- Create settings table
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Create api_keys table
+CREATE TABLE IF NOT EXISTS api_keys (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    role TEXT NOT NULL,
+    key_prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    last_used_at TEXT,
+    revoked_at TEXT
+);
@@ -0,0 +1,38 @@
+-- Scope users, face images and detections by tenant. User IDs become unique per tenant;
+-- existing rows belong to the `default` tenant.
+
+ALTER TABLE users RENAME TO users_legacy;
+ALTER TABLE face_images RENAME TO face_images_legacy;
+DROP INDEX IF EXISTS idx_users_user_id;
+DROP INDEX IF EXISTS idx_face_images_user_id;
+
+CREATE TABLE users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tenant_id TEXT NOT NULL DEFAULT 'default',
+    user_id TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    UNIQUE (tenant_id, user_id)
+);
+
+CREATE TABLE face_images (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tenant_id TEXT NOT NULL DEFAULT 'default',
+    user_id TEXT NOT NULL,
+    image_path TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (tenant_id, user_id) REFERENCES users(tenant_id, user_id) ON DELETE CASCADE
+);
+
+INSERT INTO users (id, tenant_id, user_id, created_at, updated_at)
+    SELECT id, 'default', user_id, created_at, updated_at FROM users_legacy;
+INSERT INTO face_images (id, tenant_id, user_id, image_path, created_at)
+    SELECT id, 'default', user_id, image_path, created_at FROM face_images_legacy;
+DROP TABLE face_images_legacy;
+DROP TABLE users_legacy;
+
+CREATE INDEX idx_users_user_id ON users(user_id);
+CREATE INDEX idx_face_images_user_id ON face_images(tenant_id, user_id);
+
+ALTER TABLE detection_logs ADD COLUMN tenant_id TEXT NOT NULL DEFAULT 'default';
+CREATE INDEX idx_detection_logs_tenant_id ON detection_logs(tenant_id);
@@ -0,0 +1,8 @@
+-- Enrollment images kept in the database by the `database` image backend, keyed like
+-- `face_images.image_path`
+
+CREATE TABLE image_blobs (
+    key TEXT PRIMARY KEY,
+    data BLOB NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
@@ -0,0 +1,6 @@
+-- Where a probe came from (a camera, kiosk or other client), as given to /detect-face, and an
+-- index for browsing a tenant's detection history by time
+
+ALTER TABLE detection_logs ADD COLUMN source TEXT;
+
+CREATE INDEX idx_detection_logs_tenant_detected_at ON detection_logs(tenant_id, detected_at);
@@ -0,0 +1,20 @@
+-- Proof that a user's biometric data was erased: who asked, when, and what was removed from
+-- each store. `image_keys` is a JSON array of the deleted image store keys.
+
+CREATE TABLE erasure_certificates (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tenant_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    erased_by TEXT NOT NULL,
+    detection_action TEXT NOT NULL,
+    pseudonym TEXT,
+    image_keys TEXT NOT NULL,
+    face_images INTEGER NOT NULL,
+    detections INTEGER NOT NULL,
+    feedback INTEGER NOT NULL,
+    video_appearances INTEGER NOT NULL,
+    model_users INTEGER NOT NULL,
+    erased_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_erasure_certificates_tenant_user ON erasure_certificates(tenant_id, user_id);
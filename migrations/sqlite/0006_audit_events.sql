@@ -0,0 +1,17 @@
+-- Who did what to which target, and how it went, for every mutating API request and CLI
+-- command. `status` is the HTTP status; NULL for CLI commands.
+
+CREATE TABLE audit_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    request_id TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    action TEXT NOT NULL,
+    target TEXT,
+    outcome TEXT NOT NULL,
+    status INTEGER,
+    detail TEXT,
+    occurred_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_audit_events_occurred_at ON audit_events(occurred_at);
+CREATE INDEX idx_audit_events_actor ON audit_events(actor);
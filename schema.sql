@@ -1,30 +0,0 @@
--- Create users table
-CREATE TABLE IF NOT EXISTS users (
-    id INTEGER PRIMARY KEY AUTOINCREMENT,
-    user_id TEXT NOT NULL UNIQUE,
-    created_at TEXT NOT NULL DEFAULT (datetime('now')),
-    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
-);
-
--- Create face_images table
-CREATE TABLE IF NOT EXISTS face_images (
-    id INTEGER PRIMARY KEY AUTOINCREMENT,
-    user_id TEXT NOT NULL,
-    image_path TEXT NOT NULL,
-    created_at TEXT NOT NULL DEFAULT (datetime('now')),
-    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
-);
-
--- Create detection_logs table
-CREATE TABLE IF NOT EXISTS detection_logs (
-    id INTEGER PRIMARY KEY AUTOINCREMENT,
-    detected_user_id TEXT,
-    confidence REAL,
-    image_path TEXT,
-    detected_at TEXT NOT NULL DEFAULT (datetime('now'))
-);
-
--- Create indexes
-CREATE INDEX IF NOT EXISTS idx_users_user_id ON users(user_id);
-CREATE INDEX IF NOT EXISTS idx_face_images_user_id ON face_images(user_id);
-CREATE INDEX IF NOT EXISTS idx_detection_logs_detected_at ON detection_logs(detected_at);
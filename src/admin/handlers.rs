@@ -0,0 +1,32 @@
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+    Json,
+};
+use serde_json::Value;
+
+use crate::config::SharedConfig;
+use crate::face_recognition::handlers::{ApiResponse, SharedDb, SharedModel};
+
+/// Show the effective configuration
+///
+/// Returns the settings the server is running with, after the config file and environment
+/// overrides were applied. Passwords, secrets, tokens, keys and database credentials are hidden.
+#[utoipa::path(
+    get,
+    path = "/admin/config",
+    responses(
+        (status = 200, description = "Effective configuration", body = ApiResponse<Object>)
+    ),
+    tag = "Admin"
+)]
+pub async fn get_config(
+    State((_model, _db)): State<(SharedModel, SharedDb)>,
+    Extension(config): Extension<SharedConfig>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, String)> {
+    Ok(Json(ApiResponse {
+        success: true,
+        message: "Effective configuration".to_string(),
+        data: Some(config.redacted()),
+    }))
+}
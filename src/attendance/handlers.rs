@@ -0,0 +1,227 @@
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{Attendance, AttendanceRecord, UserAttendance};
+use crate::config::SharedConfig;
+use crate::face_recognition::handlers::{ApiResponse, SharedDb, SharedModel};
+use crate::tenants::{check_tenant_id, DEFAULT_TENANT};
+
+// Longest period a single report may cover
+const MAX_REPORT_DAYS: i64 = 366;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+/// Query parameters for attendance reports
+#[derive(Debug, Deserialize)]
+pub struct AttendanceQuery {
+    /// Tenant to report on (default `default`)
+    tenant: Option<String>,
+    /// First local date, `YYYY-MM-DD` (default today)
+    from: Option<String>,
+    /// Last local date, inclusive (default `from`)
+    to: Option<String>,
+    /// Only report this user
+    user_id: Option<String>,
+    /// IANA time zone overriding `attendance.timezone`
+    timezone: Option<String>,
+    #[serde(default)]
+    format: ReportFormat,
+}
+
+/// Per-user attendance for every day of a period
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AttendanceReport {
+    tenant: String,
+    timezone: String,
+    from: String,
+    to: String,
+    records: Vec<AttendanceRecord>,
+}
+
+/// Per-user attendance totals over a period
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AttendanceSummary {
+    tenant: String,
+    timezone: String,
+    from: String,
+    to: String,
+    users: Vec<UserAttendance>,
+}
+
+/// Daily attendance report
+///
+/// One record per user and local day: first-in, last-out and presence duration from matched
+/// detections, and a `present: false` record for every enrolled user who was not seen.
+#[utoipa::path(
+    get,
+    path = "/reports/attendance",
+    params(
+        ("tenant" = Option<String>, Query, description = "Tenant ID (default `default`)"),
+        ("from" = Option<String>, Query, description = "First local date, YYYY-MM-DD (default today)"),
+        ("to" = Option<String>, Query, description = "Last local date, inclusive (default `from`)"),
+        ("user_id" = Option<String>, Query, description = "Only report this user"),
+        ("timezone" = Option<String>, Query, description = "IANA time zone (default attendance.timezone)"),
+        ("format" = Option<ReportFormat>, Query, description = "json (default) or csv")
+    ),
+    responses(
+        (status = 200, description = "Attendance records", body = ApiResponse<AttendanceReport>),
+        (status = 200, description = "Attendance records as CSV", content_type = "text/csv"),
+        (status = 400, description = "Invalid tenant, date range or time zone"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Reports"
+)]
+pub async fn attendance_report(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+    Extension(config): Extension<SharedConfig>,
+    Query(query): Query<AttendanceQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let format = query.format;
+    let (tenant, attendance) = load_attendance(&db, &config, query).await?;
+
+    if format == ReportFormat::Csv {
+        return Ok(csv_response("attendance", &attendance, attendance.records_csv()));
+    }
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("{} attendance records", attendance.records.len()),
+        data: Some(AttendanceReport {
+            tenant,
+            timezone: attendance.timezone.name().to_string(),
+            from: attendance.from.to_string(),
+            to: attendance.to.to_string(),
+            records: attendance.records,
+        }),
+    })
+    .into_response())
+}
+
+/// Attendance summary
+///
+/// Days present, days absent and total presence per user over the period.
+#[utoipa::path(
+    get,
+    path = "/reports/attendance/summary",
+    params(
+        ("tenant" = Option<String>, Query, description = "Tenant ID (default `default`)"),
+        ("from" = Option<String>, Query, description = "First local date, YYYY-MM-DD (default today)"),
+        ("to" = Option<String>, Query, description = "Last local date, inclusive (default `from`)"),
+        ("user_id" = Option<String>, Query, description = "Only report this user"),
+        ("timezone" = Option<String>, Query, description = "IANA time zone (default attendance.timezone)"),
+        ("format" = Option<ReportFormat>, Query, description = "json (default) or csv")
+    ),
+    responses(
+        (status = 200, description = "Attendance per user", body = ApiResponse<AttendanceSummary>),
+        (status = 200, description = "Attendance per user as CSV", content_type = "text/csv"),
+        (status = 400, description = "Invalid tenant, date range or time zone"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Reports"
+)]
+pub async fn attendance_summary(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+    Extension(config): Extension<SharedConfig>,
+    Query(query): Query<AttendanceQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let format = query.format;
+    let (tenant, attendance) = load_attendance(&db, &config, query).await?;
+
+    if format == ReportFormat::Csv {
+        return Ok(csv_response("attendance-summary", &attendance, attendance.summary_csv()));
+    }
+
+    let users = attendance.summary();
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Attendance of {} users", users.len()),
+        data: Some(AttendanceSummary {
+            tenant,
+            timezone: attendance.timezone.name().to_string(),
+            from: attendance.from.to_string(),
+            to: attendance.to.to_string(),
+            users,
+        }),
+    })
+    .into_response())
+}
+
+async fn load_attendance(
+    db: &SharedDb,
+    config: &SharedConfig,
+    query: AttendanceQuery,
+) -> Result<(String, Attendance), (StatusCode, String)> {
+    let tenant = query.tenant.unwrap_or_else(|| DEFAULT_TENANT.to_string());
+    check_tenant_id(&tenant).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    let timezone = match query.timezone {
+        Some(name) => name
+            .parse::<Tz>()
+            .map_err(|_| (StatusCode::BAD_REQUEST, format!("Unknown time zone: {}", name)))?,
+        None => config.attendance.timezone,
+    };
+
+    let from = match query.from {
+        Some(from) => parse_date("from", &from)?,
+        None => Utc::now().with_timezone(&timezone).date_naive(),
+    };
+    let to = match query.to {
+        Some(to) => parse_date("to", &to)?,
+        None => from,
+    };
+    if to < from {
+        return Err((StatusCode::BAD_REQUEST, "to must not be before from".to_string()));
+    }
+    if (to - from).num_days() >= MAX_REPORT_DAYS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("A report covers at most {} days", MAX_REPORT_DAYS),
+        ));
+    }
+
+    let attendance = Attendance::load(
+        db,
+        &tenant,
+        from,
+        to,
+        query.user_id.as_deref(),
+        &config.attendance,
+        timezone,
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok((tenant, attendance))
+}
+
+fn parse_date(name: &str, value: &str) -> Result<NaiveDate, (StatusCode, String)> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| (StatusCode::BAD_REQUEST, format!("Invalid {}: {} (use YYYY-MM-DD)", name, value)))
+}
+
+fn csv_response(name: &str, attendance: &Attendance, csv: String) -> Response {
+    let disposition = format!("attachment; filename=\"{}-{}-{}.csv\"", name, attendance.from, attendance.to);
+
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        csv,
+    )
+        .into_response()
+}
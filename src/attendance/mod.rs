@@ -0,0 +1,427 @@
+pub mod handlers;
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use utoipa::ToSchema;
+
+use crate::database::{Database, DETECTED_AT_FORMAT};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AttendanceConfig {
+    /// IANA time zone days are counted in, such as `Asia/Jakarta`
+    pub timezone: Tz,
+    /// Consecutive sightings further apart than this are not counted as presence in between
+    pub max_gap_mins: u32,
+}
+
+impl Default for AttendanceConfig {
+    fn default() -> Self {
+        Self {
+            timezone: Tz::UTC,
+            max_gap_mins: 60,
+        }
+    }
+}
+
+/// A user's attendance on one day
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AttendanceRecord {
+    /// Local date, `YYYY-MM-DD`
+    pub date: String,
+    pub user_id: String,
+    /// False for enrolled users who were not seen that day
+    pub present: bool,
+    /// First and last sighting, RFC 3339 in the report's time zone
+    pub first_in: Option<String>,
+    pub last_out: Option<String>,
+    /// Sum of the gaps between consecutive sightings that are at most `max_gap_mins` apart
+    pub presence_secs: i64,
+    /// Matched detections that day
+    pub sightings: usize,
+}
+
+/// A user's attendance over the whole report period
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UserAttendance {
+    pub user_id: String,
+    pub days_present: usize,
+    pub days_absent: usize,
+    pub presence_secs: i64,
+}
+
+/// Per-user attendance for every day of a period
+pub struct Attendance {
+    pub timezone: Tz,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// Sorted by date, then present users before absentees, then user ID
+    pub records: Vec<AttendanceRecord>,
+}
+
+impl Attendance {
+    /// Build the attendance of `tenant` from `from` to `to` (inclusive local dates). Only
+    /// `user_id` is reported when given. Users are absent on days after their enrollment
+    /// date on which they were not seen.
+    pub async fn load(
+        db: &Database,
+        tenant: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+        user_id: Option<&str>,
+        config: &AttendanceConfig,
+        timezone: Tz,
+    ) -> Result<Self> {
+        let start = local_midnight(timezone, from).format(DETECTED_AT_FORMAT).to_string();
+        let end = local_midnight(timezone, to + Duration::days(1)).format(DETECTED_AT_FORMAT).to_string();
+        let sightings = db.get_sightings(tenant, &start, &end).await?;
+        let enrollments = db.get_user_enrollments(tenant).await?;
+
+        Ok(Self::build(sightings, enrollments, from, to, user_id, config, timezone))
+    }
+
+    // Attendance from `(user, detected_at)` sightings and `(user, created_at)` enrollments
+    fn build(
+        sightings: Vec<(String, String)>,
+        enrollments: Vec<(String, String)>,
+        from: NaiveDate,
+        to: NaiveDate,
+        user_id: Option<&str>,
+        config: &AttendanceConfig,
+        timezone: Tz,
+    ) -> Self {
+        // Sightings grouped by user and local date
+        let mut days: BTreeMap<(NaiveDate, String), Vec<DateTime<Tz>>> = BTreeMap::new();
+        for (user, detected_at) in sightings {
+            if user_id.is_some_and(|user_id| user_id != user) {
+                continue;
+            }
+            let Some(time) = parse_time(&detected_at) else {
+                eprintln!("Ignoring detection of {} with invalid time '{}'", user, detected_at);
+                continue;
+            };
+            let local = time.with_timezone(&timezone);
+            days.entry((local.date_naive(), user)).or_default().push(local);
+        }
+
+        let enrolled: Vec<(String, NaiveDate)> = enrollments
+            .into_iter()
+            .filter(|(user, _)| user_id.is_none_or(|user_id| user_id == user))
+            .filter_map(|(user, created_at)| {
+                let enrolled_on = parse_time(&created_at)?.with_timezone(&timezone).date_naive();
+                Some((user, enrolled_on))
+            })
+            .collect();
+
+        let max_gap = Duration::minutes(config.max_gap_mins as i64);
+        let mut records = Vec::new();
+        for date in from.iter_days().take_while(|date| *date <= to) {
+            let mut present = BTreeSet::new();
+            for ((_, user), sightings) in days.range((date, String::new())..).take_while(|((day, _), _)| *day == date) {
+                present.insert(user.as_str());
+                records.push(presence(date, user, sightings, max_gap));
+            }
+
+            for (user, enrolled_on) in &enrolled {
+                if *enrolled_on <= date && !present.contains(user.as_str()) {
+                    records.push(AttendanceRecord {
+                        date: date.to_string(),
+                        user_id: user.clone(),
+                        present: false,
+                        first_in: None,
+                        last_out: None,
+                        presence_secs: 0,
+                        sightings: 0,
+                    });
+                }
+            }
+        }
+
+        Self {
+            timezone,
+            from,
+            to,
+            records,
+        }
+    }
+
+    /// Totals per user, sorted by user ID
+    pub fn summary(&self) -> Vec<UserAttendance> {
+        let mut users: BTreeMap<&str, UserAttendance> = BTreeMap::new();
+        for record in &self.records {
+            let user = users.entry(&record.user_id).or_insert_with(|| UserAttendance {
+                user_id: record.user_id.clone(),
+                days_present: 0,
+                days_absent: 0,
+                presence_secs: 0,
+            });
+            if record.present {
+                user.days_present += 1;
+            } else {
+                user.days_absent += 1;
+            }
+            user.presence_secs += record.presence_secs;
+        }
+
+        users.into_values().collect()
+    }
+
+    pub fn records_csv(&self) -> String {
+        let mut csv = String::from("date,user_id,present,first_in,last_out,presence_secs,sightings\n");
+        for record in &self.records {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                record.date,
+                csv_field(&record.user_id),
+                record.present,
+                record.first_in.as_deref().unwrap_or(""),
+                record.last_out.as_deref().unwrap_or(""),
+                record.presence_secs,
+                record.sightings
+            ));
+        }
+        csv
+    }
+
+    pub fn summary_csv(&self) -> String {
+        let mut csv = String::from("user_id,days_present,days_absent,presence_secs\n");
+        for user in self.summary() {
+            csv.push_str(&format!(
+                "{},{},{},{}\n",
+                csv_field(&user.user_id),
+                user.days_present,
+                user.days_absent,
+                user.presence_secs
+            ));
+        }
+        csv
+    }
+}
+
+// First-in, last-out and presence of a user seen at `sightings` (sorted) on `date`
+fn presence(date: NaiveDate, user_id: &str, sightings: &[DateTime<Tz>], max_gap: Duration) -> AttendanceRecord {
+    let presence_secs = sightings
+        .windows(2)
+        .map(|pair| pair[1] - pair[0])
+        .filter(|gap| *gap <= max_gap)
+        .map(|gap| gap.num_seconds())
+        .sum();
+
+    AttendanceRecord {
+        date: date.to_string(),
+        user_id: user_id.to_string(),
+        present: true,
+        first_in: sightings.first().map(|time| time.to_rfc3339()),
+        last_out: sightings.last().map(|time| time.to_rfc3339()),
+        presence_secs,
+        sightings: sightings.len(),
+    }
+}
+
+// Start of a local day in UTC; days starting in a DST gap begin at the first valid instant
+fn local_midnight(timezone: Tz, date: NaiveDate) -> NaiveDateTime {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    (0..=3)
+        .find_map(|hour| timezone.from_local_datetime(&(midnight + Duration::hours(hour))).earliest())
+        .map(|start| start.naive_utc())
+        .unwrap_or(midnight)
+}
+
+// Detections are stored as `YYYY-MM-DD HH:MM:SS` UTC, enrollments as RFC 3339
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .or_else(|_| NaiveDateTime::parse_from_str(value, DETECTED_AT_FORMAT).map(|time| time.and_utc()))
+        .ok()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> NaiveDate {
+        text.parse().unwrap()
+    }
+
+    fn pairs(values: &[(&str, &str)]) -> Vec<(String, String)> {
+        values.iter().map(|(a, b)| (a.to_string(), b.to_string())).collect()
+    }
+
+    fn record<'a>(attendance: &'a Attendance, day: &str, user_id: &str) -> Option<&'a AttendanceRecord> {
+        attendance.records.iter().find(|record| record.date == day && record.user_id == user_id)
+    }
+
+    #[test]
+    fn local_days_start_at_local_midnight() {
+        let jakarta: Tz = "Asia/Jakarta".parse().unwrap();
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+        let santiago: Tz = "America/Santiago".parse().unwrap();
+        let at = |text: &str| NaiveDateTime::parse_from_str(text, DETECTED_AT_FORMAT).unwrap();
+
+        assert_eq!(local_midnight(Tz::UTC, date("2024-03-31")), at("2024-03-31 00:00:00"));
+        assert_eq!(local_midnight(jakarta, date("2024-03-31")), at("2024-03-30 17:00:00"));
+        // The day clocks go forward is an hour shorter
+        assert_eq!(local_midnight(berlin, date("2024-03-31")), at("2024-03-30 23:00:00"));
+        assert_eq!(local_midnight(berlin, date("2024-04-01")), at("2024-03-31 22:00:00"));
+        // Chile skips local midnight itself; the day starts at 01:00
+        assert_eq!(local_midnight(santiago, date("2024-09-08")), at("2024-09-08 04:00:00"));
+        assert_eq!(local_midnight(santiago, date("2024-09-07")), at("2024-09-07 04:00:00"));
+    }
+
+    #[test]
+    fn sightings_are_counted_on_local_days_across_a_dst_change() {
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+        let sightings = pairs(&[
+            // 23:30 CET, still the 30th locally
+            ("alice", "2024-03-30 22:30:00"),
+            // 00:30 and 01:30 CET, then 03:15 CEST once clocks have gone forward
+            ("alice", "2024-03-30 23:30:00"),
+            ("alice", "2024-03-31 00:30:00"),
+            ("alice", "2024-03-31 01:15:00"),
+            ("alice", "2024-03-31 21:59:59"),
+            // Midnight CEST starts the 1st
+            ("alice", "2024-03-31 22:00:00"),
+        ]);
+        let config = AttendanceConfig {
+            timezone: berlin,
+            max_gap_mins: 60,
+        };
+
+        let attendance = Attendance::build(sightings, Vec::new(), date("2024-03-30"), date("2024-04-01"), None, &config, berlin);
+
+        let days: Vec<(&str, usize)> = attendance.records.iter().map(|record| (record.date.as_str(), record.sightings)).collect();
+        assert_eq!(days, [("2024-03-30", 1), ("2024-03-31", 4), ("2024-04-01", 1)]);
+
+        let day = record(&attendance, "2024-03-31", "alice").unwrap();
+        assert_eq!(day.first_in.as_deref(), Some("2024-03-31T00:30:00+01:00"));
+        assert_eq!(day.last_out.as_deref(), Some("2024-03-31T23:59:59+02:00"));
+        // Real elapsed time: an hour, then 45 minutes although the clock moved 1:45; the
+        // 20-hour gap to the last sighting is not presence
+        assert_eq!(day.presence_secs, 3600 + 2700);
+        assert_eq!(record(&attendance, "2024-03-30", "alice").unwrap().presence_secs, 0);
+    }
+
+    #[test]
+    fn gaps_longer_than_max_gap_split_presence() {
+        let sightings = pairs(&[
+            ("alice", "2024-05-01 08:00:00"),
+            ("alice", "2024-05-01 08:20:00"),
+            ("alice", "2024-05-01 09:00:00"),
+            ("alice", "2024-05-01 09:30:00"),
+            ("alice", "2024-05-01 12:00:00"),
+        ]);
+        let presence = |max_gap_mins| {
+            let config = AttendanceConfig {
+                timezone: Tz::UTC,
+                max_gap_mins,
+            };
+            let day = date("2024-05-01");
+            Attendance::build(sightings.clone(), Vec::new(), day, day, None, &config, Tz::UTC).records[0].presence_secs
+        };
+
+        assert_eq!(presence(60), (20 + 40 + 30) * 60);
+        assert_eq!(presence(30), (20 + 30) * 60);
+        assert_eq!(presence(10), 0);
+        assert_eq!(presence(180), 4 * 60 * 60);
+    }
+
+    #[test]
+    fn absentees_are_counted_from_their_local_enrollment_date() {
+        let jakarta: Tz = "Asia/Jakarta".parse().unwrap();
+        let sightings = pairs(&[("alice", "2024-05-02 02:00:00"), ("dave", "2024-05-01 03:00:00")]);
+        let enrollments = pairs(&[
+            ("alice", "2024-01-01T00:00:00+00:00"),
+            // 2024-05-02 01:00 in Jakarta
+            ("bob", "2024-05-01T18:00:00+00:00"),
+            ("carol", "2024-05-10T00:00:00+00:00"),
+        ]);
+
+        let attendance = Attendance::build(
+            sightings.clone(),
+            enrollments.clone(),
+            date("2024-05-01"),
+            date("2024-05-03"),
+            None,
+            &AttendanceConfig::default(),
+            jakarta,
+        );
+
+        let records: Vec<(&str, &str, bool)> = attendance
+            .records
+            .iter()
+            .map(|record| (record.date.as_str(), record.user_id.as_str(), record.present))
+            .collect();
+        assert_eq!(
+            records,
+            [
+                ("2024-05-01", "dave", true),
+                ("2024-05-01", "alice", false),
+                ("2024-05-02", "alice", true),
+                ("2024-05-02", "bob", false),
+                ("2024-05-03", "alice", false),
+                ("2024-05-03", "bob", false),
+            ]
+        );
+
+        let summary: Vec<(String, usize, usize)> = attendance
+            .summary()
+            .into_iter()
+            .map(|user| (user.user_id, user.days_present, user.days_absent))
+            .collect();
+        assert_eq!(
+            summary,
+            [("alice".to_string(), 1, 2), ("bob".to_string(), 0, 2), ("dave".to_string(), 1, 0)]
+        );
+
+        let bob = Attendance::build(
+            sightings,
+            enrollments,
+            date("2024-05-01"),
+            date("2024-05-03"),
+            Some("bob"),
+            &AttendanceConfig::default(),
+            jakarta,
+        );
+        assert!(bob.records.iter().all(|record| record.user_id == "bob"));
+        assert_eq!(bob.records.len(), 2);
+    }
+
+    #[test]
+    fn writes_csv() {
+        let sightings = pairs(&[
+            ("alice", "2024-05-01 08:00:00"),
+            ("alice", "2024-05-01 08:30:00"),
+            ("doe, \"jd\"", "2024-05-01 09:00:00"),
+        ]);
+        let enrollments = pairs(&[("alice", "2024-01-01T00:00:00Z"), ("bob", "2024-01-01T00:00:00Z")]);
+        let day = date("2024-05-01");
+
+        let attendance = Attendance::build(sightings, enrollments, day, day, None, &AttendanceConfig::default(), Tz::UTC);
+
+        assert_eq!(
+            attendance.records_csv(),
+            "date,user_id,present,first_in,last_out,presence_secs,sightings\n\
+             2024-05-01,alice,true,2024-05-01T08:00:00+00:00,2024-05-01T08:30:00+00:00,1800,2\n\
+             2024-05-01,\"doe, \"\"jd\"\"\",true,2024-05-01T09:00:00+00:00,2024-05-01T09:00:00+00:00,0,1\n\
+             2024-05-01,bob,false,,,0,0\n"
+        );
+        assert_eq!(
+            attendance.summary_csv(),
+            "user_id,days_present,days_absent,presence_secs\n\
+             alice,1,0,1800\n\
+             bob,0,1,0\n\
+             \"doe, \"\"jd\"\"\",1,0,0\n"
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::database::{AuditEvent, AuditFilter};
+use crate::detections::handlers::parse_time;
+use crate::face_recognition::handlers::{ApiResponse, SharedDb, SharedModel};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+/// Query parameters for the audit log
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    actor: Option<String>,
+    action: Option<String>,
+    target: Option<String>,
+    /// `success` or `failure`
+    outcome: Option<String>,
+    request_id: Option<String>,
+    /// RFC 3339, `YYYY-MM-DD HH:MM:SS` (UTC) or `YYYY-MM-DD`; inclusive
+    from: Option<String>,
+    /// Same formats as `from`; exclusive
+    to: Option<String>,
+    /// `next_cursor` of the previous page, with the same filters
+    cursor: Option<String>,
+    limit: Option<i64>,
+}
+
+/// One page of the audit log
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuditPage {
+    events: Vec<AuditEvent>,
+    /// Pass as `cursor` to get the next page; null on the last page
+    next_cursor: Option<String>,
+}
+
+/// Query the audit log
+///
+/// Every request that enrolls, erases, changes thresholds, gives feedback or otherwise changes
+/// data is recorded with its caller, action, target, request ID and outcome, as are
+/// administration commands run from the command line. Newest first; pages are linked by
+/// `next_cursor`.
+#[utoipa::path(
+    get,
+    path = "/admin/audit",
+    params(
+        ("actor" = Option<String>, Query, description = "API key name, token subject, `anonymous` or `cli`"),
+        ("action" = Option<String>, Query, description = "Action such as enroll, erase or set_threshold"),
+        ("target" = Option<String>, Query, description = "Target such as `default/user123`"),
+        ("outcome" = Option<String>, Query, description = "success or failure"),
+        ("request_id" = Option<String>, Query, description = "X-Request-Id of the request"),
+        ("from" = Option<String>, Query, description = "Occurred at or after (RFC 3339, `YYYY-MM-DD HH:MM:SS` UTC or `YYYY-MM-DD`)"),
+        ("to" = Option<String>, Query, description = "Occurred before (same formats as `from`)"),
+        ("cursor" = Option<String>, Query, description = "next_cursor of the previous page"),
+        ("limit" = Option<i64>, Query, description = "Page size, 1-500 (default 50)")
+    ),
+    responses(
+        (status = 200, description = "Audit events, newest first", body = ApiResponse<AuditPage>),
+        (status = 400, description = "Invalid filter, cursor or limit"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Admin"
+)]
+pub async fn list_audit_events(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<ApiResponse<AuditPage>>, (StatusCode, String)> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err((StatusCode::BAD_REQUEST, format!("limit must be between 1 and {}", MAX_PAGE_SIZE)));
+    }
+
+    let before = query
+        .cursor
+        .map(|cursor| cursor.parse::<i64>().map_err(|_| (StatusCode::BAD_REQUEST, format!("Invalid cursor: {}", cursor))))
+        .transpose()?;
+
+    let filter = AuditFilter {
+        actor: query.actor,
+        action: query.action,
+        target: query.target,
+        outcome: query.outcome,
+        request_id: query.request_id,
+        from: query.from.as_deref().map(|from| parse_time("from", from)).transpose()?,
+        to: query.to.as_deref().map(|to| parse_time("to", to)).transpose()?,
+        before,
+        // One more than asked tells whether there is a next page
+        limit: limit + 1,
+    };
+
+    let mut events = db
+        .get_audit_events(&filter)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let next_cursor = if events.len() as i64 > limit {
+        events.truncate(limit as usize);
+        events.last().map(|event| event.id.to_string())
+    } else {
+        None
+    };
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Found {} audit events", events.len()),
+        data: Some(AuditPage { events, next_cursor }),
+    }))
+}
@@ -0,0 +1,230 @@
+pub mod handlers;
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{HeaderValue, Method},
+    middleware::{self, Next},
+    response::Response,
+    routing::MethodRouter,
+};
+use chrono::Utc;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use crate::auth::middleware::{require_role, SharedAuthenticator};
+use crate::auth::{Principal, Role};
+use crate::database::{AuditEvent, Database};
+use crate::face_recognition::handlers::SharedDb;
+
+/// Header carrying the ID that ties a request to its audit event
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+// Format of `audit_events.occurred_at`, the same as `detection_logs.detected_at`
+const OCCURRED_AT_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+// Longest client-supplied request ID that is kept
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+// Error responses are short messages; anything longer is not kept as the detail
+const MAX_ERROR_BODY: usize = 64 * 1024;
+
+/// Target and details of an audited request, filled in by its handler once it knows them
+/// (the enrolled user ID, for instance, only arrives in the multipart body)
+#[derive(Debug, Clone, Default)]
+pub struct AuditContext(Arc<Mutex<(Option<String>, Option<String>)>>);
+
+impl AuditContext {
+    pub fn target(&self, target: impl Into<String>) {
+        if let Ok(mut context) = self.0.lock() {
+            context.0 = Some(target.into());
+        }
+    }
+
+    pub fn detail(&self, detail: impl Into<String>) {
+        if let Ok(mut context) = self.0.lock() {
+            context.1 = Some(detail.into());
+        }
+    }
+
+    fn take(&self) -> (Option<String>, Option<String>) {
+        self.0.lock().map(|mut context| std::mem::take(&mut *context)).unwrap_or_default()
+    }
+}
+
+/// Record the request as `action` in the audit log once it has been handled
+///
+/// Used through [`audited`] on each mutating route, around its role check so refused attempts
+/// are recorded too; the caller is read from the [`Principal`] the check puts on the response,
+/// and is `anonymous` when it had no valid credentials. GET and HEAD requests pass through
+/// unrecorded. The request ID is taken from `X-Request-Id` or generated, and echoed
+/// in the response. The error message of a failed request becomes its detail unless the
+/// handler gave one.
+pub async fn audit_request(
+    State((db, action)): State<(SharedDb, &'static str)>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if request.method() == Method::GET || request.method() == Method::HEAD {
+        return next.run(request).await;
+    }
+
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty() && value.len() <= MAX_REQUEST_ID_LEN)
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let context = AuditContext::default();
+    request.extensions_mut().insert(context.clone());
+
+    let mut response = next.run(request).await;
+
+    let actor = response
+        .extensions()
+        .get::<Principal>()
+        .map(|principal| principal.name.clone())
+        .unwrap_or_else(|| "anonymous".to_string());
+    let status = response.status();
+    let (target, mut detail) = context.take();
+    if !status.is_success() && detail.is_none() {
+        let (parts, body) = response.into_parts();
+        let message = to_bytes(body, MAX_ERROR_BODY).await.unwrap_or_default();
+        detail = (!message.is_empty()).then(|| String::from_utf8_lossy(&message).into_owned());
+        response = Response::from_parts(parts, Body::from(message));
+    }
+    let event = AuditEvent {
+        id: 0,
+        request_id: request_id.clone(),
+        actor,
+        action: action.to_string(),
+        target,
+        outcome: outcome(status.is_success()).to_string(),
+        status: Some(status.as_u16() as i64),
+        detail,
+        occurred_at: now(),
+    };
+    if let Err(e) = db.insert_audit_event(&event).await {
+        eprintln!("Failed to record audit event {} ({}): {}", action, request_id, e);
+    }
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    response
+}
+
+/// `route` behind the `role` check, with its mutating requests recorded as `action` whether the
+/// caller was let through or not
+pub fn audited<S>(
+    route: MethodRouter<S>,
+    authenticator: &SharedAuthenticator,
+    role: Role,
+    db: &SharedDb,
+    action: &'static str,
+) -> MethodRouter<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    route
+        .route_layer(middleware::from_fn_with_state((authenticator.clone(), role), require_role))
+        .route_layer(middleware::from_fn_with_state((db.clone(), action), audit_request))
+}
+
+/// Record an administration command run from the command line
+pub async fn record_command(
+    db: &Database,
+    action: &str,
+    target: Option<String>,
+    error: Option<String>,
+) -> anyhow::Result<()> {
+    let event = AuditEvent {
+        id: 0,
+        request_id: Uuid::new_v4().to_string(),
+        actor: "cli".to_string(),
+        action: action.to_string(),
+        target,
+        outcome: outcome(error.is_none()).to_string(),
+        status: None,
+        detail: error,
+        occurred_at: now(),
+    };
+    db.insert_audit_event(&event).await?;
+
+    Ok(())
+}
+
+fn outcome(success: bool) -> &'static str {
+    if success { "success" } else { "failure" }
+}
+
+fn now() -> String {
+    Utc::now().format(OCCURRED_AT_FORMAT).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::middleware::{Authenticator, API_KEY_HEADER};
+    use crate::auth::{generate_key, hash_key, key_prefix, AuthConfig};
+    use crate::config::DatabaseConfig;
+    use crate::database::AuditFilter;
+    use axum::{routing::post, Router};
+    use tower::ServiceExt;
+
+    async fn api_key(db: &Database, name: &str, role: &str) -> String {
+        let key = generate_key();
+        db.insert_api_key(name, role, &key_prefix(&key), &hash_key(&key)).await.unwrap();
+        key
+    }
+
+    #[tokio::test]
+    async fn records_refused_attempts() {
+        let config = DatabaseConfig {
+            url: "sqlite::memory:".to_string(),
+            max_connections: 1,
+        };
+        let db: SharedDb = Arc::new(Database::new(&config).await.unwrap());
+        let operator = api_key(&db, "gate", "operator").await;
+        let admin = api_key(&db, "ops", "admin").await;
+        let authenticator: SharedAuthenticator = Arc::new(Authenticator::new(db.clone(), AuthConfig::default()).unwrap());
+        let app = Router::new().route(
+            "/purge",
+            audited(post(|| async { "purged" }), &authenticator, Role::Admin, &db, "purge"),
+        );
+
+        for key in [None, Some("not-a-key"), Some(operator.as_str()), Some(admin.as_str())] {
+            let mut request = Request::builder().method(Method::POST).uri("/purge");
+            if let Some(key) = key {
+                request = request.header(API_KEY_HEADER, key);
+            }
+            let response = app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+            assert!(response.headers().contains_key(REQUEST_ID_HEADER));
+        }
+
+        let filter = AuditFilter {
+            action: Some("purge".to_string()),
+            limit: 10,
+            ..AuditFilter::default()
+        };
+        let mut events = db.get_audit_events(&filter).await.unwrap();
+        events.reverse();
+        let recorded: Vec<(&str, &str, Option<i64>)> = events
+            .iter()
+            .map(|event| (event.actor.as_str(), event.outcome.as_str(), event.status))
+            .collect();
+        assert_eq!(
+            recorded,
+            [
+                ("anonymous", "failure", Some(401)),
+                ("anonymous", "failure", Some(401)),
+                ("gate", "failure", Some(403)),
+                ("ops", "success", Some(200)),
+            ]
+        );
+        assert!(events[2].detail.as_deref().unwrap().contains("requires the admin role"));
+    }
+}
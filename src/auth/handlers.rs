@@ -0,0 +1,211 @@
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{generate_key, hash_key, key_prefix, Principal, Role};
+use crate::audit::AuditContext;
+use crate::database::ApiKey;
+use crate::face_recognition::handlers::{ApiResponse, SharedDb, SharedModel};
+
+/// Request body for creating an API key
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateApiKeyRequest {
+    /// Who or what the key is for, e.g. `front-desk-kiosk`
+    name: String,
+    role: Role,
+}
+
+/// Response for a created API key
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreateApiKeyResponse {
+    /// The key to send in the `X-API-Key` header. It is not stored and cannot be shown again.
+    api_key: String,
+    details: ApiKey,
+}
+
+/// Create an API key
+///
+/// Generates a random key with the given role. Only a hash of the key is stored, so the
+/// response is the only place the key appears.
+#[utoipa::path(
+    post,
+    path = "/admin/api-keys",
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 200, description = "API key created", body = ApiResponse<CreateApiKeyResponse>),
+        (status = 400, description = "Invalid name"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Admin"
+)]
+pub async fn create_api_key(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+    Extension(principal): Extension<Principal>,
+    Extension(audit): Extension<AuditContext>,
+    Json(request): Json<CreateApiKeyRequest>,
+) -> Result<Json<ApiResponse<CreateApiKeyResponse>>, (StatusCode, String)> {
+    let name = request.name.trim();
+    audit.target(name);
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Name must not be empty".to_string()));
+    }
+
+    let key = generate_key();
+    let id = db
+        .insert_api_key(name, request.role.as_str(), &key_prefix(&key), &hash_key(&key))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    println!("API key {} ({}) for {} created by {}", id, request.role, name, principal.name);
+    audit.detail(format!("{} API key {}", request.role, id));
+
+    let details = db
+        .get_api_key(id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+        .ok_or((StatusCode::INTERNAL_SERVER_ERROR, format!("API key {} disappeared", id)))?;
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Created {} API key for {}", request.role, name),
+        data: Some(CreateApiKeyResponse { api_key: key, details }),
+    }))
+}
+
+/// List API keys
+///
+/// Returns every key, including revoked ones, without the secret part.
+#[utoipa::path(
+    get,
+    path = "/admin/api-keys",
+    responses(
+        (status = 200, description = "API keys", body = ApiResponse<Vec<ApiKey>>),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Admin"
+)]
+pub async fn list_api_keys(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+) -> Result<Json<ApiResponse<Vec<ApiKey>>>, (StatusCode, String)> {
+    let keys = db
+        .get_api_keys()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Found {} API keys", keys.len()),
+        data: Some(keys),
+    }))
+}
+
+/// Revoke an API key
+///
+/// The key stops working immediately. Revoked keys stay listed for reference. The key used to
+/// make the request cannot revoke itself.
+#[utoipa::path(
+    delete,
+    path = "/admin/api-keys/{id}",
+    params(("id" = i64, Path, description = "API key ID")),
+    responses(
+        (status = 200, description = "API key revoked", body = ApiResponse<ApiKey>),
+        (status = 400, description = "Key used for this request"),
+        (status = 404, description = "API key not found or already revoked"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Admin"
+)]
+pub async fn revoke_api_key(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+    Extension(principal): Extension<Principal>,
+    Extension(audit): Extension<AuditContext>,
+    Path(id): Path<i64>,
+) -> Result<Json<ApiResponse<ApiKey>>, (StatusCode, String)> {
+    audit.target(format!("api_key/{}", id));
+    if principal.key_id == Some(id) {
+        return Err((StatusCode::BAD_REQUEST, "Cannot revoke the API key used for this request".to_string()));
+    }
+
+    let revoked = db
+        .revoke_api_key(id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    if !revoked {
+        return Err((StatusCode::NOT_FOUND, format!("Active API key not found: {}", id)));
+    }
+    println!("API key {} revoked by {}", id, principal.name);
+
+    let details = db
+        .get_api_key(id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Revoked API key {}", id),
+        data: details,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::audited;
+    use crate::auth::middleware::{Authenticator, API_KEY_HEADER};
+    use crate::auth::AuthConfig;
+    use crate::config::Config;
+    use crate::database::Database;
+    use crate::face_recognition::model::FaceRecognitionModel;
+    use axum::{body::Body, http::Request, routing::delete, Router};
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    // The handlers share the face model's state, so this needs OpenCV and its haarcascade
+    #[tokio::test]
+    async fn keys_cannot_revoke_themselves() {
+        let mut config = Config::default();
+        config.paths.model_path = std::env::temp_dir()
+            .join(format!("api-keys-{}/lbph.yml", Uuid::new_v4()))
+            .to_string_lossy()
+            .to_string();
+        config.database.url = "sqlite::memory:".to_string();
+        config.database.max_connections = 1;
+        let db: SharedDb = Arc::new(Database::new(&config.database).await.unwrap());
+        let model: SharedModel = Arc::new(Mutex::new(FaceRecognitionModel::new(&config).unwrap()));
+
+        let mut keys = Vec::new();
+        for name in ["ops", "old-kiosk"] {
+            let key = generate_key();
+            let id = db.insert_api_key(name, "admin", &key_prefix(&key), &hash_key(&key)).await.unwrap();
+            keys.push((id, key));
+        }
+        let authenticator = Arc::new(Authenticator::new(db.clone(), AuthConfig::default()).unwrap());
+        let app = Router::new()
+            .route("/admin/api-keys/:id", audited(delete(revoke_api_key), &authenticator, Role::Admin, &db, "revoke_api_key"))
+            .with_state((model, db.clone()));
+        let revoke = |id: i64, key: &str| {
+            let request = Request::builder()
+                .method("DELETE")
+                .uri(format!("/admin/api-keys/{}", id))
+                .header(API_KEY_HEADER, key)
+                .body(Body::empty())
+                .unwrap();
+            let app = app.clone();
+            async move { app.oneshot(request).await.unwrap().status() }
+        };
+        let ((ops, ops_key), (kiosk, kiosk_key)) = (keys[0].clone(), keys[1].clone());
+
+        assert_eq!(revoke(ops, &ops_key).await, StatusCode::BAD_REQUEST);
+        assert!(db.get_api_key(ops).await.unwrap().unwrap().revoked_at.is_none());
+
+        assert_eq!(revoke(kiosk, &ops_key).await, StatusCode::OK);
+        assert!(db.get_api_key(kiosk).await.unwrap().unwrap().revoked_at.is_some());
+        assert_eq!(revoke(kiosk, &ops_key).await, StatusCode::NOT_FOUND);
+        assert_eq!(revoke(ops, &kiosk_key).await, StatusCode::UNAUTHORIZED);
+    }
+}
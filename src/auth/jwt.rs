@@ -0,0 +1,464 @@
+use anyhow::Result;
+use axum::http::StatusCode;
+use jsonwebtoken::{
+    decode, decode_header, encode,
+    errors::ErrorKind,
+    jwk::{AlgorithmParameters, JwkSet, KeyAlgorithm},
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+
+use super::{Principal, Role};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JwtConfig {
+    /// Shared secret for HS256 tokens
+    pub hs256_secret: Option<String>,
+    /// JWKS file with the RS256 and EdDSA public keys tokens may be signed with
+    pub jwks_path: Option<String>,
+    /// Required `iss` claim
+    pub issuer: Option<String>,
+    /// Required `aud` claim
+    pub audience: Option<String>,
+    /// Claim holding a role name or a list of role names
+    pub role_claim: String,
+    /// Role names used in tokens and the roles they grant; other names are ignored
+    pub role_map: BTreeMap<String, Role>,
+    /// Scope every token must list in its `scope` (space-separated) or `scp` claim
+    pub required_scope: Option<String>,
+    /// Seconds of clock skew tolerated on `exp` and `nbf`
+    pub leeway_secs: u64,
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        Self {
+            hs256_secret: None,
+            jwks_path: None,
+            issuer: None,
+            audience: None,
+            role_claim: "roles".to_string(),
+            role_map: [Role::Admin, Role::Operator, Role::Viewer]
+                .into_iter()
+                .map(|role| (role.as_str().to_string(), role))
+                .collect(),
+            required_scope: None,
+            leeway_secs: 30,
+        }
+    }
+}
+
+impl JwtConfig {
+    /// Bearer tokens are accepted once a secret or a key file is configured
+    pub fn enabled(&self) -> bool {
+        self.hs256_secret.is_some() || self.jwks_path.is_some()
+    }
+}
+
+// A public key from the JWKS file
+struct PublicKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+/// Checks bearer tokens against locally configured keys; never fetches anything
+pub struct JwtVerifier {
+    config: JwtConfig,
+    hs256: Option<DecodingKey>,
+    public_keys: Vec<PublicKey>,
+}
+
+impl JwtVerifier {
+    pub fn new(config: JwtConfig) -> Result<Self> {
+        let hs256 = config
+            .hs256_secret
+            .as_ref()
+            .map(|secret| DecodingKey::from_secret(secret.as_bytes()));
+
+        let public_keys = match &config.jwks_path {
+            Some(path) => load_jwks(path)?,
+            None => Vec::new(),
+        };
+
+        Ok(Self {
+            config,
+            hs256,
+            public_keys,
+        })
+    }
+
+    pub fn verify(&self, token: &str) -> Result<Principal, (StatusCode, String)> {
+        let unauthorized = |message: String| (StatusCode::UNAUTHORIZED, message);
+
+        let header = decode_header(token).map_err(|e| unauthorized(format!("Invalid token: {}", e)))?;
+        let key = match header.alg {
+            Algorithm::HS256 => self
+                .hs256
+                .as_ref()
+                .ok_or_else(|| unauthorized("HS256 tokens are not accepted".to_string()))?,
+            Algorithm::RS256 | Algorithm::EdDSA => self
+                .find_public_key(header.alg, header.kid.as_deref())
+                .ok_or_else(|| unauthorized(format!("No {:?} key matches the token", header.alg)))?,
+            other => return Err(unauthorized(format!("Unsupported token algorithm {:?}", other))),
+        };
+
+        // A configured issuer or audience is only checked when the claim is there, so it must
+        // also be required
+        let mut required = vec!["exp", "sub"];
+        let mut validation = Validation::new(header.alg);
+        validation.leeway = self.config.leeway_secs;
+        if let Some(issuer) = &self.config.issuer {
+            validation.set_issuer(&[issuer]);
+            required.push("iss");
+        }
+        match &self.config.audience {
+            Some(audience) => {
+                validation.set_audience(&[audience]);
+                required.push("aud");
+            }
+            None => validation.validate_aud = false,
+        }
+        validation.set_required_spec_claims(&required);
+
+        let claims = decode::<Map<String, Value>>(token, key, &validation)
+            .map_err(|e| match e.kind() {
+                ErrorKind::ExpiredSignature => unauthorized("Token expired".to_string()),
+                ErrorKind::InvalidIssuer => unauthorized("Token issuer not accepted".to_string()),
+                ErrorKind::InvalidAudience => unauthorized("Token audience not accepted".to_string()),
+                _ => unauthorized(format!("Invalid token: {}", e)),
+            })?
+            .claims;
+
+        if let Some(scope) = &self.config.required_scope
+            && !token_scopes(&claims).contains(&scope.as_str())
+        {
+            return Err((StatusCode::FORBIDDEN, format!("Token lacks the {} scope", scope)));
+        }
+
+        let role = claim_strings(claims.get(&self.config.role_claim))
+            .into_iter()
+            .filter_map(|name| self.config.role_map.get(name).copied())
+            .max()
+            .ok_or_else(|| {
+                (
+                    StatusCode::FORBIDDEN,
+                    format!("Token {} claim carries no known role", self.config.role_claim),
+                )
+            })?;
+
+        Ok(Principal {
+            key_id: None,
+            name: claims.get("sub").and_then(Value::as_str).unwrap_or_default().to_string(),
+            role,
+        })
+    }
+
+    // With a `kid` the key must carry it; without one the algorithm must be unambiguous
+    fn find_public_key(&self, algorithm: Algorithm, kid: Option<&str>) -> Option<&DecodingKey> {
+        let mut candidates = self.public_keys.iter().filter(|key| key.algorithm == algorithm);
+
+        match kid {
+            Some(kid) => candidates.find(|key| key.kid.as_deref() == Some(kid)).map(|key| &key.key),
+            None => match (candidates.next(), candidates.next()) {
+                (Some(key), None) => Some(&key.key),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Sign an HS256 token with the configured secret, for testing and internal callers
+pub fn issue_hs256(config: &JwtConfig, subject: &str, role: &str, ttl_secs: u64) -> Result<String> {
+    let secret = config
+        .hs256_secret
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("auth.jwt.hs256_secret is not configured"))?;
+
+    let now = jsonwebtoken::get_current_timestamp();
+    let mut claims = Map::new();
+    claims.insert("sub".to_string(), Value::from(subject));
+    claims.insert("iat".to_string(), Value::from(now));
+    claims.insert("exp".to_string(), Value::from(now + ttl_secs));
+    claims.insert(config.role_claim.clone(), Value::from(vec![role]));
+    if let Some(issuer) = &config.issuer {
+        claims.insert("iss".to_string(), Value::from(issuer.as_str()));
+    }
+    if let Some(audience) = &config.audience {
+        claims.insert("aud".to_string(), Value::from(audience.as_str()));
+    }
+    if let Some(scope) = &config.required_scope {
+        claims.insert("scope".to_string(), Value::from(scope.as_str()));
+    }
+
+    Ok(encode(
+        &Header::new(Algorithm::HS256),
+        &claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )?)
+}
+
+// Keys for other algorithms are skipped so a provider's full JWKS can be used as is
+fn load_jwks(path: &str) -> Result<Vec<PublicKey>> {
+    let text = fs::read_to_string(path).map_err(|e| anyhow::anyhow!("Failed to read JWKS {}: {}", path, e))?;
+    let set: JwkSet = serde_json::from_str(&text).map_err(|e| anyhow::anyhow!("Invalid JWKS {}: {}", path, e))?;
+
+    let mut keys = Vec::new();
+    for jwk in &set.keys {
+        let algorithm = match (jwk.common.key_algorithm, &jwk.algorithm) {
+            (Some(KeyAlgorithm::RS256), _) | (None, AlgorithmParameters::RSA(_)) => Algorithm::RS256,
+            (Some(KeyAlgorithm::EdDSA), _) | (None, AlgorithmParameters::OctetKeyPair(_)) => Algorithm::EdDSA,
+            _ => {
+                println!("Skipping JWKS key {:?}: only RS256 and EdDSA keys are used", jwk.common.key_id);
+                continue;
+            }
+        };
+
+        let key = DecodingKey::from_jwk(jwk)
+            .map_err(|e| anyhow::anyhow!("Invalid JWKS key {:?} in {}: {}", jwk.common.key_id, path, e))?;
+        keys.push(PublicKey {
+            kid: jwk.common.key_id.clone(),
+            algorithm,
+            key,
+        });
+    }
+
+    if keys.is_empty() {
+        return Err(anyhow::anyhow!("JWKS {} has no RS256 or EdDSA keys", path));
+    }
+
+    Ok(keys)
+}
+
+// `scope` is a space-separated string (RFC 8693), `scp` often a list
+fn token_scopes(claims: &Map<String, Value>) -> Vec<&str> {
+    match claims.get("scope") {
+        Some(Value::String(scope)) => scope.split_whitespace().collect(),
+        _ => claim_strings(claims.get("scp")),
+    }
+}
+
+fn claim_strings(value: Option<&Value>) -> Vec<&str> {
+    match value {
+        Some(Value::String(s)) => vec![s.as_str()],
+        Some(Value::Array(items)) => items.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Throwaway keys made for these tests; the JWKS also lists an ES256 key that is skipped
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/jwt");
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+    fn hs256_config() -> JwtConfig {
+        JwtConfig {
+            hs256_secret: Some(SECRET.to_string()),
+            issuer: Some("https://auth.example.com".to_string()),
+            audience: Some("face-api".to_string()),
+            ..JwtConfig::default()
+        }
+    }
+
+    fn jwks_config() -> JwtConfig {
+        JwtConfig {
+            jwks_path: Some(format!("{}/jwks.json", FIXTURES)),
+            ..JwtConfig::default()
+        }
+    }
+
+    fn claims(roles: &[&str], exp_offset: i64) -> Map<String, Value> {
+        let now = jsonwebtoken::get_current_timestamp() as i64;
+        let mut claims = Map::new();
+        claims.insert("sub".to_string(), Value::from("alice"));
+        claims.insert("exp".to_string(), Value::from(now + exp_offset));
+        claims.insert("iss".to_string(), Value::from("https://auth.example.com"));
+        claims.insert("aud".to_string(), Value::from("face-api"));
+        claims.insert("roles".to_string(), Value::from(roles.to_vec()));
+        claims
+    }
+
+    fn sign_hs256(claims: &Map<String, Value>) -> String {
+        encode(&Header::new(Algorithm::HS256), claims, &EncodingKey::from_secret(SECRET.as_bytes())).unwrap()
+    }
+
+    fn sign_with(file: &str, algorithm: Algorithm, kid: Option<&str>, claims: &Map<String, Value>) -> String {
+        let pem = fs::read(format!("{}/{}", FIXTURES, file)).unwrap();
+        let key = match algorithm {
+            Algorithm::RS256 => EncodingKey::from_rsa_pem(&pem).unwrap(),
+            _ => EncodingKey::from_ed_pem(&pem).unwrap(),
+        };
+        let mut header = Header::new(algorithm);
+        header.kid = kid.map(str::to_string);
+
+        encode(&header, claims, &key).unwrap()
+    }
+
+    fn status(verifier: &JwtVerifier, token: &str) -> StatusCode {
+        verifier.verify(token).unwrap_err().0
+    }
+
+    #[test]
+    fn accepts_hs256() {
+        let verifier = JwtVerifier::new(hs256_config()).unwrap();
+
+        let principal = verifier.verify(&sign_hs256(&claims(&["operator"], 60))).unwrap();
+        assert_eq!(principal.name, "alice");
+        assert_eq!(principal.role, Role::Operator);
+        assert_eq!(principal.key_id, None);
+    }
+
+    #[test]
+    fn accepts_issued_token() {
+        let config = JwtConfig {
+            required_scope: Some("faces".to_string()),
+            ..hs256_config()
+        };
+        let verifier = JwtVerifier::new(config.clone()).unwrap();
+
+        let token = issue_hs256(&config, "kiosk", "viewer", 60).unwrap();
+        let principal = verifier.verify(&token).unwrap();
+        assert_eq!(principal.name, "kiosk");
+        assert_eq!(principal.role, Role::Viewer);
+    }
+
+    #[test]
+    fn rejects_expired_token() {
+        let verifier = JwtVerifier::new(hs256_config()).unwrap();
+
+        // Within the leeway the token still passes
+        assert!(verifier.verify(&sign_hs256(&claims(&["admin"], -10))).is_ok());
+        assert_eq!(status(&verifier, &sign_hs256(&claims(&["admin"], -120))), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn rejects_wrong_issuer_and_audience() {
+        let verifier = JwtVerifier::new(hs256_config()).unwrap();
+
+        let mut wrong_issuer = claims(&["admin"], 60);
+        wrong_issuer.insert("iss".to_string(), Value::from("https://evil.example.com"));
+        assert_eq!(status(&verifier, &sign_hs256(&wrong_issuer)), StatusCode::UNAUTHORIZED);
+
+        let mut wrong_audience = claims(&["admin"], 60);
+        wrong_audience.insert("aud".to_string(), Value::from("other-api"));
+        assert_eq!(status(&verifier, &sign_hs256(&wrong_audience)), StatusCode::UNAUTHORIZED);
+
+        let mut no_issuer = claims(&["admin"], 60);
+        no_issuer.remove("iss");
+        assert_eq!(status(&verifier, &sign_hs256(&no_issuer)), StatusCode::UNAUTHORIZED);
+
+        let mut no_audience = claims(&["admin"], 60);
+        no_audience.remove("aud");
+        assert_eq!(status(&verifier, &sign_hs256(&no_audience)), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn rejects_wrong_secret() {
+        let verifier = JwtVerifier::new(hs256_config()).unwrap();
+
+        let token = encode(
+            &Header::new(Algorithm::HS256),
+            &claims(&["admin"], 60),
+            &EncodingKey::from_secret(b"another-secret-of-at-least-32-bytes"),
+        )
+        .unwrap();
+        assert_eq!(status(&verifier, &token), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn requires_scope() {
+        let verifier = JwtVerifier::new(JwtConfig {
+            required_scope: Some("faces".to_string()),
+            ..hs256_config()
+        })
+        .unwrap();
+
+        let mut unscoped = claims(&["admin"], 60);
+        assert_eq!(status(&verifier, &sign_hs256(&unscoped)), StatusCode::FORBIDDEN);
+        unscoped.insert("scope".to_string(), Value::from("openid profile"));
+        assert_eq!(status(&verifier, &sign_hs256(&unscoped)), StatusCode::FORBIDDEN);
+
+        let mut scope = claims(&["admin"], 60);
+        scope.insert("scope".to_string(), Value::from("openid faces"));
+        assert!(verifier.verify(&sign_hs256(&scope)).is_ok());
+
+        let mut scp = claims(&["admin"], 60);
+        scp.insert("scp".to_string(), Value::from(vec!["faces"]));
+        assert!(verifier.verify(&sign_hs256(&scp)).is_ok());
+    }
+
+    #[test]
+    fn maps_roles_to_the_highest() {
+        let verifier = JwtVerifier::new(JwtConfig {
+            role_claim: "groups".to_string(),
+            role_map: [("face-admins", Role::Admin), ("staff", Role::Viewer), ("guards", Role::Operator)]
+                .into_iter()
+                .map(|(name, role)| (name.to_string(), role))
+                .collect(),
+            ..hs256_config()
+        })
+        .unwrap();
+
+        let mut groups = claims(&[], 60);
+        groups.insert("groups".to_string(), Value::from(vec!["staff", "guards", "admin"]));
+        assert_eq!(verifier.verify(&sign_hs256(&groups)).unwrap().role, Role::Operator);
+
+        groups.insert("groups".to_string(), Value::from("face-admins"));
+        assert_eq!(verifier.verify(&sign_hs256(&groups)).unwrap().role, Role::Admin);
+
+        // Default role names mean nothing once the map is replaced
+        groups.insert("groups".to_string(), Value::from(vec!["admin", "operator"]));
+        assert_eq!(status(&verifier, &sign_hs256(&groups)), StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn selects_jwks_key_by_kid() {
+        let verifier = JwtVerifier::new(jwks_config()).unwrap();
+        assert_eq!(verifier.public_keys.len(), 3);
+
+        let claims = claims(&["viewer"], 60);
+        let token = sign_with("rsa-2.pem", Algorithm::RS256, Some("rsa-2"), &claims);
+        assert_eq!(verifier.verify(&token).unwrap().role, Role::Viewer);
+
+        // Signed by one key but naming the other
+        let token = sign_with("rsa-1.pem", Algorithm::RS256, Some("rsa-2"), &claims);
+        assert_eq!(status(&verifier, &token), StatusCode::UNAUTHORIZED);
+
+        let token = sign_with("rsa-1.pem", Algorithm::RS256, Some("rsa-3"), &claims);
+        assert_eq!(status(&verifier, &token), StatusCode::UNAUTHORIZED);
+
+        let token = sign_with("ed-1.pem", Algorithm::EdDSA, Some("ed-1"), &claims);
+        assert!(verifier.verify(&token).is_ok());
+    }
+
+    #[test]
+    fn needs_kid_when_algorithm_is_ambiguous() {
+        let verifier = JwtVerifier::new(jwks_config()).unwrap();
+        let claims = claims(&["viewer"], 60);
+
+        // Two RS256 keys: without a kid neither is picked
+        let token = sign_with("rsa-1.pem", Algorithm::RS256, None, &claims);
+        assert_eq!(status(&verifier, &token), StatusCode::UNAUTHORIZED);
+
+        // A single EdDSA key is used without a kid
+        let token = sign_with("ed-1.pem", Algorithm::EdDSA, None, &claims);
+        assert!(verifier.verify(&token).is_ok());
+    }
+
+    #[test]
+    fn rejects_algorithms_without_keys() {
+        let verifier = JwtVerifier::new(jwks_config()).unwrap();
+        assert_eq!(status(&verifier, &sign_hs256(&claims(&["admin"], 60))), StatusCode::UNAUTHORIZED);
+
+        let verifier = JwtVerifier::new(hs256_config()).unwrap();
+        let token = sign_with("ed-1.pem", Algorithm::EdDSA, None, &claims(&["admin"], 60));
+        assert_eq!(status(&verifier, &token), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,221 @@
+use anyhow::Result;
+use axum::{
+    extract::{Request, State},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+use super::jwt::JwtVerifier;
+use super::{hash_key, AuthConfig, Principal, Role};
+use crate::face_recognition::handlers::SharedDb;
+
+/// Header clients send their API key in
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+/// Resolves the caller of a request from its credentials
+pub struct Authenticator {
+    db: SharedDb,
+    config: AuthConfig,
+    jwt: Option<JwtVerifier>,
+}
+
+pub type SharedAuthenticator = Arc<Authenticator>;
+
+impl Authenticator {
+    /// Fails if the JWKS file cannot be read
+    pub fn new(db: SharedDb, config: AuthConfig) -> Result<Self> {
+        let jwt = if config.jwt.enabled() {
+            Some(JwtVerifier::new(config.jwt.clone())?)
+        } else {
+            None
+        };
+
+        Ok(Self { db, config, jwt })
+    }
+
+    /// Accepts `Authorization: Bearer <jwt>` or an `X-API-Key` header
+    pub async fn authenticate(&self, headers: &HeaderMap) -> Result<Principal, (StatusCode, String)> {
+        if !self.config.enabled {
+            return Ok(Principal {
+                key_id: None,
+                name: "anonymous".to_string(),
+                role: Role::Admin,
+            });
+        }
+
+        let bearer = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if let Some(token) = bearer {
+            let verifier = self
+                .jwt
+                .as_ref()
+                .ok_or((StatusCode::UNAUTHORIZED, "Bearer tokens are not enabled".to_string()))?;
+            return verifier.verify(token.trim());
+        }
+
+        let key = headers
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| !value.is_empty())
+            .ok_or((
+                StatusCode::UNAUTHORIZED,
+                format!("Missing {} header or bearer token", API_KEY_HEADER),
+            ))?;
+
+        let api_key = self
+            .db
+            .find_api_key(&hash_key(key))
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+            .ok_or((StatusCode::UNAUTHORIZED, "Invalid or revoked API key".to_string()))?;
+
+        let role = api_key
+            .role
+            .parse()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("API key {}: {}", api_key.id, e)))?;
+
+        if let Err(e) = self.db.touch_api_key(api_key.id).await {
+            eprintln!("Failed to record use of API key {}: {}", api_key.id, e);
+        }
+
+        Ok(Principal {
+            key_id: Some(api_key.id),
+            name: api_key.name,
+            role,
+        })
+    }
+}
+
+/// Let the request through only if its caller has at least `role`
+///
+/// Used with `axum::middleware::from_fn_with_state((authenticator, role), require_role)` on each
+/// group of routes. The caller is added to the request extensions as a [`Principal`], and to
+/// the response's, so an audit layer around the check also knows who it refused.
+pub async fn require_role(
+    State((authenticator, role)): State<(SharedAuthenticator, Role)>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, String)> {
+    let principal = authenticator.authenticate(request.headers()).await?;
+
+    if principal.role < role {
+        let message = format!("This endpoint requires the {} role, API key has {}", role, principal.role);
+        let mut response = (StatusCode::FORBIDDEN, message).into_response();
+        response.extensions_mut().insert(principal);
+        return Ok(response);
+    }
+
+    request.extensions_mut().insert(principal.clone());
+    let mut response = next.run(request).await;
+    response.extensions_mut().insert(principal);
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{generate_key, key_prefix};
+    use crate::config::DatabaseConfig;
+    use crate::database::Database;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    async fn api_key(db: &Database, name: &str, role: Role) -> (i64, String) {
+        let key = generate_key();
+        let id = db.insert_api_key(name, role.as_str(), &key_prefix(&key), &hash_key(&key)).await.unwrap();
+        (id, key)
+    }
+
+    // One route per role, each answering with the caller's name
+    fn app(authenticator: SharedAuthenticator) -> Router {
+        let mut app = Router::new();
+        for (path, role) in [("/viewer", Role::Viewer), ("/operator", Role::Operator), ("/admin", Role::Admin)] {
+            app = app.route(
+                path,
+                get(|axum::Extension(principal): axum::Extension<Principal>| async move { principal.name })
+                    .route_layer(middleware::from_fn_with_state((authenticator.clone(), role), require_role)),
+            );
+        }
+        app
+    }
+
+    async fn status(app: &Router, path: &str, key: Option<&str>) -> StatusCode {
+        let mut request = Request::builder().uri(path);
+        if let Some(key) = key {
+            request = request.header(API_KEY_HEADER, key);
+        }
+        app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap().status()
+    }
+
+    async fn database() -> SharedDb {
+        let config = DatabaseConfig {
+            url: "sqlite::memory:".to_string(),
+            max_connections: 1,
+        };
+        Arc::new(Database::new(&config).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn roles_gate_routes() {
+        let db = database().await;
+        let (_, viewer) = api_key(&db, "dashboard", Role::Viewer).await;
+        let (_, operator) = api_key(&db, "gate", Role::Operator).await;
+        let (_, admin) = api_key(&db, "ops", Role::Admin).await;
+        let app = app(Arc::new(Authenticator::new(db, AuthConfig::default()).unwrap()));
+
+        let expected = [
+            (&viewer, [StatusCode::OK, StatusCode::FORBIDDEN, StatusCode::FORBIDDEN]),
+            (&operator, [StatusCode::OK, StatusCode::OK, StatusCode::FORBIDDEN]),
+            (&admin, [StatusCode::OK, StatusCode::OK, StatusCode::OK]),
+        ];
+        for (key, statuses) in expected {
+            for (path, expected) in ["/viewer", "/operator", "/admin"].into_iter().zip(statuses) {
+                assert_eq!(status(&app, path, Some(key)).await, expected, "{} with {}", path, key_prefix(key));
+            }
+        }
+
+        // A refusal still names the caller for the audit log
+        let request = Request::builder().uri("/admin").header(API_KEY_HEADER, &viewer).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.extensions().get::<Principal>().unwrap().name, "dashboard");
+    }
+
+    #[tokio::test]
+    async fn unknown_and_revoked_keys_are_unauthorized() {
+        let db = database().await;
+        let (id, key) = api_key(&db, "old-kiosk", Role::Admin).await;
+        let app = app(Arc::new(Authenticator::new(db.clone(), AuthConfig::default()).unwrap()));
+
+        assert_eq!(status(&app, "/viewer", Some(&key)).await, StatusCode::OK);
+        assert!(db.revoke_api_key(id).await.unwrap());
+        assert_eq!(status(&app, "/viewer", Some(&key)).await, StatusCode::UNAUTHORIZED);
+
+        assert_eq!(status(&app, "/viewer", Some(&generate_key())).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(&app, "/viewer", Some("")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(&app, "/viewer", None).await, StatusCode::UNAUTHORIZED);
+
+        // Bearer tokens are refused, not ignored, while JWT is not configured
+        let request = Request::builder()
+            .uri("/viewer")
+            .header(AUTHORIZATION, "Bearer token")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn disabled_auth_lets_everyone_in_as_admin() {
+        let config = AuthConfig {
+            enabled: false,
+            ..AuthConfig::default()
+        };
+        let app = app(Arc::new(Authenticator::new(database().await, config).unwrap()));
+
+        assert_eq!(status(&app, "/admin", None).await, StatusCode::OK);
+        assert_eq!(status(&app, "/admin", Some("not-a-key")).await, StatusCode::OK);
+    }
+}
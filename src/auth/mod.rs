@@ -0,0 +1,137 @@
+pub mod handlers;
+pub mod jwt;
+pub mod middleware;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use jwt::JwtConfig;
+
+/// Prefix of generated API keys, so leaked keys are easy to recognize
+const KEY_PREFIX: &str = "frk_";
+
+// Characters of a key stored in clear to tell keys apart in listings
+const KEY_PREFIX_LEN: usize = 12;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Require an API key or bearer token on every endpoint except `/` and the Swagger UI
+    pub enabled: bool,
+    pub jwt: JwtConfig,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            jwt: JwtConfig::default(),
+        }
+    }
+}
+
+/// What an API key may do. Each role includes the permissions of the roles below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Read job results, clusters and calibration reports
+    Viewer,
+    /// Detect, verify and anonymize faces, analyze videos and give feedback
+    Operator,
+    /// Enroll and delete users, change thresholds, manage API keys
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Operator => "operator",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "viewer" => Ok(Self::Viewer),
+            "operator" => Ok(Self::Operator),
+            "admin" => Ok(Self::Admin),
+            other => Err(anyhow::anyhow!("Unknown role '{}'. Use admin, operator or viewer", other)),
+        }
+    }
+}
+
+/// The caller of a request, added to the request extensions by the auth middleware
+#[derive(Debug, Clone)]
+pub struct Principal {
+    /// API key the request was made with; `None` for bearer tokens and when auth is disabled
+    pub key_id: Option<i64>,
+    /// API key name or token subject
+    pub name: String,
+    pub role: Role,
+}
+
+/// A new random API key. Only its hash is stored, so it must be shown to the caller right away.
+pub fn generate_key() -> String {
+    format!("{}{}{}", KEY_PREFIX, Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// The part of a key kept in clear
+pub fn key_prefix(key: &str) -> String {
+    key.chars().take(KEY_PREFIX_LEN).collect()
+}
+
+/// Keys are long random strings, so a plain SHA-256 is enough to store them
+pub fn hash_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_random_prefixed_and_hashed() {
+        let key = generate_key();
+        assert!(key.starts_with(KEY_PREFIX));
+        assert_eq!(key.len(), KEY_PREFIX.len() + 64);
+        assert_ne!(generate_key(), key);
+
+        assert_eq!(key_prefix(&key), key[..KEY_PREFIX_LEN]);
+        assert!(key_prefix(&key).starts_with(KEY_PREFIX));
+        assert_eq!(key_prefix("frk_ab"), "frk_ab");
+
+        assert_eq!(hash_key("abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(hash_key(&key), hash_key(&key));
+        assert_ne!(hash_key(&key), hash_key(&generate_key()));
+    }
+
+    #[test]
+    fn roles_include_the_roles_below_them() {
+        assert!(Role::Viewer < Role::Operator && Role::Operator < Role::Admin);
+
+        for role in [Role::Viewer, Role::Operator, Role::Admin] {
+            assert_eq!(role.to_string().parse::<Role>().unwrap(), role);
+        }
+        assert!("root".parse::<Role>().is_err());
+        assert!("Admin".parse::<Role>().is_err());
+    }
+}
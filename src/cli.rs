@@ -0,0 +1,634 @@
+use anyhow::Result;
+use opencv::{
+    core::Mat,
+    imgcodecs::{imread, IMREAD_COLOR},
+    prelude::*,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::audit::record_command;
+use crate::auth::{generate_key, hash_key, jwt::issue_hs256, key_prefix, Role};
+use crate::config::Config;
+use crate::database::Database;
+use crate::detections::apply_stored_threshold;
+use crate::encryption::{reencrypt, Cipher};
+use crate::erasure::{erase, DetectionErasure, ErasureRequest};
+use crate::evaluation;
+use crate::face_recognition::augment::TrainingStats;
+use crate::face_recognition::handlers::SharedDb;
+use crate::face_recognition::model::{is_image_file, to_gray, without_sources};
+use crate::face_recognition::FaceRecognitionModel;
+use crate::gallery::reconcile::{reconcile, ReconcileFix};
+use crate::images::{self, Gallery, SharedImageStore};
+use crate::liveness::passive::{lbp_histogram, LbpClassifier};
+use crate::retention::purge;
+use crate::tenants::{check_tenant_id, check_user_id, TenantPaths, DEFAULT_TENANT};
+
+// Image files grouped by user ID
+type UserImageFiles = Vec<(String, Vec<PathBuf>)>;
+
+const USAGE: &str = "Usage: face-recognition-be [COMMAND] [--tenant TENANT]
+
+Commands:
+  serve                          Run the HTTP API (default)
+  migrate [--status]             Apply pending database migrations (or only list them)
+  train                          Retrain the model from the stored gallery and save it
+  enroll <user_id> <files...>    Add photos for a user and retrain
+  identify <file>                Identify every face in an image
+  list-users                     List enrolled users with image and detection counts
+  delete-user <user_id>          Remove a user and their photos and retrain
+  erase <user_id> [--detections pseudonymize|delete]
+                                 Erase a user's images, rows and model entry, pseudonymize or
+                                 delete their detections, and record an erasure certificate
+  export <dir>                   Write the gallery into a directory (one folder per user)
+  import <dir>                   Enroll every user folder in a directory and retrain
+  reconcile [--fix adopt|delete [--delete-images]]
+                                 Compare face_images rows with the image store; --fix removes
+                                 missing rows and users without a detectable face (their images
+                                 too with --delete-images) and adopts or deletes orphans
+  purge [--dry-run]              Remove data the retention policy no longer keeps (or only count it)
+  create-tenant <tenant_id>      Register a tenant so gallery commands and tenant routes accept it
+  re-encrypt                     Rewrite stored images, models and unknown faces with encryption.key
+                                 (moving data off previous keys; decrypts everything when no key is set)
+  create-api-key <name> <role>   Create an API key with role admin, operator or viewer
+  issue-token <subject> <role> [ttl_secs]
+                                 Sign an HS256 bearer token with auth.jwt.hs256_secret (default 1 hour)
+  evaluate <dataset_dir> [--folds K | --split RATIO] [--roc-csv PATH] [--target-far RATE]
+                                 Measure accuracy offline
+  train-liveness <dataset_dir> <output.json>
+                                 Fit the passive print-attack classifier (liveness.lbp_classifier_path)
+                                 to photos in <dataset_dir>/live and <dataset_dir>/spoof
+
+Gallery commands (train to reconcile) work on the default tenant unless --tenant is given;
+other tenants must be registered with create-tenant first.
+Commands that change data are recorded in the audit log as actor cli.";
+
+/// Run an administration subcommand against the database and model directly
+pub async fn run(command: &str, args: &[String], config: &Config) -> Result<()> {
+    if command == "evaluate" {
+        return evaluation::run(args, config);
+    }
+
+    let (tenant, args) = split_tenant(args)?;
+    let result = dispatch(command, &args, &tenant, config).await;
+
+    if let Some((action, target)) = audited_command(command, &args, &tenant) {
+        let error = result.as_ref().err().map(|e| e.to_string());
+        let recorded = match Database::new(&config.database).await {
+            Ok(db) => record_command(&db, action, target, error).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = recorded {
+            eprintln!("Failed to record {} in the audit log: {}", action, e);
+        }
+    }
+
+    result
+}
+
+// Run one command with the tenant already taken out of the arguments
+async fn dispatch(command: &str, args: &[String], tenant: &str, config: &Config) -> Result<()> {
+    if tenant != DEFAULT_TENANT && !Database::new(&config.database).await?.tenant_exists(tenant).await? {
+        return Err(anyhow::anyhow!("Unknown tenant {}; register it with create-tenant first", tenant));
+    }
+
+    match (command, args) {
+        ("help" | "--help" | "-h", _) => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        ("train", []) => train(config, tenant).await,
+        ("enroll", [user_id, files @ ..]) if !files.is_empty() => enroll(config, tenant, user_id, files).await,
+        ("identify", [file]) => identify(config, tenant, file).await,
+        ("list-users", []) => list_users(config, tenant).await,
+        ("delete-user", [user_id]) => delete_user(config, tenant, user_id).await,
+        ("erase", [user_id]) => erase_user(config, tenant, user_id, DetectionErasure::default()).await,
+        ("erase", [user_id, flag, action]) if flag == "--detections" => {
+            erase_user(config, tenant, user_id, action.parse()?).await
+        }
+        ("export", [dir]) => export(config, tenant, dir).await,
+        ("import", [dir]) => import(config, tenant, dir).await,
+        ("reconcile", []) => reconcile_gallery(config, tenant, None).await,
+        ("reconcile", [flag, orphans, rest @ ..]) if flag == "--fix" && rest.len() <= 1 => {
+            let delete_images = match rest {
+                [] => false,
+                [flag] if flag == "--delete-images" => true,
+                _ => return Err(anyhow::anyhow!("Invalid command\n{}", USAGE)),
+            };
+            let fix = ReconcileFix {
+                orphans: orphans.parse()?,
+                delete_images,
+            };
+            reconcile_gallery(config, tenant, Some(fix)).await
+        }
+        ("migrate", []) => migrate(config, false).await,
+        ("migrate", [flag]) if flag == "--status" => migrate(config, true).await,
+        ("purge", []) => purge_data(config, false).await,
+        ("purge", [flag]) if flag == "--dry-run" => purge_data(config, true).await,
+        ("re-encrypt", []) => reencrypt_data(config).await,
+        ("create-tenant", [tenant_id]) => create_tenant(config, tenant_id).await,
+        ("create-api-key", [name, role]) => create_api_key(config, name, role).await,
+        ("issue-token", [subject, role, ttl @ ..]) if ttl.len() <= 1 => issue_token(config, subject, role, ttl.first()),
+        ("train-liveness", [dataset_dir, output]) => train_liveness(config, dataset_dir, output),
+        _ => Err(anyhow::anyhow!("Invalid command\n{}", USAGE)),
+    }
+}
+
+// Audit log action and target of a command that changes data; read-only commands, dry runs
+// and migrations are not recorded
+fn audited_command(command: &str, args: &[String], tenant: &str) -> Option<(&'static str, Option<String>)> {
+    let user = |user_id: &String| Some(format!("{}/{}", tenant, user_id));
+    match (command, args) {
+        ("train", []) => Some(("train", Some(tenant.to_string()))),
+        ("enroll", [user_id, ..]) => Some(("enroll", user(user_id))),
+        ("delete-user", [user_id]) => Some(("delete_user", user(user_id))),
+        ("erase", [user_id, ..]) => Some(("erase", user(user_id))),
+        ("export", [dir]) => Some(("export", Some(dir.clone()))),
+        ("import", [dir]) => Some(("import", Some(dir.clone()))),
+        ("reconcile", [flag, ..]) if flag == "--fix" => Some(("reconcile", Some(tenant.to_string()))),
+        ("purge", []) => Some(("purge", None)),
+        ("re-encrypt", []) => Some(("re_encrypt", None)),
+        ("create-tenant", [tenant_id]) => Some(("create_tenant", Some(tenant_id.clone()))),
+        ("create-api-key", [name, _]) => Some(("create_api_key", Some(name.clone()))),
+        ("issue-token", [subject, ..]) => Some(("issue_token", Some(subject.clone()))),
+        _ => None,
+    }
+}
+
+// Take `--tenant TENANT` out of the arguments
+fn split_tenant(args: &[String]) -> Result<(String, Vec<String>)> {
+    let mut tenant = DEFAULT_TENANT.to_string();
+    let mut rest = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--tenant" {
+            tenant = iter
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("--tenant needs a value"))?
+                .clone();
+            check_tenant_id(&tenant)?;
+        } else {
+            rest.push(arg.clone());
+        }
+    }
+
+    Ok((tenant, rest))
+}
+
+// The tenant's model, saving to the tenant's model path after training
+fn tenant_model(config: &Config, paths: &TenantPaths) -> Result<FaceRecognitionModel> {
+    FaceRecognitionModel::with_model_path(config, &paths.model_path)
+}
+
+// The database and the image store holding its enrollment photos
+async fn open_stores(config: &Config) -> Result<(SharedDb, SharedImageStore)> {
+    let db: SharedDb = Arc::new(Database::new(&config.database).await?);
+    let images = images::open(&config.images, db.clone(), Arc::new(Cipher::open(&config.encryption)?))?;
+
+    Ok((db, images))
+}
+
+fn print_training(stats: &TrainingStats) {
+    println!(
+        "Trained on {} users, {} images ({} augmented, {} failed)",
+        stats.users, stats.original_images, stats.augmented_images, stats.failed_images
+    );
+}
+
+async fn train(config: &Config, tenant: &str) -> Result<()> {
+    let (db, images) = open_stores(config).await?;
+    let paths = TenantPaths::new(config, tenant);
+    let mut model = tenant_model(config, &paths)?;
+    let stats = model.train(&Gallery::load(&db, images.as_ref(), tenant).await?)?;
+    print_training(&stats);
+
+    if !model.is_trained() {
+        println!("No usable images in the {} gallery, no model saved", tenant);
+    }
+
+    Ok(())
+}
+
+async fn enroll(config: &Config, tenant: &str, user_id: &str, files: &[String]) -> Result<()> {
+    check_user_id(user_id)?;
+    let (db, images) = open_stores(config).await?;
+    let paths = TenantPaths::new(config, tenant);
+    let mut model = tenant_model(config, &paths)?;
+
+    let mut saved_paths = Vec::new();
+    for file in files {
+        let img = imread(file, IMREAD_COLOR)?;
+        if img.empty() {
+            eprintln!("Skipping {}: not a readable image", file);
+            continue;
+        }
+
+        if model.quality_thresholds().reject_enrollment {
+            let reasons = match model.assess_quality(&img)? {
+                Some(quality) => model.quality_thresholds().failures(&quality),
+                None => vec!["no face detected".to_string()],
+            };
+            if !reasons.is_empty() {
+                eprintln!("Skipping {}: {}", file, reasons.join(", "));
+                continue;
+            }
+        }
+
+        let file_name = Path::new(file)
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("Invalid file name: {}", file))?;
+        let file_path = paths.image_key(user_id, &file_name.to_string_lossy());
+        images.put(&file_path, &fs::read(file)?).await?;
+        println!("Saved image: {}", file_path);
+        saved_paths.push(file_path);
+    }
+
+    if saved_paths.is_empty() {
+        return Err(anyhow::anyhow!("No photos were enrolled for {}", user_id));
+    }
+
+    db.upsert_user(tenant, user_id).await?;
+    for image_path in &saved_paths {
+        db.insert_face_image(tenant, user_id, image_path).await?;
+    }
+
+    let stats = model.train(&Gallery::load(&db, images.as_ref(), tenant).await?)?;
+    println!("Enrolled {} images for {}", saved_paths.len(), user_id);
+    print_training(&stats);
+
+    Ok(())
+}
+
+async fn identify(config: &Config, tenant: &str, file: &str) -> Result<()> {
+    let (db, images) = open_stores(config).await?;
+    let paths = TenantPaths::new(config, tenant);
+    let mut model = tenant_model(config, &paths)?;
+
+    let path = &paths.model_path;
+    if Path::new(path).exists() {
+        model.load(path)?;
+    } else {
+        // Nothing saved yet; train in memory only
+        println!("No saved model at {}, training from the {} gallery", path, tenant);
+        let gallery = Gallery::load(&db, images.as_ref(), tenant).await?;
+        let (users, _) = model.gallery_faces(&gallery);
+        let stats = model.train_faces(without_sources(users))?;
+        print_training(&stats);
+    }
+    apply_stored_threshold(&db, tenant, &mut model).await;
+
+    let img = imread(file, IMREAD_COLOR)?;
+    if img.empty() {
+        return Err(anyhow::anyhow!("Failed to load image {}", file));
+    }
+
+    let matches = model.predict_faces(&img)?;
+    if matches.is_empty() {
+        println!("No face detected in {}", file);
+        return Ok(());
+    }
+
+    for face in &matches {
+        let failures = model.quality_thresholds().failures(&face.quality);
+        println!(
+            "{:<20} confidence {:>7.2}  at ({}, {}) {}x{}{}",
+            face.user_id.as_deref().unwrap_or("unknown"),
+            face.confidence,
+            face.rect.x,
+            face.rect.y,
+            face.rect.width,
+            face.rect.height,
+            if failures.is_empty() {
+                String::new()
+            } else {
+                format!("  low quality: {}", failures.join(", "))
+            }
+        );
+    }
+
+    Ok(())
+}
+
+async fn list_users(config: &Config, tenant: &str) -> Result<()> {
+    let db = Database::new(&config.database).await?;
+
+    let users = db.get_all_users(tenant).await?;
+    println!("{:<30} {:>8} {:>12}", "USER", "IMAGES", "DETECTIONS");
+    for user_id in &users {
+        let (images, detections) = db.get_user_stats(tenant, user_id).await?;
+        println!("{:<30} {:>8} {:>12}", user_id, images, detections);
+    }
+    println!("{} users", users.len());
+
+    Ok(())
+}
+
+async fn delete_user(config: &Config, tenant: &str, user_id: &str) -> Result<()> {
+    check_user_id(user_id)?;
+    let (db, images) = open_stores(config).await?;
+
+    let image_paths = db.get_user_images(tenant, user_id).await?;
+    if !db.delete_user(tenant, user_id).await? {
+        return Err(anyhow::anyhow!("User not found: {}", user_id));
+    }
+    for image_path in &image_paths {
+        images.delete(image_path).await?;
+    }
+
+    println!("Deleted user {} and {} images", user_id, image_paths.len());
+
+    let paths = TenantPaths::new(config, tenant);
+    let stats = tenant_model(config, &paths)?.train(&Gallery::load(&db, images.as_ref(), tenant).await?)?;
+    print_training(&stats);
+
+    Ok(())
+}
+
+async fn erase_user(config: &Config, tenant: &str, user_id: &str, detections: DetectionErasure) -> Result<()> {
+    let (db, images) = open_stores(config).await?;
+    let paths = TenantPaths::new(config, tenant);
+    let mut model = tenant_model(config, &paths)?;
+
+    let request = ErasureRequest {
+        tenant,
+        user_id,
+        detections,
+        erased_by: "cli",
+    };
+    let certificate = erase(&db, images.as_ref(), &mut model, &paths, &request)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("No data found for user: {}", user_id))?;
+
+    println!("{}", serde_json::to_string_pretty(&certificate)?);
+    println!("Erased user {} (certificate {})", user_id, certificate.id);
+
+    Ok(())
+}
+
+async fn export(config: &Config, tenant: &str, dir: &str) -> Result<()> {
+    let target = Path::new(dir);
+    if target.exists() && fs::read_dir(target)?.next().is_some() {
+        return Err(anyhow::anyhow!("Export directory {} is not empty", dir));
+    }
+
+    let (db, images) = open_stores(config).await?;
+    let gallery = Gallery::load(&db, images.as_ref(), tenant).await?;
+
+    let mut exported = 0;
+    for (user_id, files) in &gallery.users {
+        let user_dir = target.join(user_id);
+        fs::create_dir_all(&user_dir)?;
+        for (image_path, data) in files {
+            let file_name = Path::new(image_path)
+                .file_name()
+                .ok_or_else(|| anyhow::anyhow!("Invalid image path: {}", image_path))?;
+            fs::write(user_dir.join(file_name), data)?;
+            exported += 1;
+        }
+    }
+    println!("Exported {} users ({} images) to {}", gallery.users.len(), exported, dir);
+    if !gallery.unreadable.is_empty() {
+        println!("{} images listed in the database could not be read", gallery.unreadable.len());
+    }
+
+    Ok(())
+}
+
+async fn import(config: &Config, tenant: &str, dir: &str) -> Result<()> {
+    let (db, images) = open_stores(config).await?;
+
+    // Photos already in the gallery under the same name are skipped
+    let paths = TenantPaths::new(config, tenant);
+    let users = read_gallery_dir(Path::new(dir))?;
+    let mut imported = 0;
+    for (user_id, files) in &users {
+        let existing = db.get_user_images(tenant, user_id).await?;
+        db.upsert_user(tenant, user_id).await?;
+        for file in files {
+            let image_path = paths.image_key(user_id, &file.file_name().unwrap_or_default().to_string_lossy());
+            if existing.contains(&image_path) {
+                continue;
+            }
+
+            images.put(&image_path, &fs::read(file)?).await?;
+            db.insert_face_image(tenant, user_id, &image_path).await?;
+            imported += 1;
+        }
+    }
+    println!("Imported {} users ({} images) from {}", users.len(), imported, dir);
+
+    let stats = tenant_model(config, &paths)?.train(&Gallery::load(&db, images.as_ref(), tenant).await?)?;
+    print_training(&stats);
+
+    Ok(())
+}
+
+async fn reconcile_gallery(config: &Config, tenant: &str, fix: Option<ReconcileFix>) -> Result<()> {
+    let (db, images) = open_stores(config).await?;
+    let paths = TenantPaths::new(config, tenant);
+    let mut model = tenant_model(config, &paths)?;
+
+    let report = reconcile(&db, images.as_ref(), &mut model, &paths, tenant, fix).await?;
+    for image_path in &report.orphaned_files {
+        println!("orphaned  {}", image_path);
+    }
+    for missing in &report.missing_files {
+        println!("missing   {} ({})", missing.image_path, missing.user_id);
+    }
+    for image_path in &report.unreadable_files {
+        println!("unreadable {}", image_path);
+    }
+    for user_id in &report.users_without_images {
+        println!("no faces  {}", user_id);
+    }
+
+    match (report.problems(), fix) {
+        (0, _) => println!("Gallery and image store agree"),
+        (problems, None) => println!("Found {} problems; run with --fix adopt|delete to fix them", problems),
+        (problems, Some(_)) => println!("Fixed {} problems", problems),
+    }
+    if let Some(stats) = &report.training {
+        print_training(stats);
+    }
+
+    Ok(())
+}
+
+async fn purge_data(config: &Config, dry_run: bool) -> Result<()> {
+    let db = Database::new(&config.database).await?;
+
+    let report = purge(&db, &config.retention, &config.paths.temp_dir, dry_run).await?;
+    let verb = if dry_run { "Would remove" } else { "Removed" };
+    println!(
+        "{} {} detections, {} unknown faces and {} temporary files",
+        verb, report.detections, report.unknown_faces, report.temp_files
+    );
+
+    Ok(())
+}
+
+async fn reencrypt_data(config: &Config) -> Result<()> {
+    let db: SharedDb = Arc::new(Database::new(&config.database).await?);
+    let images = images::open_raw(&config.images, db.clone())?;
+    let cipher = Cipher::open(&config.encryption)?;
+
+    let report = reencrypt(config, &db, images.as_ref(), &cipher).await?;
+    let verb = if cipher.is_enabled() { "Encrypted" } else { "Decrypted" };
+    println!(
+        "{} {} images, {} model files and {} unknown faces ({} already up to date)",
+        verb, report.images, report.models, report.unknown_faces, report.unchanged
+    );
+
+    Ok(())
+}
+
+async fn migrate(config: &Config, status_only: bool) -> Result<()> {
+    let db = Database::connect(&config.database).await?;
+
+    if !status_only {
+        let applied = db.migrate().await?;
+        if applied.is_empty() {
+            println!("Database schema is up to date");
+        } else {
+            println!("Applied {} migrations", applied.len());
+        }
+    }
+
+    for migration in db.migration_status().await? {
+        println!(
+            "{:>4}  {:<19}  {}",
+            migration.version,
+            migration.applied_at.as_deref().unwrap_or("pending"),
+            migration.description
+        );
+    }
+
+    Ok(())
+}
+
+// The only way to get the first admin key when auth is enabled
+async fn create_tenant(config: &Config, tenant_id: &str) -> Result<()> {
+    check_tenant_id(tenant_id)?;
+    let db = Database::new(&config.database).await?;
+
+    if !db.create_tenant(tenant_id).await? {
+        return Err(anyhow::anyhow!("Tenant already exists: {}", tenant_id));
+    }
+    println!("Created tenant {}", tenant_id);
+
+    Ok(())
+}
+
+async fn create_api_key(config: &Config, name: &str, role: &str) -> Result<()> {
+    let role: Role = role.parse()?;
+    let db = Database::new(&config.database).await?;
+
+    let key = generate_key();
+    let id = db.insert_api_key(name, role.as_str(), &key_prefix(&key), &hash_key(&key)).await?;
+    println!("Created {} API key {} for {}", role, id, name);
+    println!("{}", key);
+    println!("Store it now; it cannot be shown again");
+
+    Ok(())
+}
+
+// `role` is a token role name from auth.jwt.role_map
+fn issue_token(config: &Config, subject: &str, role: &str, ttl: Option<&String>) -> Result<()> {
+    let ttl_secs = match ttl {
+        Some(ttl) => ttl.parse().map_err(|e| anyhow::anyhow!("Invalid ttl_secs {}: {}", ttl, e))?,
+        None => 3600,
+    };
+    if !config.auth.jwt.role_map.contains_key(role) {
+        return Err(anyhow::anyhow!("Role {} is not in auth.jwt.role_map", role));
+    }
+
+    println!("{}", issue_hs256(&config.auth.jwt, subject, role, ttl_secs)?);
+
+    Ok(())
+}
+
+fn train_liveness(config: &Config, dataset_dir: &str, output: &str) -> Result<()> {
+    let folders = read_gallery_dir(Path::new(dataset_dir))?;
+    let files = |name: &str| {
+        folders
+            .iter()
+            .find(|(folder, _)| folder == name)
+            .map(|(_, files)| files.clone())
+            .ok_or_else(|| anyhow::anyhow!("Missing folder {}/{}", dataset_dir, name))
+    };
+    let (live, spoof) = (files("live")?, files("spoof")?);
+
+    // Only the face detector of the model is used
+    let mut model = FaceRecognitionModel::with_model_path(config, &config.paths.model_path)?;
+    let mut samples = Vec::new();
+    for (files, is_live) in [(live, true), (spoof, false)] {
+        for file in files {
+            let img = imread(&file.to_string_lossy(), IMREAD_COLOR)?;
+            if img.empty() {
+                eprintln!("Skipping {}: not a readable image", file.display());
+                continue;
+            }
+            let Some(face) = model.detect_face_rects(&img)?.into_iter().max_by_key(|face| face.area()) else {
+                eprintln!("Skipping {}: no face detected", file.display());
+                continue;
+            };
+
+            let gray = to_gray(&Mat::roi(&img, face)?.try_clone()?)?;
+            samples.push((lbp_histogram(&gray)?, is_live));
+        }
+    }
+
+    let classifier = LbpClassifier::train(&samples)?;
+    let correct = samples
+        .iter()
+        .filter(|(histogram, is_live)| (classifier.live_probability(histogram) >= 0.5) == *is_live)
+        .count();
+    classifier.save(output)?;
+
+    let live = samples.iter().filter(|(_, is_live)| *is_live).count();
+    println!("Trained on {} live and {} spoofed faces", live, samples.len() - live);
+    println!(
+        "Training accuracy {:.1}% (measure on held-out photos before relying on it)",
+        100.0 * correct as f64 / samples.len() as f64
+    );
+    println!("Saved classifier to {}; set liveness.lbp_classifier_path to use it", output);
+
+    Ok(())
+}
+
+// Image files under `{dir}/{user_id}/`, grouped by user and sorted by user ID and file name
+fn read_gallery_dir(dir: &Path) -> Result<UserImageFiles> {
+    if !dir.is_dir() {
+        return Err(anyhow::anyhow!("Directory not found: {}", dir.display()));
+    }
+
+    let mut user_dirs: Vec<_> = fs::read_dir(dir)?
+        .collect::<std::io::Result<Vec<_>>>()?
+        .into_iter()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    user_dirs.sort();
+
+    let mut users = Vec::new();
+    for user_dir in user_dirs {
+        let Some(user_id) = user_dir.file_name().and_then(|s| s.to_str()).map(str::to_string) else {
+            continue;
+        };
+
+        let mut files: Vec<_> = fs::read_dir(&user_dir)?
+            .collect::<std::io::Result<Vec<_>>>()?
+            .into_iter()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && is_image_file(path))
+            .collect();
+        files.sort();
+
+        users.push((user_id, files));
+    }
+
+    Ok(users)
+}
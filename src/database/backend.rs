@@ -0,0 +1,103 @@
+use super::migrations::{Migration, POSTGRES_MIGRATIONS, SQLITE_MIGRATIONS};
+
+/// What differs between the SQL databases the service can store its data in. Queries are
+/// written once with `$N` placeholders and run through `sqlx::Any`; only the schema and the
+/// migration bookkeeping are backend specific.
+pub trait StorageBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Up-migrations for this backend, in version order
+    fn migrations(&self) -> &'static [Migration];
+
+    /// The URL handed to the driver
+    fn connect_url(&self, url: &str) -> String {
+        url.to_string()
+    }
+
+    /// Statements run on the migration connection before and after pending migrations,
+    /// outside their transactions
+    fn before_migrations(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn after_migrations(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Query returning the rows whose foreign keys are broken, for backends that do not
+    /// enforce them while migrating
+    fn foreign_key_check(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Query counting the columns named `$2` in table `$1`
+    fn column_exists_query(&self) -> &'static str;
+}
+
+pub struct Sqlite;
+
+impl StorageBackend for Sqlite {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn migrations(&self) -> &'static [Migration] {
+        SQLITE_MIGRATIONS
+    }
+
+    // Create the database file when it does not exist yet
+    fn connect_url(&self, url: &str) -> String {
+        if url.contains("mode=") || url.contains(":memory:") {
+            url.to_string()
+        } else if url.contains('?') {
+            format!("{}&mode=rwc", url)
+        } else {
+            format!("{}?mode=rwc", url)
+        }
+    }
+
+    // Table rebuilds need foreign keys off, and they cannot be switched inside a transaction
+    fn before_migrations(&self) -> &'static [&'static str] {
+        &["PRAGMA foreign_keys = OFF"]
+    }
+
+    fn after_migrations(&self) -> &'static [&'static str] {
+        &["PRAGMA foreign_keys = ON"]
+    }
+
+    fn foreign_key_check(&self) -> Option<&'static str> {
+        Some("PRAGMA foreign_key_check")
+    }
+
+    fn column_exists_query(&self) -> &'static str {
+        "SELECT COUNT(*) FROM pragma_table_info($1) WHERE name = $2"
+    }
+}
+
+pub struct Postgres;
+
+impl StorageBackend for Postgres {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    fn migrations(&self) -> &'static [Migration] {
+        POSTGRES_MIGRATIONS
+    }
+
+    fn column_exists_query(&self) -> &'static str {
+        "SELECT COUNT(*) FROM information_schema.columns \
+         WHERE table_schema = current_schema() AND table_name = $1 AND column_name = $2"
+    }
+}
+
+/// The backend for a `database.url`, picked by its scheme
+pub fn backend_for(url: &str) -> Option<&'static dyn StorageBackend> {
+    if url.starts_with("sqlite:") {
+        Some(&Sqlite)
+    } else if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        Some(&Postgres)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,340 @@
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::{AnyConnection, AnyPool, Connection, Row};
+
+use super::backend::StorageBackend;
+
+/// An up-migration embedded in the binary. Pending migrations are applied in version order
+/// and recorded in `schema_migrations`. Every backend has the same versions, so a version
+/// means the same schema change everywhere.
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    sql: &'static str,
+    // Column that a database created by `init_schema` (before migrations existed) already has
+    // when it includes this change; such migrations are recorded without being run
+    baseline: Option<(&'static str, &'static str)>,
+}
+
+pub const SQLITE_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        sql: include_str!("../../migrations/sqlite/0001_initial.sql"),
+        baseline: None,
+    },
+    Migration {
+        version: 2,
+        description: "tenant IDs on users, face images and detections",
+        sql: include_str!("../../migrations/sqlite/0002_tenants.sql"),
+        baseline: Some(("users", "tenant_id")),
+    },
+    Migration {
+        version: 3,
+        description: "image blobs",
+        sql: include_str!("../../migrations/sqlite/0003_image_blobs.sql"),
+        baseline: None,
+    },
+    Migration {
+        version: 4,
+        description: "detection sources",
+        sql: include_str!("../../migrations/sqlite/0004_detection_source.sql"),
+        baseline: None,
+    },
+    Migration {
+        version: 5,
+        description: "erasure certificates",
+        sql: include_str!("../../migrations/sqlite/0005_erasure_certificates.sql"),
+        baseline: None,
+    },
+    Migration {
+        version: 6,
+        description: "audit events",
+        sql: include_str!("../../migrations/sqlite/0006_audit_events.sql"),
+        baseline: None,
+    },
+    Migration {
+        version: 7,
+        description: "tenant registry",
+        sql: include_str!("../../migrations/sqlite/0007_tenant_registry.sql"),
+        baseline: None,
+    },
+];
+
+pub const POSTGRES_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        sql: include_str!("../../migrations/postgres/0001_initial.sql"),
+        baseline: None,
+    },
+    Migration {
+        version: 2,
+        description: "tenant IDs on users, face images and detections",
+        sql: include_str!("../../migrations/postgres/0002_tenants.sql"),
+        baseline: None,
+    },
+    Migration {
+        version: 3,
+        description: "image blobs",
+        sql: include_str!("../../migrations/postgres/0003_image_blobs.sql"),
+        baseline: None,
+    },
+    Migration {
+        version: 4,
+        description: "detection sources",
+        sql: include_str!("../../migrations/postgres/0004_detection_source.sql"),
+        baseline: None,
+    },
+    Migration {
+        version: 5,
+        description: "erasure certificates",
+        sql: include_str!("../../migrations/postgres/0005_erasure_certificates.sql"),
+        baseline: None,
+    },
+    Migration {
+        version: 6,
+        description: "audit events",
+        sql: include_str!("../../migrations/postgres/0006_audit_events.sql"),
+        baseline: None,
+    },
+    Migration {
+        version: 7,
+        description: "tenant registry",
+        sql: include_str!("../../migrations/postgres/0007_tenant_registry.sql"),
+        baseline: None,
+    },
+];
+
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: &'static str,
+    pub applied_at: Option<String>,
+}
+
+// Valid on every backend; `applied_at` is set by the runner
+const MIGRATIONS_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS schema_migrations (
+        version BIGINT PRIMARY KEY,
+        description TEXT NOT NULL,
+        applied_at TEXT NOT NULL
+    )
+"#;
+
+/// Every known migration with the time it was applied, if it was
+pub async fn status(pool: &AnyPool, backend: &dyn StorageBackend) -> Result<Vec<MigrationStatus>> {
+    let migrations = backend.migrations();
+
+    sqlx::query(MIGRATIONS_TABLE).execute(pool).await?;
+
+    let rows = sqlx::query("SELECT version, applied_at FROM schema_migrations")
+        .fetch_all(pool)
+        .await?;
+    let applied: Vec<(i64, String)> = rows
+        .iter()
+        .map(|row| (row.get("version"), row.get("applied_at")))
+        .collect();
+
+    let latest = migrations.last().map(|m| m.version).unwrap_or(0);
+    if let Some((version, _)) = applied.iter().find(|(version, _)| *version > latest) {
+        return Err(anyhow::anyhow!(
+            "Database schema version {} is newer than this build supports ({})",
+            version,
+            latest
+        ));
+    }
+
+    Ok(migrations
+        .iter()
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            description: migration.description,
+            applied_at: applied
+                .iter()
+                .find(|(version, _)| *version == migration.version)
+                .map(|(_, applied_at)| applied_at.clone()),
+        })
+        .collect())
+}
+
+/// Apply every pending migration and return the versions that were applied
+pub async fn run(pool: &AnyPool, backend: &dyn StorageBackend) -> Result<Vec<i64>> {
+    let pending: Vec<&Migration> = status(pool, backend)
+        .await?
+        .iter()
+        .filter(|status| status.applied_at.is_none())
+        .filter_map(|status| backend.migrations().iter().find(|m| m.version == status.version))
+        .collect();
+
+    if pending.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut conn = pool.acquire().await?;
+    for statement in backend.before_migrations() {
+        sqlx::query(statement).execute(&mut *conn).await?;
+    }
+    let result = apply(&mut conn, backend, &pending).await;
+    for statement in backend.after_migrations() {
+        sqlx::query(statement).execute(&mut *conn).await?;
+    }
+
+    result
+}
+
+async fn apply(conn: &mut AnyConnection, backend: &dyn StorageBackend, pending: &[&Migration]) -> Result<Vec<i64>> {
+    let mut applied = Vec::new();
+
+    for migration in pending {
+        let already_present = match migration.baseline {
+            Some((table, column)) => column_exists(conn, backend, table, column).await?,
+            None => false,
+        };
+
+        let mut tx = conn.begin().await?;
+        if !already_present {
+            sqlx::raw_sql(migration.sql).execute(&mut *tx).await.map_err(|e| {
+                anyhow::anyhow!("Migration {} ({}) failed: {}", migration.version, migration.description, e)
+            })?;
+
+            if let Some(check) = backend.foreign_key_check() {
+                let violations = sqlx::query(check).fetch_all(&mut *tx).await?;
+                if !violations.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "Migration {} ({}) left {} rows with broken foreign keys",
+                        migration.version,
+                        migration.description,
+                        violations.len()
+                    ));
+                }
+            }
+        }
+
+        sqlx::query("INSERT INTO schema_migrations (version, description, applied_at) VALUES ($1, $2, $3)")
+            .bind(migration.version)
+            .bind(migration.description)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        applied.push(migration.version);
+    }
+
+    Ok(applied)
+}
+
+async fn column_exists(conn: &mut AnyConnection, backend: &dyn StorageBackend, table: &str, column: &str) -> Result<bool> {
+    let count: i64 = sqlx::query_scalar(backend.column_exists_query())
+        .bind(table)
+        .bind(column)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    Ok(count > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::backend::Sqlite;
+    use crate::database::Database;
+    use sqlx::any::AnyPoolOptions;
+
+    // `schema.sql` of the first release, before tenants and versioned migrations
+    const BASELINE_SCHEMA: &str = r#"
+        CREATE TABLE IF NOT EXISTS users (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id TEXT NOT NULL UNIQUE,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE TABLE IF NOT EXISTS face_images (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id TEXT NOT NULL,
+            image_path TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+        );
+        CREATE TABLE IF NOT EXISTS detection_logs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            detected_user_id TEXT,
+            confidence REAL,
+            image_path TEXT,
+            detected_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE INDEX IF NOT EXISTS idx_users_user_id ON users(user_id);
+        CREATE INDEX IF NOT EXISTS idx_face_images_user_id ON face_images(user_id);
+        CREATE INDEX IF NOT EXISTS idx_detection_logs_detected_at ON detection_logs(detected_at);
+
+        INSERT INTO users (user_id) VALUES ('alice'), ('bob');
+        INSERT INTO face_images (user_id, image_path) VALUES
+            ('alice', 'knowledge/alice/1.jpg'),
+            ('alice', 'knowledge/alice/2.jpg'),
+            ('bob', 'knowledge/bob/1.jpg');
+        INSERT INTO detection_logs (detected_user_id, confidence, image_path, detected_at) VALUES
+            ('alice', 42.5, 'temp/probe.jpg', '2024-01-02 08:00:00'),
+            (NULL, NULL, 'temp/unknown.jpg', '2024-01-02 09:00:00');
+    "#;
+
+    // A baseline database in memory; one connection, so it lives as long as the pool
+    async fn baseline_database() -> Database {
+        sqlx::any::install_default_drivers();
+        let pool = AnyPoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::raw_sql(BASELINE_SCHEMA).execute(&pool).await.unwrap();
+
+        Database { pool, backend: &Sqlite }
+    }
+
+    #[tokio::test]
+    async fn upgrades_baseline_database() {
+        let db = baseline_database().await;
+
+        let applied = db.migrate().await.unwrap();
+        assert_eq!(applied, (1..=7).collect::<Vec<i64>>());
+        let status = db.migration_status().await.unwrap();
+        assert!(status.iter().all(|migration| migration.applied_at.is_some()));
+        assert_eq!(status.last().map(|migration| migration.version), Some(7));
+
+        let mut users = db.get_all_users("default").await.unwrap();
+        users.sort();
+        assert_eq!(users, ["alice", "bob"]);
+        assert_eq!(
+            db.get_face_images("default").await.unwrap(),
+            [
+                ("alice".to_string(), "knowledge/alice/1.jpg".to_string()),
+                ("alice".to_string(), "knowledge/alice/2.jpg".to_string()),
+                ("bob".to_string(), "knowledge/bob/1.jpg".to_string()),
+            ]
+        );
+
+        let tenants: Vec<String> = sqlx::query_scalar(
+            "SELECT tenant_id FROM users UNION ALL SELECT tenant_id FROM face_images \
+             UNION ALL SELECT tenant_id FROM detection_logs",
+        )
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(tenants.len(), 7);
+        assert!(tenants.iter().all(|tenant| tenant == "default"));
+
+        let detection = db.get_detection("default", 1).await.unwrap().unwrap();
+        assert_eq!(detection.detected_user_id.as_deref(), Some("alice"));
+        assert_eq!(detection.detected_at, "2024-01-02 08:00:00");
+        assert!(db.tenant_exists("default").await.unwrap());
+        assert!(!db.tenant_exists("site-a").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn migrating_twice_changes_nothing() {
+        let db = baseline_database().await;
+
+        db.migrate().await.unwrap();
+        assert!(db.migrate().await.unwrap().is_empty());
+        assert_eq!(db.get_all_users("default").await.unwrap().len(), 2);
+    }
+}
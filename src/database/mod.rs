@@ -0,0 +1,1770 @@
+use anyhow::Result;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::any::{AnyPoolOptions, AnyRow};
+use sqlx::{AnyPool, Row};
+use std::fmt;
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+use crate::config::DatabaseConfig;
+
+pub mod backend;
+pub mod migrations;
+
+use backend::{backend_for, StorageBackend};
+use migrations::MigrationStatus;
+
+#[derive(Clone)]
+pub struct Database {
+    pool: AnyPool,
+    backend: &'static dyn StorageBackend,
+}
+
+impl Database {
+    /// Connect without touching the schema. The backend is picked by the URL scheme
+    /// (`sqlite:` or `postgres://`).
+    pub async fn connect(config: &DatabaseConfig) -> Result<Self> {
+        let backend = backend_for(&config.url)
+            .ok_or_else(|| anyhow::anyhow!("database.url must start with sqlite: or postgres://"))?;
+
+        sqlx::any::install_default_drivers();
+        let pool = AnyPoolOptions::new()
+            .max_connections(config.max_connections)
+            .connect(&backend.connect_url(&config.url))
+            .await?;
+
+        Ok(Self { pool, backend })
+    }
+
+    /// Connect and apply pending migrations
+    pub async fn new(config: &DatabaseConfig) -> Result<Self> {
+        let db = Self::connect(config).await?;
+        for version in db.migrate().await? {
+            println!("Applied {} database migration {}", db.backend.name(), version);
+        }
+
+        Ok(db)
+    }
+
+    // Apply pending migrations and return their versions
+    pub async fn migrate(&self) -> Result<Vec<i64>> {
+        migrations::run(&self.pool, self.backend).await
+    }
+
+    // Known migrations and when they were applied
+    pub async fn migration_status(&self) -> Result<Vec<MigrationStatus>> {
+        migrations::status(&self.pool, self.backend).await
+    }
+
+    // Insert or update user
+    pub async fn upsert_user(&self, tenant_id: &str, user_id: &str) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        
+        sqlx::query(
+            r#"
+            INSERT INTO users (tenant_id, user_id, created_at, updated_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT(tenant_id, user_id) DO UPDATE SET updated_at = $5
+            "#,
+        )
+        .bind(tenant_id)
+        .bind(user_id)
+        .bind(&now)
+        .bind(&now)
+        .bind(&now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Insert face image record
+    pub async fn insert_face_image(&self, tenant_id: &str, user_id: &str, image_path: &str) -> Result<i64> {
+        let id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO face_images (tenant_id, user_id, image_path)
+            VALUES ($1, $2, $3) RETURNING id
+            "#,
+        )
+        .bind(tenant_id)
+        .bind(user_id)
+        .bind(image_path)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    // Get all images for a user
+    pub async fn get_user_images(&self, tenant_id: &str, user_id: &str) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT image_path FROM face_images
+            WHERE tenant_id = $1 AND user_id = $2
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(tenant_id)
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let images: Vec<String> = rows
+            .iter()
+            .map(|row| row.get("image_path"))
+            .collect();
+
+        Ok(images)
+    }
+
+    // Get every image of a tenant as (user_id, image_path), sorted by user and path
+    pub async fn get_face_images(&self, tenant_id: &str) -> Result<Vec<(String, String)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT user_id, image_path FROM face_images
+            WHERE tenant_id = $1
+            ORDER BY user_id, image_path
+            "#,
+        )
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let images = rows
+            .iter()
+            .map(|row| (row.get("user_id"), row.get("image_path")))
+            .collect();
+
+        Ok(images)
+    }
+
+    // Delete the image records with this path
+    pub async fn delete_face_image(&self, tenant_id: &str, image_path: &str) -> Result<()> {
+        sqlx::query("DELETE FROM face_images WHERE tenant_id = $1 AND image_path = $2")
+            .bind(tenant_id)
+            .bind(image_path)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Delete a user and their image records. Returns false if the user did not exist
+    pub async fn delete_user(&self, tenant_id: &str, user_id: &str) -> Result<bool> {
+        sqlx::query("DELETE FROM face_images WHERE tenant_id = $1 AND user_id = $2")
+            .bind(tenant_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        let result = sqlx::query("DELETE FROM users WHERE tenant_id = $1 AND user_id = $2")
+            .bind(tenant_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Log detection result
+    pub async fn log_detection(
+        &self,
+        tenant_id: &str,
+        detected_user_id: Option<&str>,
+        confidence: Option<f64>,
+        image_path: Option<&str>,
+        source: Option<&str>,
+    ) -> Result<i64> {
+        let id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO detection_logs (tenant_id, detected_user_id, confidence, image_path, source)
+            VALUES ($1, $2, $3, $4, $5) RETURNING id
+            "#,
+        )
+        .bind(tenant_id)
+        .bind(detected_user_id)
+        .bind(confidence)
+        .bind(image_path)
+        .bind(source)
+        // sqlx's Any driver binds a NULL f64 as float4; a cached statement would then reject
+        // the next non-NULL confidence on PostgreSQL
+        .persistent(false)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    // Get user stats
+    pub async fn get_user_stats(&self, tenant_id: &str, user_id: &str) -> Result<(i64, i64)> {
+        // Get image count
+        let image_count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM face_images WHERE tenant_id = $1 AND user_id = $2"
+        )
+        .bind(tenant_id)
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        // Get detection count
+        let detection_count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM detection_logs WHERE tenant_id = $1 AND detected_user_id = $2"
+        )
+        .bind(tenant_id)
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok((image_count, detection_count))
+    }
+
+    // Get all users
+    pub async fn get_all_users(&self, tenant_id: &str) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT user_id FROM users
+            WHERE tenant_id = $1
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let users: Vec<String> = rows
+            .iter()
+            .map(|row| row.get("user_id"))
+            .collect();
+
+        Ok(users)
+    }
+
+    // Get every user of a tenant with the time they were enrolled
+    pub async fn get_user_enrollments(&self, tenant_id: &str) -> Result<Vec<(String, String)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT user_id, created_at FROM users
+            WHERE tenant_id = $1
+            ORDER BY user_id
+            "#,
+        )
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("user_id"), row.get("created_at")))
+            .collect())
+    }
+
+    // Get the user and time of every matched detection in [from, to), ordered by user and time
+    pub async fn get_sightings(&self, tenant_id: &str, from: &str, to: &str) -> Result<Vec<(String, String)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT detected_user_id, detected_at FROM detection_logs
+            WHERE tenant_id = $1 AND detected_user_id IS NOT NULL
+              AND detected_at >= $2 AND detected_at < $3
+            ORDER BY detected_user_id, detected_at
+            "#,
+        )
+        .bind(tenant_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("detected_user_id"), row.get("detected_at")))
+            .collect())
+    }
+
+    // Register a tenant; false when it already exists
+    pub async fn create_tenant(&self, tenant_id: &str) -> Result<bool> {
+        let now = Utc::now().to_rfc3339();
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO tenants (tenant_id, created_at) VALUES ($1, $2)
+            ON CONFLICT(tenant_id) DO NOTHING
+            "#,
+        )
+        .bind(tenant_id)
+        .bind(&now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Check whether a tenant is registered
+    pub async fn tenant_exists(&self, tenant_id: &str) -> Result<bool> {
+        let found: Option<String> = sqlx::query_scalar("SELECT tenant_id FROM tenants WHERE tenant_id = $1")
+            .bind(tenant_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(found.is_some())
+    }
+
+    // Get every registered tenant, with user and image counts
+    pub async fn get_tenants(&self) -> Result<Vec<TenantSummary>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT t.tenant_id, COUNT(DISTINCT u.user_id) AS users, COUNT(f.id) AS images
+            FROM tenants t
+            LEFT JOIN users u ON u.tenant_id = t.tenant_id
+            LEFT JOIN face_images f ON f.tenant_id = u.tenant_id AND f.user_id = u.user_id
+            GROUP BY t.tenant_id
+            ORDER BY t.tenant_id
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let tenants: Vec<TenantSummary> = rows
+            .iter()
+            .map(|row| TenantSummary {
+                tenant_id: row.get("tenant_id"),
+                users: row.get("users"),
+                images: row.get("images"),
+            })
+            .collect();
+
+        Ok(tenants)
+    }
+
+    // Get one page of a tenant's detections matching the filter
+    pub async fn get_detections(&self, filter: &DetectionFilter) -> Result<Vec<DetectionLog>> {
+        let mut conditions = vec!["tenant_id = $1".to_string()];
+        let mut binds = vec![Bind::Text(filter.tenant_id.clone())];
+        let mut condition = |sql: &str, values: Vec<Bind>| {
+            let mut sql = sql.to_string();
+            for value in values {
+                binds.push(value);
+                sql = sql.replacen('?', &format!("${}", binds.len()), 1);
+            }
+            conditions.push(sql);
+        };
+
+        if let Some(user_id) = &filter.user_id {
+            condition("detected_user_id = ?", vec![Bind::Text(user_id.clone())]);
+        }
+        match filter.matched {
+            Some(true) => condition("detected_user_id IS NOT NULL", vec![]),
+            Some(false) => condition("detected_user_id IS NULL", vec![]),
+            None => {}
+        }
+        if let Some(min) = filter.min_confidence {
+            condition("confidence >= ?", vec![Bind::Real(min)]);
+        }
+        if let Some(max) = filter.max_confidence {
+            condition("confidence <= ?", vec![Bind::Real(max)]);
+        }
+        if let Some(from) = &filter.from {
+            condition("detected_at >= ?", vec![Bind::Text(from.clone())]);
+        }
+        if let Some(to) = &filter.to {
+            condition("detected_at < ?", vec![Bind::Text(to.clone())]);
+        }
+        if let Some(source) = &filter.source {
+            condition("source = ?", vec![Bind::Text(source.clone())]);
+        }
+
+        // Keyset pagination: continue after the cursor row in (sort key, id) order
+        let key = match filter.sort {
+            DetectionSort::DetectedAt => "detected_at",
+            DetectionSort::Confidence => "COALESCE(confidence, -1)",
+        };
+        let (direction, comparison) = match filter.order {
+            SortOrder::Asc => ("ASC", ">"),
+            SortOrder::Desc => ("DESC", "<"),
+        };
+        if let Some(after) = &filter.after {
+            let last_key = match (filter.sort, after) {
+                (DetectionSort::DetectedAt, DetectionCursor::DetectedAt(detected_at, _)) => Bind::Text(detected_at.clone()),
+                (DetectionSort::Confidence, DetectionCursor::Confidence(confidence, _)) => Bind::Real(*confidence),
+                _ => return Err(anyhow::anyhow!("Cursor is for a different sort")),
+            };
+            condition(&format!("({key}, id) {comparison} (?, ?)"), vec![last_key, Bind::Int(after.id())]);
+        }
+
+        binds.push(Bind::Int(filter.limit));
+        let sql = format!(
+            r#"
+            SELECT id, tenant_id, detected_user_id, confidence, image_path, source, detected_at
+            FROM detection_logs
+            WHERE {}
+            ORDER BY {key} {direction}, id {direction}
+            LIMIT ${}
+            "#,
+            conditions.join(" AND "),
+            binds.len()
+        );
+
+        let mut query = sqlx::query(&sql);
+        for value in binds {
+            query = match value {
+                Bind::Text(text) => query.bind(text),
+                Bind::Real(real) => query.bind(real),
+                Bind::Int(int) => query.bind(int),
+            };
+        }
+
+        let rows = query.fetch_all(&self.pool).await?;
+
+        Ok(rows.iter().map(detection_from_row).collect())
+    }
+
+    // Get every tenant with logged detections
+    pub async fn get_detection_tenants(&self) -> Result<Vec<String>> {
+        let tenants: Vec<String> = sqlx::query_scalar("SELECT DISTINCT tenant_id FROM detection_logs ORDER BY tenant_id")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(tenants)
+    }
+
+    // Get the ID of a tenant's newest detection beyond the `keep` newest
+    pub async fn get_detection_overflow_id(&self, tenant_id: &str, keep: i64) -> Result<Option<i64>> {
+        let id: Option<i64> = sqlx::query_scalar(
+            "SELECT id FROM detection_logs WHERE tenant_id = $1 ORDER BY id DESC LIMIT 1 OFFSET $2",
+        )
+        .bind(tenant_id)
+        .bind(keep)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    // Delete (or with `dry_run` only count) a tenant's detections logged before `before` or
+    // with an ID up to `up_to`; their feedback goes with them
+    pub async fn purge_detections(
+        &self,
+        tenant_id: &str,
+        before: Option<&str>,
+        up_to: Option<i64>,
+        dry_run: bool,
+    ) -> Result<u64> {
+        let condition = "tenant_id = $1 AND (detected_at < $2 OR id <= $3)";
+
+        if dry_run {
+            let count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM detection_logs WHERE {}", condition))
+                .bind(tenant_id)
+                .bind(before)
+                .bind(up_to)
+                .fetch_one(&self.pool)
+                .await?;
+
+            return Ok(count as u64);
+        }
+
+        let result = sqlx::query(&format!("DELETE FROM detection_logs WHERE {}", condition))
+            .bind(tenant_id)
+            .bind(before)
+            .bind(up_to)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // Create a queued video analysis job
+    pub async fn create_video_job(
+        &self,
+        job_id: &str,
+        source: &str,
+        frame_stride: i64,
+        output_path: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO video_jobs (id, source, frame_stride, output_path, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(job_id)
+        .bind(source)
+        .bind(frame_stride)
+        .bind(output_path)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Update the status and counters of a video job
+    pub async fn update_video_job(&self, job_id: &str, status: &str, progress: &VideoJobProgress) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE video_jobs
+            SET status = $1, frames_read = $2, frames_analyzed = $3, faces_detected = $4, unknown_faces = $5
+            WHERE id = $6
+            "#,
+        )
+        .bind(status)
+        .bind(progress.frames_read)
+        .bind(progress.frames_analyzed)
+        .bind(progress.faces_detected)
+        .bind(progress.unknown_faces)
+        .bind(job_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Mark a video job as finished, recording the error if it failed
+    pub async fn finish_video_job(&self, job_id: &str, status: &str, error: Option<&str>) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE video_jobs
+            SET status = $1, error = $2, finished_at = $3
+            WHERE id = $4
+            "#,
+        )
+        .bind(status)
+        .bind(error)
+        .bind(Utc::now().to_rfc3339())
+        .bind(job_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Insert an appearance found while analyzing a video
+    pub async fn insert_video_appearance(&self, job_id: &str, appearance: &VideoAppearance) -> Result<i64> {
+        let id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO video_appearances
+                (job_id, user_id, first_frame, last_frame, first_seen_ms, last_seen_ms, detections, best_confidence)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id
+            "#,
+        )
+        .bind(job_id)
+        .bind(&appearance.user_id)
+        .bind(appearance.first_frame)
+        .bind(appearance.last_frame)
+        .bind(appearance.first_seen_ms)
+        .bind(appearance.last_seen_ms)
+        .bind(appearance.detections)
+        .bind(appearance.best_confidence)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    // Get a video job by id
+    pub async fn get_video_job(&self, job_id: &str) -> Result<Option<VideoJob>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, source, frame_stride, status, frames_read, frames_analyzed,
+                   faces_detected, unknown_faces, output_path, error, created_at, finished_at
+            FROM video_jobs
+            WHERE id = $1
+            "#,
+        )
+        .bind(job_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| VideoJob {
+            id: row.get("id"),
+            source: row.get("source"),
+            frame_stride: row.get("frame_stride"),
+            status: row.get("status"),
+            progress: VideoJobProgress {
+                frames_read: row.get("frames_read"),
+                frames_analyzed: row.get("frames_analyzed"),
+                faces_detected: row.get("faces_detected"),
+                unknown_faces: row.get("unknown_faces"),
+            },
+            output_path: row.get("output_path"),
+            error: row.get("error"),
+            created_at: row.get("created_at"),
+            finished_at: row.get("finished_at"),
+        }))
+    }
+
+    // Get all appearances recorded for a video job
+    pub async fn get_video_appearances(&self, job_id: &str) -> Result<Vec<VideoAppearance>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT user_id, first_frame, last_frame, first_seen_ms, last_seen_ms, detections, best_confidence
+            FROM video_appearances
+            WHERE job_id = $1
+            ORDER BY first_seen_ms ASC
+            "#,
+        )
+        .bind(job_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let appearances: Vec<VideoAppearance> = rows
+            .iter()
+            .map(|row| VideoAppearance {
+                user_id: row.get("user_id"),
+                first_frame: row.get("first_frame"),
+                last_frame: row.get("last_frame"),
+                first_seen_ms: row.get("first_seen_ms"),
+                last_seen_ms: row.get("last_seen_ms"),
+                detections: row.get("detections"),
+                best_confidence: row.get("best_confidence"),
+            })
+            .collect();
+
+        Ok(appearances)
+    }
+
+    // Get a single detection log entry of a tenant
+    pub async fn get_detection(&self, tenant_id: &str, id: i64) -> Result<Option<DetectionLog>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, tenant_id, detected_user_id, confidence, image_path, source, detected_at
+            FROM detection_logs
+            WHERE tenant_id = $1 AND id = $2
+            "#,
+        )
+        .bind(tenant_id)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(detection_from_row))
+    }
+
+    // Record operator feedback on a detection, replacing any earlier feedback
+    pub async fn upsert_detection_feedback(
+        &self,
+        detection_id: i64,
+        verdict: &str,
+        actual_user_id: Option<&str>,
+        note: Option<&str>,
+    ) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+
+        sqlx::query(
+            r#"
+            INSERT INTO detection_feedback (detection_id, verdict, actual_user_id, note, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT(detection_id) DO UPDATE SET
+                verdict = excluded.verdict,
+                actual_user_id = excluded.actual_user_id,
+                note = excluded.note,
+                created_at = excluded.created_at
+            "#,
+        )
+        .bind(detection_id)
+        .bind(verdict)
+        .bind(actual_user_id)
+        .bind(note)
+        .bind(&now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Get every detection of a tenant with feedback and a stored confidence
+    pub async fn get_feedback_samples(&self, tenant_id: &str) -> Result<Vec<FeedbackSample>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT l.detected_user_id, l.confidence, f.verdict
+            FROM detection_feedback f
+            JOIN detection_logs l ON l.id = f.detection_id
+            WHERE l.tenant_id = $1 AND l.confidence IS NOT NULL
+            "#,
+        )
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let samples: Vec<FeedbackSample> = rows
+            .iter()
+            .map(|row| FeedbackSample {
+                detected_user_id: row.get("detected_user_id"),
+                confidence: row.get("confidence"),
+                verdict: row.get("verdict"),
+            })
+            .collect();
+
+        Ok(samples)
+    }
+
+    // Retain an unidentified face for clustering
+    pub async fn insert_unknown_face(
+        &self,
+        image_path: &str,
+        features: &[u8],
+        detection_id: Option<i64>,
+        confidence: Option<f64>,
+    ) -> Result<i64> {
+        let now = Utc::now().to_rfc3339();
+
+        let id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO unknown_faces (image_path, features, detection_id, confidence, created_at)
+            VALUES ($1, $2, $3, $4, $5) RETURNING id
+            "#,
+        )
+        .bind(image_path)
+        .bind(features)
+        .bind(detection_id)
+        .bind(confidence)
+        .bind(&now)
+        // sqlx's Any driver binds a NULL f64 as float4; a cached statement would then reject
+        // the next non-NULL confidence on PostgreSQL
+        .persistent(false)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    // Get the features and current cluster of every retained unknown face
+    pub async fn get_unknown_face_features(&self) -> Result<Vec<UnknownFaceFeatures>> {
+        let rows = sqlx::query("SELECT id, cluster_id, features FROM unknown_faces ORDER BY id")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let faces: Vec<UnknownFaceFeatures> = rows
+            .iter()
+            .map(|row| UnknownFaceFeatures {
+                id: row.get("id"),
+                cluster_id: row.get("cluster_id"),
+                features: row.get("features"),
+            })
+            .collect();
+
+        Ok(faces)
+    }
+
+    // Get the crop path and features of every retained unknown face
+    pub async fn get_unknown_face_data(&self) -> Result<Vec<(i64, String, Vec<u8>)>> {
+        let rows = sqlx::query("SELECT id, image_path, features FROM unknown_faces ORDER BY id")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let faces = rows
+            .iter()
+            .map(|row| (row.get("id"), row.get("image_path"), row.get("features")))
+            .collect();
+
+        Ok(faces)
+    }
+
+    // Replace the stored features of an unknown face
+    pub async fn update_unknown_face_features(&self, id: i64, features: &[u8]) -> Result<()> {
+        sqlx::query("UPDATE unknown_faces SET features = $1 WHERE id = $2")
+            .bind(features)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Create an empty unknown-face cluster
+    pub async fn create_unknown_cluster(&self) -> Result<i64> {
+        let now = Utc::now().to_rfc3339();
+
+        let id: i64 = sqlx::query_scalar("INSERT INTO unknown_clusters (created_at) VALUES ($1) RETURNING id")
+            .bind(&now)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(id)
+    }
+
+    // Move an unknown face into a cluster
+    pub async fn assign_unknown_face(&self, face_id: i64, cluster_id: i64) -> Result<()> {
+        sqlx::query("UPDATE unknown_faces SET cluster_id = $1 WHERE id = $2")
+            .bind(cluster_id)
+            .bind(face_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Get clusters with at least `min_size` faces, largest first
+    pub async fn get_unknown_clusters(&self, min_size: i64) -> Result<Vec<UnknownCluster>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT c.id, COUNT(f.id) AS face_count, MIN(f.created_at) AS first_seen, MAX(f.created_at) AS last_seen
+            FROM unknown_clusters c
+            JOIN unknown_faces f ON f.cluster_id = c.id
+            GROUP BY c.id
+            HAVING COUNT(f.id) >= $1
+            ORDER BY face_count DESC, c.id
+            "#,
+        )
+        .bind(min_size)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let clusters: Vec<UnknownCluster> = rows
+            .iter()
+            .map(|row| UnknownCluster {
+                id: row.get("id"),
+                face_count: row.get("face_count"),
+                first_seen: row.get("first_seen"),
+                last_seen: row.get("last_seen"),
+            })
+            .collect();
+
+        Ok(clusters)
+    }
+
+    // Get the faces in an unknown-face cluster
+    pub async fn get_unknown_cluster_faces(&self, cluster_id: i64) -> Result<Vec<UnknownFace>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, image_path, detection_id, confidence, created_at
+            FROM unknown_faces
+            WHERE cluster_id = $1
+            ORDER BY id
+            "#,
+        )
+        .bind(cluster_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let faces: Vec<UnknownFace> = rows
+            .iter()
+            .map(|row| UnknownFace {
+                id: row.get("id"),
+                image_path: row.get("image_path"),
+                detection_id: row.get("detection_id"),
+                confidence: row.get("confidence"),
+                created_at: row.get("created_at"),
+            })
+            .collect();
+
+        Ok(faces)
+    }
+
+    // Delete a cluster together with its faces
+    pub async fn delete_unknown_cluster(&self, cluster_id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM unknown_faces WHERE cluster_id = $1")
+            .bind(cluster_id)
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("DELETE FROM unknown_clusters WHERE id = $1")
+            .bind(cluster_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Get the ID of the newest unknown face beyond the `keep` newest
+    pub async fn get_unknown_face_overflow_id(&self, keep: i64) -> Result<Option<i64>> {
+        let id: Option<i64> = sqlx::query_scalar("SELECT id FROM unknown_faces ORDER BY id DESC LIMIT 1 OFFSET $1")
+            .bind(keep)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(id)
+    }
+
+    // Get the ID and crop of every unknown face retained before `before` or with an ID up to `up_to`
+    pub async fn get_expired_unknown_faces(&self, before: Option<&str>, up_to: Option<i64>) -> Result<Vec<(i64, String)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, image_path FROM unknown_faces
+            WHERE created_at < $1 OR id <= $2
+            ORDER BY id
+            "#,
+        )
+        .bind(before)
+        .bind(up_to)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(|row| (row.get("id"), row.get("image_path"))).collect())
+    }
+
+    pub async fn delete_unknown_face(&self, id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM unknown_faces WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Delete clusters whose faces have all been removed
+    pub async fn delete_empty_unknown_clusters(&self) -> Result<u64> {
+        let result = sqlx::query(
+            "DELETE FROM unknown_clusters WHERE id NOT IN (SELECT cluster_id FROM unknown_faces WHERE cluster_id IS NOT NULL)",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // Replace a user's ID in their detections with `pseudonym` (clearing the probe path), or
+    // delete the detections when there is none
+    pub async fn erase_user_detections(&self, tenant_id: &str, user_id: &str, pseudonym: Option<&str>) -> Result<u64> {
+        let query = match pseudonym {
+            Some(pseudonym) => sqlx::query(
+                r#"
+                UPDATE detection_logs SET detected_user_id = $3, image_path = NULL
+                WHERE tenant_id = $1 AND detected_user_id = $2
+                "#,
+            )
+            .bind(tenant_id)
+            .bind(user_id)
+            .bind(pseudonym),
+            None => sqlx::query("DELETE FROM detection_logs WHERE tenant_id = $1 AND detected_user_id = $2")
+                .bind(tenant_id)
+                .bind(user_id),
+        };
+
+        Ok(query.execute(&self.pool).await?.rows_affected())
+    }
+
+    // Replace a user's ID in feedback naming them as the actual person with `pseudonym`, or
+    // clear it when there is none
+    pub async fn erase_user_feedback(&self, tenant_id: &str, user_id: &str, pseudonym: Option<&str>) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            UPDATE detection_feedback SET actual_user_id = $3
+            WHERE actual_user_id = $2
+              AND detection_id IN (SELECT id FROM detection_logs WHERE tenant_id = $1)
+            "#,
+        )
+        .bind(tenant_id)
+        .bind(user_id)
+        .bind(pseudonym)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // Replace a user's ID in video appearances with `pseudonym`, or delete the appearances
+    // when there is none
+    pub async fn erase_user_appearances(&self, user_id: &str, pseudonym: Option<&str>) -> Result<u64> {
+        let query = match pseudonym {
+            Some(pseudonym) => sqlx::query("UPDATE video_appearances SET user_id = $2 WHERE user_id = $1")
+                .bind(user_id)
+                .bind(pseudonym),
+            None => sqlx::query("DELETE FROM video_appearances WHERE user_id = $1").bind(user_id),
+        };
+
+        Ok(query.execute(&self.pool).await?.rows_affected())
+    }
+
+    // Record an erasure; the ID of `certificate` is ignored
+    pub async fn insert_erasure_certificate(&self, certificate: &ErasureCertificate) -> Result<i64> {
+        let id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO erasure_certificates (tenant_id, user_id, erased_by, detection_action, pseudonym,
+                image_keys, face_images, detections, feedback, video_appearances, model_users, erased_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) RETURNING id
+            "#,
+        )
+        .bind(&certificate.tenant_id)
+        .bind(&certificate.user_id)
+        .bind(&certificate.erased_by)
+        .bind(&certificate.detection_action)
+        .bind(&certificate.pseudonym)
+        .bind(serde_json::to_string(&certificate.image_keys)?)
+        .bind(certificate.face_images)
+        .bind(certificate.detections)
+        .bind(certificate.feedback)
+        .bind(certificate.video_appearances)
+        .bind(certificate.model_users)
+        .bind(&certificate.erased_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    // Get a tenant's erasure certificates, newest first
+    pub async fn get_erasure_certificates(&self, tenant_id: &str) -> Result<Vec<ErasureCertificate>> {
+        let rows = sqlx::query("SELECT * FROM erasure_certificates WHERE tenant_id = $1 ORDER BY id DESC")
+            .bind(tenant_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter().map(erasure_certificate_from_row).collect()
+    }
+
+    pub async fn get_erasure_certificate(&self, id: i64) -> Result<Option<ErasureCertificate>> {
+        let row = sqlx::query("SELECT * FROM erasure_certificates WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.as_ref().map(erasure_certificate_from_row).transpose()
+    }
+
+    // Record an audited operation
+    pub async fn insert_audit_event(&self, event: &AuditEvent) -> Result<i64> {
+        let id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO audit_events (request_id, actor, action, target, outcome, status, detail, occurred_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id
+            "#,
+        )
+        .bind(&event.request_id)
+        .bind(&event.actor)
+        .bind(&event.action)
+        .bind(&event.target)
+        .bind(&event.outcome)
+        .bind(event.status)
+        .bind(&event.detail)
+        .bind(&event.occurred_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    // Get audit events matching every filter, newest first
+    pub async fn get_audit_events(&self, filter: &AuditFilter) -> Result<Vec<AuditEvent>> {
+        let mut conditions = Vec::new();
+        let mut binds = Vec::new();
+        let mut condition = |sql: &str, value: Bind| {
+            binds.push(value);
+            conditions.push(sql.replacen('?', &format!("${}", binds.len()), 1));
+        };
+
+        for (column, value) in [
+            ("actor", &filter.actor),
+            ("action", &filter.action),
+            ("target", &filter.target),
+            ("outcome", &filter.outcome),
+            ("request_id", &filter.request_id),
+        ] {
+            if let Some(value) = value {
+                condition(&format!("{} = ?", column), Bind::Text(value.clone()));
+            }
+        }
+        if let Some(from) = &filter.from {
+            condition("occurred_at >= ?", Bind::Text(from.clone()));
+        }
+        if let Some(to) = &filter.to {
+            condition("occurred_at < ?", Bind::Text(to.clone()));
+        }
+        if let Some(before) = filter.before {
+            condition("id < ?", Bind::Int(before));
+        }
+
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        binds.push(Bind::Int(filter.limit));
+        let sql = format!(
+            "SELECT * FROM audit_events {} ORDER BY id DESC LIMIT ${}",
+            where_clause,
+            binds.len()
+        );
+
+        let mut query = sqlx::query(&sql);
+        for value in binds {
+            query = match value {
+                Bind::Text(text) => query.bind(text),
+                Bind::Real(real) => query.bind(real),
+                Bind::Int(int) => query.bind(int),
+            };
+        }
+
+        let rows = query.fetch_all(&self.pool).await?;
+
+        let events = rows
+            .iter()
+            .map(|row| AuditEvent {
+                id: row.get("id"),
+                request_id: row.get("request_id"),
+                actor: row.get("actor"),
+                action: row.get("action"),
+                target: row.get("target"),
+                outcome: row.get("outcome"),
+                status: row.get("status"),
+                detail: row.get("detail"),
+                occurred_at: row.get("occurred_at"),
+            })
+            .collect();
+
+        Ok(events)
+    }
+
+    // Get a persisted setting
+    pub async fn get_setting(&self, key: &str) -> Result<Option<String>> {
+        let value: Option<String> = sqlx::query_scalar("SELECT value FROM settings WHERE key = $1")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(value)
+    }
+
+    // Insert or update a persisted setting
+    pub async fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+
+        sqlx::query(
+            r#"
+            INSERT INTO settings (key, value, updated_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(key)
+        .bind(value)
+        .bind(&now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Insert or replace an image kept by the database image store
+    pub async fn put_image_blob(&self, key: &str, data: &[u8]) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO image_blobs (key, data)
+            VALUES ($1, $2)
+            ON CONFLICT(key) DO UPDATE SET data = excluded.data
+            "#,
+        )
+        .bind(key)
+        .bind(data)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Get an image kept by the database image store
+    pub async fn get_image_blob(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let data: Option<Vec<u8>> = sqlx::query_scalar("SELECT data FROM image_blobs WHERE key = $1")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(data)
+    }
+
+    // Get the keys of the images kept by the database image store under `{prefix}/`
+    pub async fn get_image_blob_keys(&self, prefix: &str) -> Result<Vec<String>> {
+        let prefix = format!("{}/", prefix);
+        let pattern = format!("{}%", prefix.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+        let keys: Vec<String> = sqlx::query_scalar(r"SELECT key FROM image_blobs WHERE key LIKE $1 ESCAPE '\' ORDER BY key")
+            .bind(pattern)
+            .fetch_all(&self.pool)
+            .await?;
+
+        // LIKE ignores case on SQLite
+        Ok(keys.into_iter().filter(|key| key.starts_with(&prefix)).collect())
+    }
+
+    // Delete an image kept by the database image store
+    pub async fn delete_image_blob(&self, key: &str) -> Result<()> {
+        sqlx::query("DELETE FROM image_blobs WHERE key = $1")
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Store a new API key by its hash
+    pub async fn insert_api_key(&self, name: &str, role: &str, key_prefix: &str, key_hash: &str) -> Result<i64> {
+        let now = Utc::now().to_rfc3339();
+
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO api_keys (name, role, key_prefix, key_hash, created_at) VALUES ($1, $2, $3, $4, $5) RETURNING id",
+        )
+        .bind(name)
+        .bind(role)
+        .bind(key_prefix)
+        .bind(key_hash)
+        .bind(&now)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    // Get an active (not revoked) API key by its hash
+    pub async fn find_api_key(&self, key_hash: &str) -> Result<Option<ApiKey>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, name, role, key_prefix, created_at, last_used_at, revoked_at
+            FROM api_keys
+            WHERE key_hash = $1 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(key_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(api_key_from_row))
+    }
+
+    // Get an API key by ID
+    pub async fn get_api_key(&self, id: i64) -> Result<Option<ApiKey>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, name, role, key_prefix, created_at, last_used_at, revoked_at
+            FROM api_keys
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(api_key_from_row))
+    }
+
+    // Get all API keys, newest first
+    pub async fn get_api_keys(&self) -> Result<Vec<ApiKey>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, name, role, key_prefix, created_at, last_used_at, revoked_at
+            FROM api_keys
+            ORDER BY id DESC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(api_key_from_row).collect())
+    }
+
+    // Record that an API key was just used
+    pub async fn touch_api_key(&self, id: i64) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+
+        sqlx::query("UPDATE api_keys SET last_used_at = $1 WHERE id = $2")
+            .bind(&now)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Revoke an active API key; returns false if there was none
+    pub async fn revoke_api_key(&self, id: i64) -> Result<bool> {
+        let now = Utc::now().to_rfc3339();
+
+        let result = sqlx::query("UPDATE api_keys SET revoked_at = $1 WHERE id = $2 AND revoked_at IS NULL")
+            .bind(&now)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+fn detection_from_row(row: &AnyRow) -> DetectionLog {
+    DetectionLog {
+        id: row.get("id"),
+        tenant_id: row.get("tenant_id"),
+        detected_user_id: row.get("detected_user_id"),
+        confidence: row.get("confidence"),
+        image_path: row.get("image_path"),
+        source: row.get("source"),
+        detected_at: row.get("detected_at"),
+    }
+}
+
+fn erasure_certificate_from_row(row: &AnyRow) -> Result<ErasureCertificate> {
+    let image_keys: String = row.get("image_keys");
+
+    Ok(ErasureCertificate {
+        id: row.get("id"),
+        tenant_id: row.get("tenant_id"),
+        user_id: row.get("user_id"),
+        erased_by: row.get("erased_by"),
+        detection_action: row.get("detection_action"),
+        pseudonym: row.get("pseudonym"),
+        image_keys: serde_json::from_str(&image_keys)?,
+        face_images: row.get("face_images"),
+        detections: row.get("detections"),
+        feedback: row.get("feedback"),
+        video_appearances: row.get("video_appearances"),
+        model_users: row.get("model_users"),
+        erased_at: row.get("erased_at"),
+    })
+}
+
+fn api_key_from_row(row: &AnyRow) -> ApiKey {
+    ApiKey {
+        id: row.get("id"),
+        name: row.get("name"),
+        role: row.get("role"),
+        key_prefix: row.get("key_prefix"),
+        created_at: row.get("created_at"),
+        last_used_at: row.get("last_used_at"),
+        revoked_at: row.get("revoked_at"),
+    }
+}
+
+/// chrono format of `detection_logs.detected_at`, which is stored as UTC text so that it
+/// sorts and compares the same on SQLite and PostgreSQL
+pub const DETECTED_AT_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// A logged face detection
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DetectionLog {
+    pub id: i64,
+    pub tenant_id: String,
+    /// Matched user; null when the face matched nobody
+    pub detected_user_id: Option<String>,
+    /// LBPH distance to the closest identity (lower is closer)
+    pub confidence: Option<f64>,
+    /// Temporary path the probe was processed from
+    pub image_path: Option<String>,
+    /// Camera or other client the probe came from, as given to `/detect-face`
+    pub source: Option<String>,
+    /// UTC, `YYYY-MM-DD HH:MM:SS`
+    pub detected_at: String,
+}
+
+/// Which detections to return and in what order
+#[derive(Debug, Clone)]
+pub struct DetectionFilter {
+    pub tenant_id: String,
+    pub user_id: Option<String>,
+    /// Only detections that did (true) or did not (false) match a user
+    pub matched: Option<bool>,
+    pub min_confidence: Option<f64>,
+    pub max_confidence: Option<f64>,
+    /// Inclusive lower and exclusive upper bound on `detected_at`, in its format
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub source: Option<String>,
+    pub sort: DetectionSort,
+    pub order: SortOrder,
+    /// Last detection of the previous page
+    pub after: Option<DetectionCursor>,
+    pub limit: i64,
+}
+
+/// Sort key and ID of the last detection of a page. The key is carried rather than looked
+/// up, so paging continues even when that detection has since been deleted.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DetectionCursor {
+    DetectedAt(String, i64),
+    /// A NULL confidence sorts as -1
+    Confidence(f64, i64),
+}
+
+impl DetectionCursor {
+    pub fn after(detection: &DetectionLog, sort: DetectionSort) -> Self {
+        match sort {
+            DetectionSort::DetectedAt => Self::DetectedAt(detection.detected_at.clone(), detection.id),
+            DetectionSort::Confidence => Self::Confidence(detection.confidence.unwrap_or(-1.0), detection.id),
+        }
+    }
+
+    pub fn sort(&self) -> DetectionSort {
+        match self {
+            Self::DetectedAt(..) => DetectionSort::DetectedAt,
+            Self::Confidence(..) => DetectionSort::Confidence,
+        }
+    }
+
+    pub fn id(&self) -> i64 {
+        match self {
+            Self::DetectedAt(_, id) | Self::Confidence(_, id) => *id,
+        }
+    }
+}
+
+// Opaque and URL-safe: base64 of `{sort}:{id}:{key}`
+impl fmt::Display for DetectionCursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            Self::DetectedAt(detected_at, id) => format!("d:{}:{}", id, detected_at),
+            Self::Confidence(confidence, id) => format!("c:{}:{}", id, confidence),
+        };
+        f.write_str(&URL_SAFE_NO_PAD.encode(text))
+    }
+}
+
+impl FromStr for DetectionCursor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow::anyhow!("Invalid cursor: {}", s);
+        let text = String::from_utf8(URL_SAFE_NO_PAD.decode(s).map_err(|_| invalid())?).map_err(|_| invalid())?;
+        let mut parts = text.splitn(3, ':');
+        let (Some(sort), Some(id), Some(key)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid());
+        };
+        let id = id.parse().map_err(|_| invalid())?;
+
+        match sort {
+            "d" => Ok(Self::DetectedAt(key.to_string(), id)),
+            "c" => Ok(Self::Confidence(key.parse().map_err(|_| invalid())?, id)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectionSort {
+    #[default]
+    DetectedAt,
+    Confidence,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+// A value bound to a query assembled at runtime
+enum Bind {
+    Text(String),
+    Real(f64),
+    Int(i64),
+}
+
+/// A tenant's gallery size
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TenantSummary {
+    pub tenant_id: String,
+    pub users: i64,
+    pub images: i64,
+}
+
+/// A detection with operator feedback, used for threshold calibration
+#[derive(Debug, Clone)]
+pub struct FeedbackSample {
+    pub detected_user_id: Option<String>,
+    pub confidence: f64,
+    /// One of `correct`, `incorrect`, `unknown_person`
+    pub verdict: String,
+}
+
+/// Counters reported while a video job is running
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct VideoJobProgress {
+    /// Frames read from the source
+    pub frames_read: i64,
+    /// Frames passed through the recognizer (every `frame_stride`-th frame)
+    pub frames_analyzed: i64,
+    /// Faces detected across all analyzed frames
+    pub faces_detected: i64,
+    /// Detected faces that did not match any enrolled user
+    pub unknown_faces: i64,
+}
+
+/// A video analysis job
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct VideoJob {
+    /// Job ID
+    pub id: String,
+    /// Video file path or stream URL
+    pub source: String,
+    /// Only every n-th frame is analyzed
+    pub frame_stride: i64,
+    /// One of `queued`, `running`, `completed`, `failed`
+    pub status: String,
+    #[serde(flatten)]
+    pub progress: VideoJobProgress,
+    /// Path of the annotated output video, if requested
+    pub output_path: Option<String>,
+    /// Error message if the job failed
+    pub error: Option<String>,
+    pub created_at: String,
+    pub finished_at: Option<String>,
+}
+
+/// A continuous stretch of time in which an enrolled user was seen in a video
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct VideoAppearance {
+    /// Recognized user ID
+    pub user_id: String,
+    pub first_frame: i64,
+    pub last_frame: i64,
+    /// Offset from the start of the video in milliseconds
+    pub first_seen_ms: i64,
+    /// Offset from the start of the video in milliseconds
+    pub last_seen_ms: i64,
+    /// Number of analyzed frames the user was recognized in
+    pub detections: i64,
+    /// Lowest (best) LBPH distance observed
+    pub best_confidence: f64,
+}
+
+/// Stored descriptor of a retained unknown face
+#[derive(Debug, Clone)]
+pub struct UnknownFaceFeatures {
+    pub id: i64,
+    pub cluster_id: Option<i64>,
+    pub features: Vec<u8>,
+}
+
+/// A group of unknown faces that look like the same person
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UnknownCluster {
+    pub id: i64,
+    pub face_count: i64,
+    pub first_seen: String,
+    pub last_seen: String,
+}
+
+/// A retained face that did not match any enrolled user
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UnknownFace {
+    pub id: i64,
+    pub image_path: String,
+    /// Detection log entry the face came from
+    pub detection_id: Option<i64>,
+    /// LBPH distance to the closest enrolled user
+    pub confidence: Option<f64>,
+    pub created_at: String,
+}
+
+/// An API key without its secret
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ApiKey {
+    pub id: i64,
+    pub name: String,
+    /// `admin`, `operator` or `viewer`
+    pub role: String,
+    /// First characters of the key, to tell keys apart
+    pub key_prefix: String,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+    pub revoked_at: Option<String>,
+}
+
+/// Record of a user's biometric data being erased from every store
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ErasureCertificate {
+    pub id: i64,
+    pub tenant_id: String,
+    pub user_id: String,
+    /// API key name or token subject of the caller, or `cli`
+    pub erased_by: String,
+    /// `pseudonymize` or `delete`, what was done to the user's detections
+    pub detection_action: String,
+    /// ID that replaced the user's in detections, feedback and video appearances
+    pub pseudonym: Option<String>,
+    /// Image store keys of the deleted enrollment images
+    pub image_keys: Vec<String>,
+    /// Deleted `face_images` rows
+    pub face_images: i64,
+    /// Detections pseudonymized or deleted
+    pub detections: i64,
+    /// Feedback rows that named the user as the actual person
+    pub feedback: i64,
+    pub video_appearances: i64,
+    /// Users in the tenant's model after retraining without the erased user
+    pub model_users: i64,
+    pub erased_at: String,
+}
+
+/// An audited operation: an API request that changes data, or an administration command
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AuditEvent {
+    pub id: i64,
+    /// `X-Request-Id` of the request (generated if the client sent none), or a new ID per CLI command
+    pub request_id: String,
+    /// API key name or token subject of the caller, `anonymous` without authentication, or `cli`
+    pub actor: String,
+    /// What was done, such as `enroll`, `erase` or `set_threshold`
+    pub action: String,
+    /// What it was done to, such as `{tenant}/{user_id}`, when known
+    pub target: Option<String>,
+    /// `success` or `failure`
+    pub outcome: String,
+    /// HTTP status of the response; null for CLI commands
+    pub status: Option<i64>,
+    /// Extra context, such as the new threshold or why a CLI command failed
+    pub detail: Option<String>,
+    /// UTC, `YYYY-MM-DD HH:MM:SS`
+    pub occurred_at: String,
+}
+
+/// Filters of the audit log; `None` matches everything
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub target: Option<String>,
+    pub outcome: Option<String>,
+    pub request_id: Option<String>,
+    /// Inclusive lower and exclusive upper bound on `occurred_at`, in its format
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// ID of the last event of the previous page
+    pub before: Option<i64>,
+    pub limit: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DatabaseConfig;
+
+    const TENANT: &str = "acme";
+
+    // Pairs of equal timestamps and confidences, and NULL confidences, so every page boundary
+    // falls between rows with the same sort key
+    const DETECTIONS: [(&str, Option<f64>); 7] = [
+        ("2024-01-01 08:00:00", Some(40.0)),
+        ("2024-01-01 08:00:00", None),
+        ("2024-01-01 09:00:00", Some(40.0)),
+        ("2024-01-01 08:00:00", Some(55.5)),
+        ("2024-01-01 09:00:00", None),
+        ("2024-01-01 10:00:00", Some(40.0)),
+        ("2024-01-01 09:00:00", Some(12.25)),
+    ];
+
+    async fn logged() -> (Database, Vec<(i64, &'static str, Option<f64>)>) {
+        let config = DatabaseConfig {
+            url: "sqlite::memory:".to_string(),
+            max_connections: 1,
+        };
+        let db = Database::new(&config).await.unwrap();
+
+        let mut rows = Vec::new();
+        for (detected_at, confidence) in DETECTIONS {
+            let id = db.log_detection(TENANT, None, confidence, None, None).await.unwrap();
+            sqlx::query("UPDATE detection_logs SET detected_at = $1 WHERE id = $2")
+                .bind(detected_at)
+                .bind(id)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+            rows.push((id, detected_at, confidence));
+        }
+        db.log_detection("other", None, Some(40.0), None, None).await.unwrap();
+
+        (db, rows)
+    }
+
+    fn filter(sort: DetectionSort, order: SortOrder, after: Option<DetectionCursor>) -> DetectionFilter {
+        DetectionFilter {
+            tenant_id: TENANT.to_string(),
+            user_id: None,
+            matched: None,
+            min_confidence: None,
+            max_confidence: None,
+            from: None,
+            to: None,
+            source: None,
+            sort,
+            order,
+            after,
+            limit: 2,
+        }
+    }
+
+    // IDs in (sort key, id) order, computed independently of SQL
+    fn expected(rows: &[(i64, &str, Option<f64>)], sort: DetectionSort, order: SortOrder) -> Vec<i64> {
+        let mut rows = rows.to_vec();
+        rows.sort_by(|a, b| {
+            let by_key = match sort {
+                DetectionSort::DetectedAt => a.1.cmp(b.1),
+                DetectionSort::Confidence => a.2.unwrap_or(-1.0).total_cmp(&b.2.unwrap_or(-1.0)),
+            };
+            by_key.then(a.0.cmp(&b.0))
+        });
+        if order == SortOrder::Desc {
+            rows.reverse();
+        }
+        rows.iter().map(|row| row.0).collect()
+    }
+
+    async fn all_pages(db: &Database, sort: DetectionSort, order: SortOrder) -> Vec<i64> {
+        let mut ids = Vec::new();
+        let mut after = None;
+        loop {
+            let page = db.get_detections(&filter(sort, order, after)).await.unwrap();
+            let Some(last) = page.last() else {
+                return ids;
+            };
+            after = Some(DetectionCursor::after(last, sort).to_string().parse().unwrap());
+            ids.extend(page.iter().map(|detection| detection.id));
+        }
+    }
+
+    #[tokio::test]
+    async fn pages_through_equal_sort_keys_in_both_orders() {
+        let (db, rows) = logged().await;
+
+        for sort in [DetectionSort::DetectedAt, DetectionSort::Confidence] {
+            for order in [SortOrder::Asc, SortOrder::Desc] {
+                assert_eq!(all_pages(&db, sort, order).await, expected(&rows, sort, order), "{:?} {:?}", sort, order);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn pages_continue_after_the_cursor_row_is_deleted() {
+        let (db, mut rows) = logged().await;
+
+        for sort in [DetectionSort::DetectedAt, DetectionSort::Confidence] {
+            let order = expected(&rows, sort, SortOrder::Desc);
+            let page = db.get_detections(&filter(sort, SortOrder::Desc, None)).await.unwrap();
+            let after = DetectionCursor::after(page.last().unwrap(), sort);
+
+            rows.retain(|row| row.0 != after.id());
+            sqlx::query("DELETE FROM detection_logs WHERE id = $1")
+                .bind(after.id())
+                .execute(&db.pool)
+                .await
+                .unwrap();
+
+            let next = db.get_detections(&filter(sort, SortOrder::Desc, Some(after))).await.unwrap();
+            let ids: Vec<i64> = next.iter().map(|detection| detection.id).collect();
+            assert_eq!(ids, order[2..4], "{:?}", sort);
+        }
+    }
+
+    #[tokio::test]
+    async fn cursors_must_match_the_sort() {
+        let (db, _) = logged().await;
+        let after = DetectionCursor::Confidence(40.0, 1);
+
+        assert!(db.get_detections(&filter(DetectionSort::DetectedAt, SortOrder::Desc, Some(after))).await.is_err());
+    }
+
+    #[test]
+    fn cursor_text_round_trips() {
+        for cursor in [
+            DetectionCursor::DetectedAt("2024-01-01 08:00:00".to_string(), 7),
+            DetectionCursor::Confidence(-1.0, 3),
+            DetectionCursor::Confidence(42.29999923706055, 12),
+        ] {
+            let text = cursor.to_string();
+            assert!(text.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'), "{}", text);
+            assert_eq!(text.parse::<DetectionCursor>().unwrap(), cursor);
+        }
+
+        for text in ["", "42", "not base64!", &URL_SAFE_NO_PAD.encode("x:1:key"), &URL_SAFE_NO_PAD.encode("c:1:high")] {
+            assert!(text.parse::<DetectionCursor>().is_err(), "{}", text);
+        }
+    }
+}
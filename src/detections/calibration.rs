@@ -0,0 +1,210 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+use crate::database::FeedbackSample;
+
+/// Operator verdict on a logged detection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedbackVerdict {
+    /// The reported user (or "no match") was right
+    Correct,
+    /// The reported user was wrong, or an enrolled user was not recognized
+    Incorrect,
+    /// The face belongs to someone who is not enrolled
+    UnknownPerson,
+}
+
+impl FeedbackVerdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Correct => "correct",
+            Self::Incorrect => "incorrect",
+            Self::UnknownPerson => "unknown_person",
+        }
+    }
+}
+
+impl FromStr for FeedbackVerdict {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "correct" => Ok(Self::Correct),
+            "incorrect" => Ok(Self::Incorrect),
+            "unknown_person" => Ok(Self::UnknownPerson),
+            other => Err(anyhow::anyhow!(
+                "Unknown verdict '{}'. Use correct, incorrect or unknown_person",
+                other
+            )),
+        }
+    }
+}
+
+/// Suggested match threshold and the error rates it is expected to give
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CalibrationReport {
+    /// False-accept rate the suggestion aims for
+    pub target_far: f64,
+    /// Detections where the face was an enrolled user's
+    pub genuine_samples: usize,
+    /// Detections where the face should not have matched anyone
+    pub impostor_samples: usize,
+    pub current_threshold: f64,
+    pub current_far: f64,
+    /// Null when there are no genuine samples
+    pub current_frr: Option<f64>,
+    pub suggested_threshold: f64,
+    pub suggested_far: f64,
+    pub suggested_frr: Option<f64>,
+    /// Whether the suggested threshold is now in use
+    pub applied: bool,
+}
+
+/// Split feedback into genuine and impostor LBPH distances.
+///
+/// A match marked incorrect or unknown_person, and a non-match that was correct or an unknown
+/// person, are impostor attempts. A correct match and a missed enrolled user are genuine.
+/// Misses are treated as genuine for the nearest user, since the nearest label is not logged.
+fn split_samples(samples: &[FeedbackSample]) -> (Vec<f64>, Vec<f64>) {
+    let mut genuine = Vec::new();
+    let mut impostors = Vec::new();
+
+    for sample in samples {
+        let Ok(verdict) = sample.verdict.parse::<FeedbackVerdict>() else {
+            continue;
+        };
+        if !sample.confidence.is_finite() {
+            continue;
+        }
+
+        let matched = sample.detected_user_id.is_some();
+        match (matched, verdict) {
+            (true, FeedbackVerdict::Correct) | (false, FeedbackVerdict::Incorrect) => {
+                genuine.push(sample.confidence)
+            }
+            _ => impostors.push(sample.confidence),
+        }
+    }
+
+    (genuine, impostors)
+}
+
+/// Suggest the largest threshold whose false-accept rate on the feedback stays at or below
+/// `target_far`
+pub fn calibrate(
+    samples: &[FeedbackSample],
+    current_threshold: f64,
+    target_far: f64,
+    min_samples: usize,
+) -> Result<CalibrationReport> {
+    if !(0.0..1.0).contains(&target_far) {
+        return Err(anyhow::anyhow!("target_far must be in 0..1"));
+    }
+
+    let (genuine, mut impostors) = split_samples(samples);
+    if impostors.len() < min_samples.max(1) {
+        return Err(anyhow::anyhow!(
+            "Not enough feedback: {} impostor samples, at least {} required",
+            impostors.len(),
+            min_samples.max(1)
+        ));
+    }
+
+    impostors.sort_by(f64::total_cmp);
+
+    // Accepting is `distance < threshold`, so the n-th smallest impostor distance lets exactly
+    // the n closer ones through
+    let allowed = (target_far * impostors.len() as f64).floor() as usize;
+    let suggested_threshold = impostors[allowed.min(impostors.len() - 1)];
+
+    let far = |threshold: f64| {
+        impostors.iter().filter(|d| **d < threshold).count() as f64 / impostors.len() as f64
+    };
+    let frr = |threshold: f64| {
+        (!genuine.is_empty()).then(|| {
+            genuine.iter().filter(|d| **d >= threshold).count() as f64 / genuine.len() as f64
+        })
+    };
+
+    Ok(CalibrationReport {
+        target_far,
+        genuine_samples: genuine.len(),
+        impostor_samples: impostors.len(),
+        current_threshold,
+        current_far: far(current_threshold),
+        current_frr: frr(current_threshold),
+        suggested_threshold,
+        suggested_far: far(suggested_threshold),
+        suggested_frr: frr(suggested_threshold),
+        applied: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(user_id: Option<&str>, confidence: f64, verdict: &str) -> FeedbackSample {
+        FeedbackSample {
+            detected_user_id: user_id.map(str::to_string),
+            confidence,
+            verdict: verdict.to_string(),
+        }
+    }
+
+    #[test]
+    fn splits_feedback_by_what_the_face_was() {
+        let samples = [
+            sample(Some("alice"), 30.0, "correct"),
+            sample(None, 90.0, "incorrect"),
+            sample(Some("alice"), 40.0, "incorrect"),
+            sample(Some("alice"), 45.0, "unknown_person"),
+            sample(None, 95.0, "correct"),
+            sample(None, 99.0, "unknown_person"),
+            sample(Some("alice"), 20.0, "maybe"),
+            sample(Some("alice"), f64::INFINITY, "correct"),
+        ];
+
+        let (genuine, impostors) = split_samples(&samples);
+        assert_eq!(genuine, [30.0, 90.0]);
+        assert_eq!(impostors, [40.0, 45.0, 95.0, 99.0]);
+    }
+
+    #[test]
+    fn suggests_the_largest_threshold_within_the_target() {
+        // Impostors at 10, 20, ..., 100 and genuine matches at 5, 15, 25 and 35
+        let mut samples: Vec<FeedbackSample> =
+            (1..=10).rev().map(|i| sample(Some("bob"), i as f64 * 10.0, "incorrect")).collect();
+        samples.extend([5.0, 15.0, 25.0, 35.0].map(|d| sample(Some("alice"), d, "correct")));
+
+        let report = calibrate(&samples, 50.0, 0.2, 5).unwrap();
+        assert_eq!((report.genuine_samples, report.impostor_samples), (4, 10));
+        // Accepting below 30 lets exactly the impostors at 10 and 20 through
+        assert_eq!(report.suggested_threshold, 30.0);
+        assert_eq!(report.suggested_far, 0.2);
+        assert_eq!(report.suggested_frr, Some(0.25));
+        assert_eq!((report.current_far, report.current_frr), (0.4, Some(0.0)));
+        assert!(!report.applied);
+
+        let report = calibrate(&samples, 50.0, 0.0, 5).unwrap();
+        assert_eq!((report.suggested_threshold, report.suggested_far), (10.0, 0.0));
+        assert_eq!(report.suggested_frr, Some(0.75));
+    }
+
+    #[test]
+    fn needs_enough_impostors_and_a_valid_target() {
+        let samples: Vec<FeedbackSample> = (1..=3).map(|i| sample(None, i as f64 * 10.0, "correct")).collect();
+
+        let report = calibrate(&samples, 50.0, 0.1, 3).unwrap();
+        assert_eq!(report.suggested_threshold, 10.0);
+        assert_eq!(report.current_frr, None);
+
+        assert!(calibrate(&samples, 50.0, 0.1, 4).is_err());
+        assert!(calibrate(&[], 50.0, 0.1, 0).is_err());
+        assert!(calibrate(&samples, 50.0, 1.0, 3).is_err());
+        assert!(calibrate(&samples, 50.0, -0.1, 3).is_err());
+    }
+}
@@ -0,0 +1,425 @@
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::calibration::{calibrate, CalibrationReport, FeedbackVerdict};
+use super::match_threshold_setting;
+use crate::audit::AuditContext;
+use crate::config::SharedConfig;
+use crate::database::{DetectionCursor, DetectionFilter, DetectionLog, DetectionSort, SortOrder, DETECTED_AT_FORMAT};
+use crate::face_recognition::handlers::{tenant_model, ApiResponse, SharedDb, SharedModel};
+use crate::tenants::registry::SharedRegistry;
+use crate::tenants::{check_tenant_id, DEFAULT_TENANT};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+/// Request body for detection feedback
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct FeedbackRequest {
+    verdict: FeedbackVerdict,
+    /// Who the face actually belongs to, if known
+    actual_user_id: Option<String>,
+    /// Free-form operator note
+    note: Option<String>,
+}
+
+/// Response for detection feedback
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FeedbackResponse {
+    detection_id: i64,
+    verdict: FeedbackVerdict,
+    /// Recalibration result, present when `calibration.auto_apply` is enabled and there was
+    /// enough feedback
+    #[serde(skip_serializing_if = "Option::is_none")]
+    calibration: Option<CalibrationReport>,
+}
+
+/// Query parameters for threshold calibration
+#[derive(Debug, Deserialize)]
+pub struct CalibrationQuery {
+    /// Tenant whose feedback and model are calibrated (default `default`)
+    tenant: Option<String>,
+    /// Overrides `calibration.target_far`
+    target_far: Option<f64>,
+}
+
+/// Query parameters for a single detection
+#[derive(Debug, Deserialize)]
+pub struct DetectionQuery {
+    /// Tenant the detection was logged for (default `default`)
+    tenant: Option<String>,
+}
+
+/// Query parameters for the detection history
+#[derive(Debug, Deserialize)]
+pub struct DetectionsQuery {
+    /// Tenant whose detections are listed (default `default`)
+    tenant: Option<String>,
+    user_id: Option<String>,
+    matched: Option<bool>,
+    min_confidence: Option<f64>,
+    max_confidence: Option<f64>,
+    /// RFC 3339, `YYYY-MM-DD HH:MM:SS` (UTC) or `YYYY-MM-DD`; inclusive
+    from: Option<String>,
+    /// Same formats as `from`; exclusive
+    to: Option<String>,
+    source: Option<String>,
+    #[serde(default)]
+    sort: DetectionSort,
+    #[serde(default)]
+    order: SortOrder,
+    /// `next_cursor` of the previous page, with the same filters and sorting
+    cursor: Option<String>,
+    limit: Option<i64>,
+}
+
+/// One page of the detection history
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DetectionPage {
+    detections: Vec<DetectionLog>,
+    /// Pass as `cursor` to get the next page; null on the last page
+    next_cursor: Option<String>,
+}
+
+/// List logged detections
+///
+/// Returns a tenant's detections matching every given filter, newest first by default.
+/// Pages are linked by `next_cursor`; keep the filters and sorting the same while paging.
+#[utoipa::path(
+    get,
+    path = "/detections",
+    params(
+        ("tenant" = Option<String>, Query, description = "Tenant ID (default `default`)"),
+        ("user_id" = Option<String>, Query, description = "Only detections matched to this user"),
+        ("matched" = Option<bool>, Query, description = "Only detections that matched a user (true) or nobody (false)"),
+        ("min_confidence" = Option<f64>, Query, description = "Lowest LBPH distance"),
+        ("max_confidence" = Option<f64>, Query, description = "Highest LBPH distance"),
+        ("from" = Option<String>, Query, description = "Detected at or after (RFC 3339, `YYYY-MM-DD HH:MM:SS` UTC or `YYYY-MM-DD`)"),
+        ("to" = Option<String>, Query, description = "Detected before (same formats as `from`)"),
+        ("source" = Option<String>, Query, description = "Camera or client given to /detect-face"),
+        ("sort" = Option<DetectionSort>, Query, description = "detected_at (default) or confidence"),
+        ("order" = Option<SortOrder>, Query, description = "desc (default) or asc"),
+        ("cursor" = Option<String>, Query, description = "next_cursor of the previous page"),
+        ("limit" = Option<i64>, Query, description = "Page size, 1-500 (default 50)")
+    ),
+    responses(
+        (status = 200, description = "Detections", body = ApiResponse<DetectionPage>),
+        (status = 400, description = "Invalid filter, cursor or limit"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Detections"
+)]
+pub async fn list_detections(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+    Query(query): Query<DetectionsQuery>,
+) -> Result<Json<ApiResponse<DetectionPage>>, (StatusCode, String)> {
+    let tenant = query.tenant.unwrap_or_else(|| DEFAULT_TENANT.to_string());
+    check_tenant_id(&tenant).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err((StatusCode::BAD_REQUEST, format!("limit must be between 1 and {}", MAX_PAGE_SIZE)));
+    }
+
+    let after = query
+        .cursor
+        .map(|cursor| {
+            cursor
+                .parse::<DetectionCursor>()
+                .ok()
+                .filter(|after| after.sort() == query.sort)
+                .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("Invalid cursor: {}", cursor)))
+        })
+        .transpose()?;
+
+    let filter = DetectionFilter {
+        tenant_id: tenant,
+        user_id: query.user_id,
+        matched: query.matched,
+        min_confidence: query.min_confidence,
+        max_confidence: query.max_confidence,
+        from: query.from.as_deref().map(|from| parse_time("from", from)).transpose()?,
+        to: query.to.as_deref().map(|to| parse_time("to", to)).transpose()?,
+        source: query.source,
+        sort: query.sort,
+        order: query.order,
+        after,
+        // One more than asked tells whether there is a next page
+        limit: limit + 1,
+    };
+
+    let mut detections = db
+        .get_detections(&filter)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let next_cursor = if detections.len() as i64 > limit {
+        detections.truncate(limit as usize);
+        detections.last().map(|detection| DetectionCursor::after(detection, query.sort).to_string())
+    } else {
+        None
+    };
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Found {} detections", detections.len()),
+        data: Some(DetectionPage { detections, next_cursor }),
+    }))
+}
+
+/// Get a logged detection
+#[utoipa::path(
+    get,
+    path = "/detections/{id}",
+    params(
+        ("id" = i64, Path, description = "Detection ID returned by /detect-face"),
+        ("tenant" = Option<String>, Query, description = "Tenant ID (default `default`)")
+    ),
+    responses(
+        (status = 200, description = "Detection", body = ApiResponse<DetectionLog>),
+        (status = 400, description = "Invalid tenant"),
+        (status = 404, description = "Detection not found in the tenant"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Detections"
+)]
+pub async fn get_detection(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+    Path(id): Path<i64>,
+    Query(query): Query<DetectionQuery>,
+) -> Result<Json<ApiResponse<DetectionLog>>, (StatusCode, String)> {
+    let tenant = query.tenant.unwrap_or_else(|| DEFAULT_TENANT.to_string());
+    check_tenant_id(&tenant).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    let detection = db
+        .get_detection(&tenant, id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, format!("Detection not found: {}", id)))?;
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: "Detection found".to_string(),
+        data: Some(detection),
+    }))
+}
+
+/// Give feedback on a logged detection
+///
+/// Marks the detection as `correct`, `incorrect` or `unknown_person`. Submitting again
+/// replaces the earlier feedback. The feedback and the logged confidence are used to
+/// calibrate the match threshold of the detection's tenant.
+#[utoipa::path(
+    post,
+    path = "/detections/{id}/feedback",
+    params(
+        ("id" = i64, Path, description = "Detection ID returned by /detect-face"),
+        ("tenant" = Option<String>, Query, description = "Tenant ID (default `default`)")
+    ),
+    request_body = FeedbackRequest,
+    responses(
+        (status = 200, description = "Feedback recorded", body = ApiResponse<FeedbackResponse>),
+        (status = 400, description = "Invalid tenant"),
+        (status = 404, description = "Detection not found in the tenant"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Detections"
+)]
+pub async fn submit_feedback(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+    Extension(config): Extension<SharedConfig>,
+    Extension(registry): Extension<SharedRegistry>,
+    Extension(audit): Extension<AuditContext>,
+    Path(id): Path<i64>,
+    Query(query): Query<DetectionQuery>,
+    Json(request): Json<FeedbackRequest>,
+) -> Result<Json<ApiResponse<FeedbackResponse>>, (StatusCode, String)> {
+    let tenant = query.tenant.unwrap_or_else(|| DEFAULT_TENANT.to_string());
+    audit.target(format!("{}/detection/{}", tenant, id));
+    check_tenant_id(&tenant).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    db.get_detection(&tenant, id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, format!("Detection not found: {}", id)))?;
+
+    db.upsert_detection_feedback(
+        id,
+        request.verdict.as_str(),
+        request.actual_user_id.as_deref(),
+        request.note.as_deref(),
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let settings = &config.calibration;
+    let calibration = if settings.auto_apply {
+        let model = tenant_model(&registry, &tenant).await?;
+        match calibrate_threshold(&model, &db, &tenant, settings.target_far, settings.min_samples, true).await {
+            Ok(report) => Some(report),
+            Err((StatusCode::BAD_REQUEST, message)) => {
+                println!("Skipping recalibration: {}", message);
+                None
+            }
+            Err(e) => return Err(e),
+        }
+    } else {
+        None
+    };
+
+    let mut detail = format!("verdict {}", request.verdict.as_str());
+    if let Some(report) = &calibration {
+        detail += &format!(
+            ", match threshold {:.2} -> {:.2}",
+            report.current_threshold, report.suggested_threshold
+        );
+    }
+    audit.detail(detail);
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: "Feedback recorded".to_string(),
+        data: Some(FeedbackResponse {
+            detection_id: id,
+            verdict: request.verdict,
+            calibration,
+        }),
+    }))
+}
+
+/// Suggest a match threshold
+///
+/// Computes the threshold that keeps the false-accept rate on a tenant's detections with
+/// feedback at or below `target_far`, and reports FAR/FRR at the tenant's current and
+/// suggested thresholds.
+#[utoipa::path(
+    get,
+    path = "/calibration/threshold",
+    params(
+        ("tenant" = Option<String>, Query, description = "Tenant ID (default `default`)"),
+        ("target_far" = Option<f64>, Query, description = "Target false-accept rate (default calibration.target_far)")
+    ),
+    responses(
+        (status = 200, description = "Suggested threshold", body = ApiResponse<CalibrationReport>),
+        (status = 400, description = "Invalid tenant or target, or not enough feedback"),
+        (status = 404, description = "Tenant not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Detections"
+)]
+pub async fn suggest_threshold(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+    Extension(config): Extension<SharedConfig>,
+    Extension(registry): Extension<SharedRegistry>,
+    Query(query): Query<CalibrationQuery>,
+) -> Result<Json<ApiResponse<CalibrationReport>>, (StatusCode, String)> {
+    let tenant = query.tenant.unwrap_or_else(|| DEFAULT_TENANT.to_string());
+    let model = tenant_model(&registry, &tenant).await?;
+    let target_far = query.target_far.unwrap_or(config.calibration.target_far);
+    let report = calibrate_threshold(&model, &db, &tenant, target_far, config.calibration.min_samples, false).await?;
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Suggested threshold is {:.2}", report.suggested_threshold),
+        data: Some(report),
+    }))
+}
+
+/// Apply a calibrated match threshold
+///
+/// Same as `GET /calibration/threshold`, but the suggested threshold is put in use
+/// immediately for the tenant's model and kept across restarts.
+#[utoipa::path(
+    post,
+    path = "/calibration/threshold",
+    params(
+        ("tenant" = Option<String>, Query, description = "Tenant ID (default `default`)"),
+        ("target_far" = Option<f64>, Query, description = "Target false-accept rate (default calibration.target_far)")
+    ),
+    responses(
+        (status = 200, description = "Threshold applied", body = ApiResponse<CalibrationReport>),
+        (status = 400, description = "Invalid tenant or target, or not enough feedback"),
+        (status = 404, description = "Tenant not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Detections"
+)]
+pub async fn apply_threshold(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+    Extension(config): Extension<SharedConfig>,
+    Extension(registry): Extension<SharedRegistry>,
+    Extension(audit): Extension<AuditContext>,
+    Query(query): Query<CalibrationQuery>,
+) -> Result<Json<ApiResponse<CalibrationReport>>, (StatusCode, String)> {
+    let tenant = query.tenant.unwrap_or_else(|| DEFAULT_TENANT.to_string());
+    audit.target(format!("{}/match_threshold", tenant));
+    let model = tenant_model(&registry, &tenant).await?;
+    let target_far = query.target_far.unwrap_or(config.calibration.target_far);
+    let report = calibrate_threshold(&model, &db, &tenant, target_far, config.calibration.min_samples, true).await?;
+    audit.detail(format!(
+        "{:.2} -> {:.2} for target FAR {}",
+        report.current_threshold, report.suggested_threshold, target_far
+    ));
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!(
+            "Match threshold changed from {:.2} to {:.2}",
+            report.current_threshold, report.suggested_threshold
+        ),
+        data: Some(report),
+    }))
+}
+
+// Calibrate the tenant's model against the tenant's feedback. With `apply`, persist the result
+// and put it in use for that model.
+async fn calibrate_threshold(
+    model: &SharedModel,
+    db: &SharedDb,
+    tenant: &str,
+    target_far: f64,
+    min_samples: usize,
+    apply: bool,
+) -> Result<CalibrationReport, (StatusCode, String)> {
+    let samples = db
+        .get_feedback_samples(tenant)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let mut model_guard = model.lock().await;
+    let mut report = calibrate(&samples, model_guard.match_threshold(), target_far, min_samples)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    if apply {
+        db.set_setting(&match_threshold_setting(tenant), &report.suggested_threshold.to_string())
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+        model_guard.set_match_threshold(report.suggested_threshold);
+        report.applied = true;
+        println!("Match threshold for tenant {} set to {:.2}", tenant, report.suggested_threshold);
+    }
+
+    Ok(report)
+}
+
+// Accept RFC 3339, `YYYY-MM-DD HH:MM:SS` (UTC) or `YYYY-MM-DD` and convert to the format
+// `detected_at` is stored in, so the bounds compare as text
+pub(crate) fn parse_time(name: &str, value: &str) -> Result<String, (StatusCode, String)> {
+    let time = DateTime::parse_from_rfc3339(value)
+        .map(|time| time.naive_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(value, DETECTED_AT_FORMAT))
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|date| date.and_time(NaiveTime::MIN)))
+        .map_err(|_| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid {}: {} (use RFC 3339, YYYY-MM-DD HH:MM:SS or YYYY-MM-DD)", name, value),
+            )
+        })?;
+
+    Ok(time.format(DETECTED_AT_FORMAT).to_string())
+}
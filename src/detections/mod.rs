@@ -0,0 +1,57 @@
+pub mod calibration;
+pub mod handlers;
+
+use serde::{Deserialize, Serialize};
+
+use crate::database::Database;
+use crate::face_recognition::FaceRecognitionModel;
+use crate::tenants::DEFAULT_TENANT;
+
+// Settings key the default tenant's applied match threshold is persisted under; other
+// tenants use `match_threshold.{tenant}`
+pub const MATCH_THRESHOLD_SETTING: &str = "match_threshold";
+
+/// Settings key of a tenant's calibrated match threshold
+pub fn match_threshold_setting(tenant: &str) -> String {
+    if tenant == DEFAULT_TENANT {
+        return MATCH_THRESHOLD_SETTING.to_string();
+    }
+
+    format!("{}.{}", MATCH_THRESHOLD_SETTING, tenant)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CalibrationConfig {
+    /// False-accept rate the suggested threshold aims for when the request does not give one
+    pub target_far: f64,
+    /// Impostor samples required before a threshold is suggested
+    pub min_samples: usize,
+    /// Recalibrate and apply the new threshold after every feedback submission
+    pub auto_apply: bool,
+}
+
+impl Default for CalibrationConfig {
+    fn default() -> Self {
+        Self {
+            target_far: 0.01,
+            min_samples: 20,
+            auto_apply: false,
+        }
+    }
+}
+
+/// Use the tenant's last calibrated match threshold, if one was applied
+pub async fn apply_stored_threshold(db: &Database, tenant: &str, model: &mut FaceRecognitionModel) {
+    match db.get_setting(&match_threshold_setting(tenant)).await {
+        Ok(Some(value)) => match value.parse::<f64>() {
+            Ok(threshold) => {
+                println!("Using calibrated match threshold {:.2} for tenant {}", threshold, tenant);
+                model.set_match_threshold(threshold);
+            }
+            Err(e) => eprintln!("Ignoring invalid stored match threshold '{}': {}", value, e),
+        },
+        Ok(None) => {}
+        Err(e) => eprintln!("Failed to load match threshold: {}", e),
+    }
+}
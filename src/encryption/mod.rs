@@ -0,0 +1,432 @@
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::database::Database;
+use crate::face_recognition::model::labels_path;
+use crate::images::ImageStore;
+use crate::tenants::{TenantPaths, DEFAULT_TENANT};
+
+// Start of every encrypted blob; anything else is read as plaintext written before
+// encryption was enabled
+const MAGIC: &[u8; 8] = b"FRCRYPT1";
+const KEY_ID_LEN: usize = 8;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + KEY_ID_LEN + NONCE_LEN;
+
+/// At-rest encryption of enrollment images, saved models and unknown faces. Keys are 32
+/// random bytes, base64 encoded (`openssl rand -base64 32`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EncryptionConfig {
+    /// Key new data is encrypted with; prefer `key_file`
+    pub key: Option<String>,
+    /// File holding the key
+    pub key_file: Option<String>,
+    /// Keys that only decrypt, kept until `re-encrypt` has moved everything to `key`
+    pub previous_keys: Vec<String>,
+    pub previous_key_files: Vec<String>,
+}
+
+/// XChaCha20-Poly1305 with the configured keys. Without a current key data is written as
+/// plaintext, but anything encrypted with a previous key can still be read.
+pub struct Cipher {
+    current: Option<[u8; KEY_ID_LEN]>,
+    keys: Vec<([u8; KEY_ID_LEN], XChaCha20Poly1305)>,
+}
+
+pub type SharedCipher = Arc<Cipher>;
+
+impl Cipher {
+    pub fn open(config: &EncryptionConfig) -> Result<Self> {
+        let current = match (&config.key, &config.key_file) {
+            (Some(key), _) => Some(parse_key("encryption.key", key)?),
+            (None, Some(path)) => Some(parse_key(path, &read_key_file(path)?)?),
+            (None, None) => None,
+        };
+
+        let mut keys: Vec<[u8; 32]> = current.into_iter().collect();
+        for key in &config.previous_keys {
+            keys.push(parse_key("encryption.previous_keys", key)?);
+        }
+        for path in &config.previous_key_files {
+            keys.push(parse_key(path, &read_key_file(path)?)?);
+        }
+
+        Ok(Self {
+            current: current.map(|key| key_id(&key)),
+            keys: keys
+                .iter()
+                .map(|key| (key_id(key), XChaCha20Poly1305::new(key.into())))
+                .collect(),
+        })
+    }
+
+    /// Whether new data is encrypted
+    pub fn is_enabled(&self) -> bool {
+        self.current.is_some()
+    }
+
+    pub fn is_encrypted(data: &[u8]) -> bool {
+        data.len() >= HEADER_LEN && data.starts_with(MAGIC)
+    }
+
+    /// Encrypt `data` with the current key, or copy it when encryption is disabled
+    pub fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let Some(id) = self.current else {
+            return Ok(data.to_vec());
+        };
+
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut blob = Vec::with_capacity(HEADER_LEN + data.len() + 16);
+        blob.extend_from_slice(MAGIC);
+        blob.extend_from_slice(&id);
+        let ciphertext = self
+            .key(&id)?
+            .encrypt(&nonce, Payload { msg: data, aad: &blob })
+            .map_err(|_| anyhow::anyhow!("Encryption failed"))?;
+        blob.extend_from_slice(&nonce);
+        blob.extend_from_slice(&ciphertext);
+
+        Ok(blob)
+    }
+
+    /// Decrypt `data` with whichever configured key encrypted it; plaintext is returned as is
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if !Self::is_encrypted(data) {
+            return Ok(data.to_vec());
+        }
+
+        let (aad, rest) = data.split_at(MAGIC.len() + KEY_ID_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let id: [u8; KEY_ID_LEN] = aad[MAGIC.len()..].try_into()?;
+
+        self.key(&id)?
+            .decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+            .map_err(|_| anyhow::anyhow!("Decryption failed: wrong key or corrupted data"))
+    }
+
+    /// `data` encrypted with the current key (or decrypted when there is none), or `None` when
+    /// it already is
+    pub fn reencrypt(&self, data: &[u8]) -> Result<Option<Vec<u8>>> {
+        let up_to_date = match self.current {
+            Some(id) => Self::is_encrypted(data) && data[MAGIC.len()..MAGIC.len() + KEY_ID_LEN] == id,
+            None => !Self::is_encrypted(data),
+        };
+        if up_to_date {
+            return Ok(None);
+        }
+
+        Ok(Some(self.encrypt(&self.decrypt(data)?)?))
+    }
+
+    fn key(&self, id: &[u8; KEY_ID_LEN]) -> Result<&XChaCha20Poly1305> {
+        self.keys
+            .iter()
+            .find(|(key_id, _)| key_id == id)
+            .map(|(_, key)| key)
+            .ok_or_else(|| anyhow::anyhow!("Data is encrypted with a key that is not configured"))
+    }
+}
+
+/// What `re-encrypt` rewrote
+#[derive(Debug, Default)]
+pub struct ReencryptReport {
+    pub images: usize,
+    pub models: usize,
+    pub unknown_faces: usize,
+    /// Items already under the current key
+    pub unchanged: usize,
+}
+
+/// Rewrite every enrollment image, saved model and retained unknown face with the current
+/// key: data under a previous key is rotated, plaintext is encrypted, and without a current
+/// key everything is decrypted. `images` must be the raw store, without `EncryptedImageStore`.
+pub async fn reencrypt(
+    config: &Config,
+    db: &Database,
+    images: &dyn ImageStore,
+    cipher: &Cipher,
+) -> Result<ReencryptReport> {
+    let mut report = ReencryptReport::default();
+
+    let mut tenants = vec![DEFAULT_TENANT.to_string()];
+    for tenant in db.get_tenants().await? {
+        if tenant.tenant_id != DEFAULT_TENANT {
+            tenants.push(tenant.tenant_id);
+        }
+    }
+
+    for tenant in &tenants {
+        let paths = TenantPaths::new(config, tenant);
+
+        for key in images.list(&paths.knowledge_dir).await? {
+            match cipher.reencrypt(&images.get(&key).await?)? {
+                Some(data) => {
+                    images.put(&key, &data).await?;
+                    report.images += 1;
+                }
+                None => report.unchanged += 1,
+            }
+        }
+
+        for path in [paths.model_path.clone(), labels_path(&paths.model_path)] {
+            match reencrypt_file(cipher, &path)? {
+                Some(true) => report.models += 1,
+                Some(false) => report.unchanged += 1,
+                None => {}
+            }
+        }
+    }
+
+    for (id, image_path, features) in db.get_unknown_face_data().await? {
+        let features = cipher.reencrypt(&features)?;
+        if let Some(features) = &features {
+            db.update_unknown_face_features(id, features).await?;
+        }
+        let crop = reencrypt_file(cipher, &image_path)?;
+
+        if features.is_some() || crop == Some(true) {
+            report.unknown_faces += 1;
+        } else {
+            report.unchanged += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Read a file whose content may be encrypted
+pub fn read_file(cipher: &Cipher, path: &str) -> Result<Vec<u8>> {
+    cipher.decrypt(&fs::read(path)?)
+}
+
+/// Write a file, encrypted when encryption is enabled
+pub fn write_file(cipher: &Cipher, path: &str, data: &[u8]) -> Result<()> {
+    Ok(fs::write(path, cipher.encrypt(data)?)?)
+}
+
+// Some(rewritten) for an existing file, None for a missing one
+fn reencrypt_file(cipher: &Cipher, path: &str) -> Result<Option<bool>> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    match cipher.reencrypt(&data)? {
+        Some(data) => {
+            fs::write(path, data)?;
+            Ok(Some(true))
+        }
+        None => Ok(Some(false)),
+    }
+}
+
+fn read_key_file(path: &str) -> Result<String> {
+    if !Path::new(path).is_file() {
+        return Err(anyhow::anyhow!("Encryption key file not found: {}", path));
+    }
+
+    Ok(fs::read_to_string(path)?.trim().to_string())
+}
+
+fn parse_key(name: &str, text: &str) -> Result<[u8; 32]> {
+    STANDARD
+        .decode(text.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow::anyhow!("{} must be 32 bytes encoded as base64", name))
+}
+
+// Identifies the key in the header without revealing it
+fn key_id(key: &[u8; 32]) -> [u8; KEY_ID_LEN] {
+    let digest = Sha256::digest(key);
+    let mut id = [0; KEY_ID_LEN];
+    id.copy_from_slice(&digest[..KEY_ID_LEN]);
+
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::images::local::FsImageStore;
+    use uuid::Uuid;
+
+    const OLD: [u8; 32] = [1; 32];
+    const NEW: [u8; 32] = [2; 32];
+
+    fn cipher(current: Option<[u8; 32]>, previous: &[[u8; 32]]) -> Cipher {
+        Cipher::open(&EncryptionConfig {
+            key: current.map(|key| STANDARD.encode(key)),
+            key_file: None,
+            previous_keys: previous.iter().map(|key| STANDARD.encode(key)).collect(),
+            previous_key_files: Vec::new(),
+        })
+        .unwrap()
+    }
+
+    fn key_id_of(blob: &[u8]) -> &[u8] {
+        &blob[MAGIC.len()..MAGIC.len() + KEY_ID_LEN]
+    }
+
+    #[test]
+    fn round_trips_and_passes_plaintext_through() {
+        let cipher = cipher(Some(NEW), &[]);
+        assert!(cipher.is_enabled());
+
+        let blob = cipher.encrypt(b"face").unwrap();
+        assert!(Cipher::is_encrypted(&blob));
+        assert_eq!(key_id_of(&blob), key_id(&NEW));
+        assert_eq!(blob.len(), HEADER_LEN + 4 + 16);
+        assert_ne!(cipher.encrypt(b"face").unwrap(), blob);
+        assert_eq!(cipher.decrypt(&blob).unwrap(), b"face");
+
+        // Data written before encryption was enabled, including anything shorter than a header
+        assert_eq!(cipher.decrypt(b"plain image").unwrap(), b"plain image");
+        assert_eq!(cipher.decrypt(MAGIC).unwrap(), MAGIC);
+
+        let disabled = self::cipher(None, &[]);
+        assert!(!disabled.is_enabled());
+        assert_eq!(disabled.encrypt(b"face").unwrap(), b"face");
+        assert_eq!(disabled.decrypt(b"face").unwrap(), b"face");
+    }
+
+    #[test]
+    fn rejects_tampered_data() {
+        let cipher = cipher(Some(NEW), &[OLD]);
+        let blob = cipher.encrypt(b"face").unwrap();
+
+        for (name, index) in [
+            ("key id", MAGIC.len()),
+            ("nonce", MAGIC.len() + KEY_ID_LEN),
+            ("ciphertext", HEADER_LEN),
+            ("tag", blob.len() - 1),
+        ] {
+            let mut tampered = blob.clone();
+            tampered[index] ^= 1;
+            assert!(cipher.decrypt(&tampered).is_err(), "tampered {} was accepted", name);
+        }
+
+        // The key id is authenticated: relabelling the blob with another configured key fails
+        let mut relabelled = blob.clone();
+        relabelled[MAGIC.len()..MAGIC.len() + KEY_ID_LEN].copy_from_slice(&key_id(&OLD));
+        let error = cipher.decrypt(&relabelled).unwrap_err().to_string();
+        assert!(error.contains("wrong key or corrupted data"), "{}", error);
+
+        assert!(cipher.decrypt(&blob[..blob.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn rejects_data_under_an_unknown_key() {
+        let blob = cipher(Some(OLD), &[]).encrypt(b"face").unwrap();
+
+        let error = cipher(Some(NEW), &[]).decrypt(&blob).unwrap_err().to_string();
+        assert!(error.contains("not configured"), "{}", error);
+        assert!(cipher(None, &[NEW]).decrypt(&blob).is_err());
+    }
+
+    #[test]
+    fn reencrypts_under_the_current_key() {
+        let old = cipher(Some(OLD), &[]).encrypt(b"face").unwrap();
+        let rotated = cipher(Some(NEW), &[OLD]);
+
+        assert_eq!(rotated.decrypt(&old).unwrap(), b"face");
+        let new = rotated.reencrypt(&old).unwrap().unwrap();
+        assert_eq!(key_id_of(&new), key_id(&NEW));
+        assert_eq!(cipher(Some(NEW), &[]).decrypt(&new).unwrap(), b"face");
+        assert!(rotated.reencrypt(&new).unwrap().is_none());
+
+        let encrypted = rotated.reencrypt(b"face").unwrap().unwrap();
+        assert_eq!(key_id_of(&encrypted), key_id(&NEW));
+
+        // Without a current key everything is decrypted and plaintext is left alone
+        let disabled = cipher(None, &[OLD, NEW]);
+        assert_eq!(disabled.reencrypt(&old).unwrap().unwrap(), b"face");
+        assert_eq!(disabled.reencrypt(&new).unwrap().unwrap(), b"face");
+        assert!(disabled.reencrypt(b"face").unwrap().is_none());
+
+        // Data under a key that is no longer configured is an error, not skipped
+        assert!(cipher(Some(NEW), &[]).reencrypt(&old).is_err());
+    }
+
+    #[test]
+    fn open_rejects_bad_keys() {
+        let config = |key: &str| EncryptionConfig {
+            key: Some(key.to_string()),
+            ..Default::default()
+        };
+
+        assert!(Cipher::open(&config("not base64!")).is_err());
+        assert!(Cipher::open(&config(&STANDARD.encode([1u8; 16]))).is_err());
+        assert!(Cipher::open(&EncryptionConfig {
+            key_file: Some("/nonexistent/key".to_string()),
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn reencrypt_rewrites_images_models_and_unknown_faces() {
+        let dir = std::env::temp_dir().join(format!("encryption-{}", Uuid::new_v4()));
+        let dir = dir.to_string_lossy();
+        let mut config = Config::default();
+        config.paths.knowledge_dir = format!("{}/knowledge", dir);
+        config.paths.tenants_dir = format!("{}/tenants", dir);
+        config.paths.model_path = format!("{}/models/lbph.yml", dir);
+        config.database.url = "sqlite::memory:".to_string();
+        config.database.max_connections = 1;
+
+        let db = Database::new(&config.database).await.unwrap();
+        let images = FsImageStore;
+        let old = cipher(Some(OLD), &[]);
+        let rotated = cipher(Some(NEW), &[OLD]);
+
+        let paths = TenantPaths::new(&config, DEFAULT_TENANT);
+        images.put(&paths.image_key("alice", "plain.jpg"), b"plain").await.unwrap();
+        images.put(&paths.image_key("alice", "old.jpg"), &old.encrypt(b"old").unwrap()).await.unwrap();
+        images.put(&paths.image_key("bob", "new.jpg"), &rotated.encrypt(b"new").unwrap()).await.unwrap();
+
+        // The labels file is missing and is skipped
+        fs::create_dir_all(format!("{}/models", dir)).unwrap();
+        write_file(&old, &paths.model_path, b"model").unwrap();
+
+        let crop = format!("{}/unknown.jpg", dir);
+        write_file(&rotated, &crop, b"crop").unwrap();
+        let face = db.insert_unknown_face(&crop, &old.encrypt(b"features").unwrap(), None, None).await.unwrap();
+
+        let report = reencrypt(&config, &db, &images, &rotated).await.unwrap();
+        assert_eq!(report.images, 2);
+        assert_eq!(report.models, 1);
+        assert_eq!(report.unknown_faces, 1);
+        assert_eq!(report.unchanged, 1);
+
+        let current = cipher(Some(NEW), &[]);
+        for (key, data) in [("alice/plain.jpg", b"plain" as &[u8]), ("alice/old.jpg", b"old"), ("bob/new.jpg", b"new")] {
+            let stored = images.get(&format!("{}/{}", paths.knowledge_dir, key)).await.unwrap();
+            assert_eq!(current.decrypt(&stored).unwrap(), data);
+        }
+        assert_eq!(read_file(&current, &paths.model_path).unwrap(), b"model");
+        assert_eq!(read_file(&current, &crop).unwrap(), b"crop");
+        let (id, _, features) = db.get_unknown_face_data().await.unwrap().remove(0);
+        assert_eq!(id, face);
+        assert_eq!(current.decrypt(&features).unwrap(), b"features");
+
+        let report = reencrypt(&config, &db, &images, &rotated).await.unwrap();
+        assert_eq!((report.images, report.models, report.unknown_faces), (0, 0, 0));
+        assert_eq!(report.unchanged, 5);
+
+        fs::remove_dir_all(&*dir).unwrap();
+    }
+}
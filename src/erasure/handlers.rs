@@ -0,0 +1,153 @@
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+
+use super::{erase, DetectionErasure, ErasureRequest};
+use crate::audit::AuditContext;
+use crate::auth::Principal;
+use crate::config::SharedConfig;
+use crate::database::ErasureCertificate;
+use crate::face_recognition::handlers::{tenant_model, ApiResponse, SharedDb, SharedModel};
+use crate::images::SharedImageStore;
+use crate::tenants::{check_tenant_id, check_user_id, registry::SharedRegistry, TenantPaths, DEFAULT_TENANT};
+
+/// Query parameters for erasing a user
+#[derive(Debug, Deserialize)]
+pub struct EraseUserQuery {
+    /// Tenant the user is enrolled in (default `default`)
+    tenant: Option<String>,
+    /// What to do with the user's detections (default `pseudonymize`)
+    #[serde(default)]
+    detections: DetectionErasure,
+}
+
+/// Query parameters for listing erasure certificates
+#[derive(Debug, Deserialize)]
+pub struct ErasuresQuery {
+    tenant: Option<String>,
+}
+
+/// Erase a user's biometric data
+///
+/// Deletes the user, their enrollment images (every file in their gallery folder, listed or
+/// not) and `face_images` rows, retrains the tenant's model without them, and pseudonymizes or
+/// deletes their detections, detection feedback and video appearances. An erasure certificate
+/// recording what was removed, when and by whom is stored and returned.
+#[utoipa::path(
+    post,
+    path = "/admin/users/{user_id}/erase",
+    params(
+        ("user_id" = String, Path, description = "User ID"),
+        ("tenant" = Option<String>, Query, description = "Tenant ID (default `default`)"),
+        ("detections" = Option<DetectionErasure>, Query, description = "pseudonymize (default) or delete the user's detections")
+    ),
+    responses(
+        (status = 200, description = "User erased", body = ApiResponse<ErasureCertificate>),
+        (status = 400, description = "Invalid tenant or user ID"),
+        (status = 404, description = "Tenant or data of the user not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Admin"
+)]
+pub async fn erase_user(
+    Extension(config): Extension<SharedConfig>,
+    Extension(registry): Extension<SharedRegistry>,
+    Extension(images): Extension<SharedImageStore>,
+    Extension(principal): Extension<Principal>,
+    Extension(audit): Extension<AuditContext>,
+    Path(user_id): Path<String>,
+    Query(query): Query<EraseUserQuery>,
+) -> Result<Json<ApiResponse<ErasureCertificate>>, (StatusCode, String)> {
+    let tenant = query.tenant.unwrap_or_else(|| DEFAULT_TENANT.to_string());
+    audit.target(format!("{}/{}", tenant, user_id));
+    check_user_id(&user_id).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    let model = tenant_model(&registry, &tenant).await?;
+    let paths = TenantPaths::new(&config, &tenant);
+
+    let request = ErasureRequest {
+        tenant: &tenant,
+        user_id: &user_id,
+        detections: query.detections,
+        erased_by: &principal.name,
+    };
+    let mut model_guard = model.lock().await;
+    let certificate = erase(registry.db(), images.as_ref(), &mut model_guard, &paths, &request)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Erasure failed: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, format!("No data found for user: {}", user_id)))?;
+
+    println!(
+        "Erased user {} of tenant {} by {} (certificate {})",
+        user_id, tenant, principal.name, certificate.id
+    );
+    audit.detail(format!("erasure certificate {}", certificate.id));
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("User {} erased and model retrained", user_id),
+        data: Some(certificate),
+    }))
+}
+
+/// List erasure certificates
+#[utoipa::path(
+    get,
+    path = "/admin/erasures",
+    params(("tenant" = Option<String>, Query, description = "Tenant ID (default `default`)")),
+    responses(
+        (status = 200, description = "Erasure certificates, newest first", body = ApiResponse<Vec<ErasureCertificate>>),
+        (status = 400, description = "Invalid tenant"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Admin"
+)]
+pub async fn list_erasures(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+    Query(query): Query<ErasuresQuery>,
+) -> Result<Json<ApiResponse<Vec<ErasureCertificate>>>, (StatusCode, String)> {
+    let tenant = query.tenant.unwrap_or_else(|| DEFAULT_TENANT.to_string());
+    check_tenant_id(&tenant).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    let certificates = db
+        .get_erasure_certificates(&tenant)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Found {} erasure certificates", certificates.len()),
+        data: Some(certificates),
+    }))
+}
+
+/// Get an erasure certificate
+#[utoipa::path(
+    get,
+    path = "/admin/erasures/{id}",
+    params(("id" = i64, Path, description = "Certificate ID")),
+    responses(
+        (status = 200, description = "Erasure certificate", body = ApiResponse<ErasureCertificate>),
+        (status = 404, description = "Certificate not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Admin"
+)]
+pub async fn get_erasure(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+    Path(id): Path<i64>,
+) -> Result<Json<ApiResponse<ErasureCertificate>>, (StatusCode, String)> {
+    let certificate = db
+        .get_erasure_certificate(id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, format!("Erasure certificate not found: {}", id)))?;
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: "Erasure certificate found".to_string(),
+        data: Some(certificate),
+    }))
+}
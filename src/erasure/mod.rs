@@ -0,0 +1,271 @@
+pub mod handlers;
+
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::str::FromStr;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::database::{Database, ErasureCertificate};
+use crate::face_recognition::FaceRecognitionModel;
+use crate::images::{Gallery, ImageStore};
+use crate::tenants::{check_user_id, TenantPaths, DEFAULT_TENANT};
+
+/// What happens to the detections of an erased user
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectionErasure {
+    /// Replace the user ID with a random pseudonym, so attendance and calibration counts
+    /// survive but can no longer be tied to the person
+    #[default]
+    Pseudonymize,
+    Delete,
+}
+
+impl DetectionErasure {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pseudonymize => "pseudonymize",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+impl FromStr for DetectionErasure {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pseudonymize" => Ok(Self::Pseudonymize),
+            "delete" => Ok(Self::Delete),
+            other => Err(anyhow::anyhow!("Unknown detection erasure '{}'. Use pseudonymize or delete", other)),
+        }
+    }
+}
+
+/// Whose data to erase, and on whose behalf
+pub struct ErasureRequest<'a> {
+    pub tenant: &'a str,
+    pub user_id: &'a str,
+    pub detections: DetectionErasure,
+    /// API key name or token subject of the caller, or `cli`
+    pub erased_by: &'a str,
+}
+
+/// Erase every trace of a user's biometric data in a tenant: enrollment images in the image
+/// store (including files in the user's folder that no row lists), `face_images` and `users`
+/// rows, the user's label in the retrained model, and their ID in detections, detection
+/// feedback and video appearances. Returns the recorded certificate, or `None` when nothing
+/// of the user was found.
+pub async fn erase(
+    db: &Database,
+    images: &dyn ImageStore,
+    model: &mut FaceRecognitionModel,
+    paths: &TenantPaths,
+    request: &ErasureRequest<'_>,
+) -> Result<Option<ErasureCertificate>> {
+    let ErasureRequest {
+        tenant,
+        user_id,
+        detections,
+        erased_by,
+    } = *request;
+
+    check_user_id(user_id)?;
+
+    let rows = db.get_user_images(tenant, user_id).await?;
+    let mut image_keys: BTreeSet<String> = rows.iter().cloned().collect();
+    image_keys.extend(images.list(&format!("{}/{}", paths.knowledge_dir, user_id)).await?);
+
+    // Files go first: if deleting one fails, the rows still point at what is left
+    for key in &image_keys {
+        images.delete(key).await?;
+    }
+    let user_existed = db.delete_user(tenant, user_id).await?;
+
+    let pseudonym =
+        (detections == DetectionErasure::Pseudonymize).then(|| format!("erased-{}", Uuid::new_v4().simple()));
+    let feedback = db.erase_user_feedback(tenant, user_id, pseudonym.as_deref()).await?;
+    let detection_rows = db.erase_user_detections(tenant, user_id, pseudonym.as_deref()).await?;
+    // Videos are only analyzed against the default gallery
+    let video_appearances = if tenant == DEFAULT_TENANT {
+        db.erase_user_appearances(user_id, pseudonym.as_deref()).await?
+    } else {
+        0
+    };
+
+    if !user_existed && image_keys.is_empty() && feedback + detection_rows + video_appearances == 0 {
+        return Ok(None);
+    }
+
+    let training = model.train(&Gallery::load(db, images, tenant).await?)?;
+
+    let mut certificate = ErasureCertificate {
+        id: 0,
+        tenant_id: tenant.to_string(),
+        user_id: user_id.to_string(),
+        erased_by: erased_by.to_string(),
+        detection_action: detections.as_str().to_string(),
+        pseudonym,
+        image_keys: image_keys.into_iter().collect(),
+        face_images: rows.len() as i64,
+        detections: detection_rows as i64,
+        feedback: feedback as i64,
+        video_appearances: video_appearances as i64,
+        model_users: training.users as i64,
+        erased_at: Utc::now().to_rfc3339(),
+    };
+    certificate.id = db.insert_erasure_certificate(&certificate).await?;
+
+    Ok(Some(certificate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::database::{DetectionFilter, DetectionSort, SortOrder};
+    use crate::face_recognition::model::labels_path;
+    use crate::images::local::FsImageStore;
+    use std::collections::HashMap;
+
+    const TENANT: &str = "acme";
+
+    // Both tenants enroll the same two people from the sample gallery; training needs OpenCV
+    // and its haarcascade
+    async fn enrolled(config: &Config, db: &Database, images: &dyn ImageStore, tenant: &str) {
+        let users = [
+            ("nasri", vec!["knowledge/nasri/photo_1.jpg", "knowledge/nasri/photo_2.jpg", "knowledge/nasri/photo_3.jpg"]),
+            ("elon", vec!["knowledge/elon01/Elon Musk Colorado 2022.jpg"]),
+        ];
+        let paths = TenantPaths::new(config, tenant);
+
+        for (user_id, files) in users {
+            db.upsert_user(tenant, user_id).await.unwrap();
+            for (index, file) in files.iter().enumerate() {
+                let key = paths.image_key(user_id, &format!("{}.jpg", index));
+                let photo = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), file);
+                images.put(&key, &std::fs::read(photo).unwrap()).await.unwrap();
+                db.insert_face_image(tenant, user_id, &key).await.unwrap();
+            }
+
+            let detection = db
+                .log_detection(tenant, Some(user_id), Some(35.0), Some("temp/probe.jpg"), None)
+                .await
+                .unwrap();
+            db.log_detection(tenant, Some(user_id), Some(45.0), Some("temp/probe.jpg"), None).await.unwrap();
+            db.upsert_detection_feedback(detection, "correct", Some(user_id), None).await.unwrap();
+        }
+
+        let mut model = FaceRecognitionModel::with_model_path(config, &paths.model_path).unwrap();
+        assert_eq!(model.train(&Gallery::load(db, images, tenant).await.unwrap()).unwrap().users, 2);
+    }
+
+    fn model_labels(paths: &TenantPaths) -> Vec<String> {
+        let labels: HashMap<i32, String> =
+            serde_json::from_str(&std::fs::read_to_string(labels_path(&paths.model_path)).unwrap()).unwrap();
+        let mut users: Vec<String> = labels.into_values().collect();
+        users.sort();
+        users
+    }
+
+    async fn detections_of(db: &Database, tenant: &str, user_id: &str) -> usize {
+        let filter = DetectionFilter {
+            tenant_id: tenant.to_string(),
+            user_id: Some(user_id.to_string()),
+            matched: None,
+            min_confidence: None,
+            max_confidence: None,
+            from: None,
+            to: None,
+            source: None,
+            sort: DetectionSort::DetectedAt,
+            order: SortOrder::Desc,
+            after: None,
+            limit: 100,
+        };
+        db.get_detections(&filter).await.unwrap().len()
+    }
+
+    #[tokio::test]
+    async fn erases_one_tenants_user() {
+        let dir = std::env::temp_dir().join(format!("erasure-{}", Uuid::new_v4()));
+        let dir = dir.to_string_lossy();
+        let mut config = Config::default();
+        config.paths.knowledge_dir = format!("{}/knowledge", dir);
+        config.paths.tenants_dir = format!("{}/tenants", dir);
+        config.paths.model_path = format!("{}/models/lbph.yml", dir);
+        config.paths.temp_dir = format!("{}/temp", dir);
+        config.database.url = "sqlite::memory:".to_string();
+        config.database.max_connections = 1;
+
+        let db = Database::new(&config.database).await.unwrap();
+        let images = FsImageStore;
+        enrolled(&config, &db, &images, DEFAULT_TENANT).await;
+        enrolled(&config, &db, &images, TENANT).await;
+
+        // A file in the user's folder that no row lists is erased as well
+        let paths = TenantPaths::new(&config, DEFAULT_TENANT);
+        images.put(&paths.image_key("nasri", "stray.jpg"), b"not an image").await.unwrap();
+
+        let mut model = FaceRecognitionModel::with_model_path(&config, &paths.model_path).unwrap();
+        let request = ErasureRequest {
+            tenant: DEFAULT_TENANT,
+            user_id: "nasri",
+            detections: DetectionErasure::Pseudonymize,
+            erased_by: "test",
+        };
+        let certificate = erase(&db, &images, &mut model, &paths, &request).await.unwrap().unwrap();
+
+        assert_eq!(certificate.image_keys.len(), 4);
+        assert_eq!(certificate.face_images, 3);
+        assert_eq!(certificate.detections, 2);
+        assert_eq!(certificate.feedback, 1);
+        assert_eq!(certificate.video_appearances, 0);
+        assert_eq!(certificate.model_users, 1);
+        let pseudonym = certificate.pseudonym.clone().unwrap();
+
+        assert!(images.list(&format!("{}/nasri", paths.knowledge_dir)).await.unwrap().is_empty());
+        assert!(db.get_user_images(DEFAULT_TENANT, "nasri").await.unwrap().is_empty());
+        assert_eq!(db.get_all_users(DEFAULT_TENANT).await.unwrap(), ["elon"]);
+        assert_eq!(model_labels(&paths), ["elon"]);
+        assert_eq!(detections_of(&db, DEFAULT_TENANT, "nasri").await, 0);
+        assert_eq!(detections_of(&db, DEFAULT_TENANT, &pseudonym).await, 2);
+        assert_eq!(db.get_erasure_certificates(DEFAULT_TENANT).await.unwrap().len(), 1);
+
+        // The other tenant's user of the same name is untouched
+        let other = TenantPaths::new(&config, TENANT);
+        assert_eq!(images.list(&format!("{}/nasri", other.knowledge_dir)).await.unwrap().len(), 3);
+        assert_eq!(db.get_user_images(TENANT, "nasri").await.unwrap().len(), 3);
+        assert_eq!(model_labels(&other), ["elon", "nasri"]);
+        assert_eq!(detections_of(&db, TENANT, "nasri").await, 2);
+
+        let mut model = FaceRecognitionModel::with_model_path(&config, &other.model_path).unwrap();
+        let request = ErasureRequest {
+            tenant: TENANT,
+            user_id: "nasri",
+            detections: DetectionErasure::Delete,
+            erased_by: "test",
+        };
+        let certificate = erase(&db, &images, &mut model, &other, &request).await.unwrap().unwrap();
+
+        assert_eq!(certificate.image_keys.len(), 3);
+        assert_eq!(certificate.face_images, 3);
+        assert_eq!(certificate.detections, 2);
+        assert_eq!(certificate.feedback, 1);
+        assert_eq!(certificate.model_users, 1);
+        assert!(certificate.pseudonym.is_none());
+        assert!(images.list(&format!("{}/nasri", other.knowledge_dir)).await.unwrap().is_empty());
+        assert_eq!(db.get_all_users(TENANT).await.unwrap(), ["elon"]);
+        assert_eq!(model_labels(&other), ["elon"]);
+        assert_eq!(detections_of(&db, TENANT, "nasri").await, 0);
+        assert_eq!(detections_of(&db, TENANT, "elon").await, 2);
+
+        // Nothing is left to erase
+        assert!(erase(&db, &images, &mut model, &other, &request).await.unwrap().is_none());
+
+        std::fs::remove_dir_all(&*dir).unwrap();
+    }
+}
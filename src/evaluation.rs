@@ -0,0 +1,475 @@
+use anyhow::Result;
+use opencv::{core::Mat, prelude::*};
+use std::fs;
+
+use crate::config::Config;
+use crate::face_recognition::model::UserFaces;
+use crate::face_recognition::FaceRecognitionModel;
+
+// Number of thresholds sampled for the ROC/DET curve
+const CURVE_STEPS: usize = 200;
+
+const USAGE: &str = "Usage: evaluate <dataset_dir> [--folds K | --split RATIO] [--roc-csv PATH] [--target-far RATE]";
+
+/// How the dataset is divided into gallery and probes
+#[derive(Debug, Clone, Copy)]
+pub enum Split {
+    /// k rounds; each image and each identity is held out once
+    KFold(usize),
+    /// One round training on this fraction of every identity's images
+    TrainTest(f64),
+}
+
+#[derive(Debug, Clone)]
+pub struct EvaluationOptions {
+    pub dataset: String,
+    pub split: Split,
+    pub roc_csv: String,
+    /// Recommend the most permissive threshold whose false accept rate stays at or below this
+    pub target_far: Option<f64>,
+}
+
+impl EvaluationOptions {
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut options = Self {
+            dataset: String::new(),
+            split: Split::KFold(5),
+            roc_csv: "evaluation_roc.csv".to_string(),
+            target_far: None,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow::anyhow!("Missing value for {}\n{}", arg, USAGE))
+            };
+
+            match arg.as_str() {
+                "--folds" => options.split = Split::KFold(value()?.parse()?),
+                "--split" => options.split = Split::TrainTest(value()?.parse()?),
+                "--roc-csv" => options.roc_csv = value()?.clone(),
+                "--target-far" => options.target_far = Some(value()?.parse()?),
+                flag if flag.starts_with("--") => {
+                    return Err(anyhow::anyhow!("Unknown option {}\n{}", flag, USAGE));
+                }
+                dataset => options.dataset = dataset.to_string(),
+            }
+        }
+
+        if options.dataset.is_empty() {
+            return Err(anyhow::anyhow!("{}", USAGE));
+        }
+        match options.split {
+            Split::KFold(k) if k < 2 => return Err(anyhow::anyhow!("--folds must be at least 2")),
+            Split::TrainTest(r) if r <= 0.0 || r >= 1.0 => {
+                return Err(anyhow::anyhow!("--split must be between 0 and 1"))
+            }
+            _ => {}
+        }
+
+        Ok(options)
+    }
+}
+
+/// False accept and false reject rates when accepting matches below `threshold`
+#[derive(Debug, Clone)]
+pub struct CurvePoint {
+    pub threshold: f64,
+    pub far: f64,
+    pub frr: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct EvaluationReport {
+    pub identities: usize,
+    pub genuine_probes: usize,
+    pub impostor_probes: usize,
+    /// Fraction of genuine probes whose nearest identity is correct, ignoring thresholds
+    pub rank1_accuracy: f64,
+    pub curve: Vec<CurvePoint>,
+    /// Point where FAR and FRR are closest
+    pub eer: CurvePoint,
+    pub recommended: CurvePoint,
+    /// Rates at the threshold the server currently uses
+    pub current: CurvePoint,
+}
+
+// One gallery/probe division of the dataset
+struct Round {
+    gallery: UserFaces,
+    /// Probes of identities enrolled in the gallery, with their true user ID
+    genuine: Vec<(String, Mat)>,
+    /// Probes of identities not enrolled in this round
+    impostors: Vec<Mat>,
+}
+
+// Where a round puts one image
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Assignment {
+    Gallery,
+    /// Probe of an identity that is enrolled in the round
+    Genuine,
+    /// Probe of an identity that is not enrolled in the round
+    Impostor,
+}
+
+// Outcome of a single probe
+#[derive(Debug, Clone)]
+struct Attempt {
+    genuine: bool,
+    /// Nearest identity is the probe's own identity (always false for impostors)
+    correct: bool,
+    distance: f64,
+}
+
+/// Entry point of the `evaluate` subcommand
+pub fn run(args: &[String], config: &Config) -> Result<()> {
+    let options = EvaluationOptions::parse(args)?;
+    let mut model = FaceRecognitionModel::new(config)?;
+
+    let report = evaluate(&mut model, &options)?;
+    write_curve_csv(&report.curve, &options.roc_csv)?;
+
+    let split = match options.split {
+        Split::KFold(k) => format!("{}-fold", k),
+        Split::TrainTest(r) => format!("{:.0}/{:.0} split", r * 100.0, (1.0 - r) * 100.0),
+    };
+    println!();
+    println!("Evaluation of {} ({})", options.dataset, split);
+    println!("  Identities:            {}", report.identities);
+    println!("  Genuine probes:        {}", report.genuine_probes);
+    println!("  Impostor probes:       {}", report.impostor_probes);
+    println!("  Rank-1 accuracy:       {:.1}%", report.rank1_accuracy * 100.0);
+    println!(
+        "  EER:                   {:.1}% at threshold {:.1}",
+        (report.eer.far + report.eer.frr) / 2.0 * 100.0,
+        report.eer.threshold
+    );
+    println!(
+        "  Current threshold:     {:.1} (FAR {:.1}%, FRR {:.1}%)",
+        report.current.threshold,
+        report.current.far * 100.0,
+        report.current.frr * 100.0
+    );
+    println!(
+        "  Recommended threshold: {:.1} (FAR {:.1}%, FRR {:.1}%){}",
+        report.recommended.threshold,
+        report.recommended.far * 100.0,
+        report.recommended.frr * 100.0,
+        match options.target_far {
+            Some(target) => format!(" for target FAR {:.1}%", target * 100.0),
+            None => " at EER".to_string(),
+        }
+    );
+    println!("  ROC/DET curve written to {}", options.roc_csv);
+
+    Ok(())
+}
+
+/// Run every round of the split through the model and summarize the results
+pub fn evaluate(model: &mut FaceRecognitionModel, options: &EvaluationOptions) -> Result<EvaluationReport> {
+    let (users, failed_images) = model.load_faces(&options.dataset)?;
+    if failed_images > 0 {
+        eprintln!("Skipped {} images without a usable face", failed_images);
+    }
+    if users.len() < 2 {
+        return Err(anyhow::anyhow!(
+            "Dataset needs at least 2 identities with usable faces, found {}",
+            users.len()
+        ));
+    }
+
+    let image_counts: Vec<usize> = users.iter().map(|(_, faces)| faces.len()).collect();
+    let assignments = match options.split {
+        Split::KFold(k) => (0..k).map(|fold| kfold_assignments(&image_counts, k, fold)).collect(),
+        Split::TrainTest(ratio) => vec![train_test_assignments(&image_counts, ratio)],
+    };
+    let rounds = assignments
+        .iter()
+        .map(|assignments| build_round(&users, assignments))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut attempts = Vec::new();
+    for round in rounds {
+        if round.gallery.is_empty() {
+            continue;
+        }
+        model.train_faces(round.gallery)?;
+
+        for (user_id, face) in &round.genuine {
+            let (nearest, distance) = model.nearest(face)?;
+            attempts.push(Attempt {
+                genuine: true,
+                correct: nearest.as_deref() == Some(user_id.as_str()),
+                distance,
+            });
+        }
+        for face in &round.impostors {
+            let (_, distance) = model.nearest(face)?;
+            attempts.push(Attempt {
+                genuine: false,
+                correct: false,
+                distance,
+            });
+        }
+    }
+
+    curve_report(&attempts, users.len(), model.match_threshold(), options.target_far)
+}
+
+// Error rates over a range of thresholds, the EER and the recommended threshold for the
+// outcomes of all probes
+fn curve_report(
+    attempts: &[Attempt],
+    identities: usize,
+    current_threshold: f64,
+    target_far: Option<f64>,
+) -> Result<EvaluationReport> {
+    let genuine: Vec<&Attempt> = attempts.iter().filter(|a| a.genuine).collect();
+    let impostors: Vec<&Attempt> = attempts.iter().filter(|a| !a.genuine).collect();
+    if genuine.is_empty() || impostors.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Not enough data: {} genuine and {} impostor probes. Add more identities or images per identity",
+            genuine.len(),
+            impostors.len()
+        ));
+    }
+
+    // LBPH reports unmatched probes with an effectively infinite distance
+    let max_distance = attempts
+        .iter()
+        .map(|a| a.distance)
+        .filter(|d| d.is_finite() && *d < f64::MAX / 2.0)
+        .fold(current_threshold, f64::max);
+
+    let point = |threshold: f64| CurvePoint {
+        threshold,
+        far: impostors.iter().filter(|a| a.distance < threshold).count() as f64 / impostors.len() as f64,
+        frr: genuine
+            .iter()
+            .filter(|a| !(a.correct && a.distance < threshold))
+            .count() as f64
+            / genuine.len() as f64,
+    };
+
+    let curve: Vec<CurvePoint> = (0..=CURVE_STEPS)
+        .map(|i| point(max_distance * i as f64 / CURVE_STEPS as f64))
+        .collect();
+
+    let eer = curve
+        .iter()
+        .min_by(|a, b| (a.far - a.frr).abs().total_cmp(&(b.far - b.frr).abs()))
+        .cloned()
+        .expect("curve is never empty");
+
+    let recommended = match target_far {
+        Some(target) => curve
+            .iter()
+            .rfind(|p| p.far <= target)
+            .cloned()
+            .unwrap_or_else(|| curve[0].clone()),
+        None => eer.clone(),
+    };
+
+    Ok(EvaluationReport {
+        identities,
+        genuine_probes: genuine.len(),
+        impostor_probes: impostors.len(),
+        rank1_accuracy: genuine.iter().filter(|a| a.correct).count() as f64 / genuine.len() as f64,
+        eer,
+        recommended,
+        current: point(current_threshold),
+        curve,
+    })
+}
+
+// Fold `fold` holds out every k-th identity as impostors and every k-th image of the rest.
+// `image_counts` holds the number of images of each identity.
+fn kfold_assignments(image_counts: &[usize], k: usize, fold: usize) -> Vec<Vec<Assignment>> {
+    image_counts
+        .iter()
+        .enumerate()
+        .map(|(i, &count)| {
+            if i % k == fold {
+                return vec![Assignment::Impostor; count];
+            }
+            let assignments = (0..count)
+                .map(|j| if j % k == fold { Assignment::Genuine } else { Assignment::Gallery })
+                .collect();
+            unenrolled_as_impostors(assignments)
+        })
+        .collect()
+}
+
+// Holds out an evenly spread (1 - ratio) share of identities and of each identity's images
+fn train_test_assignments(image_counts: &[usize], ratio: f64) -> Vec<Vec<Assignment>> {
+    let holdout = 1.0 - ratio;
+
+    image_counts
+        .iter()
+        .enumerate()
+        .map(|(i, &count)| {
+            if ((i + 1) as f64 * holdout).floor() > (i as f64 * holdout).floor() {
+                return vec![Assignment::Impostor; count];
+            }
+            let train_count = ((count as f64 * ratio).ceil() as usize).max(1);
+            let assignments = (0..count)
+                .map(|j| if j < train_count { Assignment::Gallery } else { Assignment::Genuine })
+                .collect();
+            unenrolled_as_impostors(assignments)
+        })
+        .collect()
+}
+
+// An identity left without gallery images is not enrolled, so its probes become impostors
+fn unenrolled_as_impostors(assignments: Vec<Assignment>) -> Vec<Assignment> {
+    if assignments.contains(&Assignment::Gallery) {
+        return assignments;
+    }
+
+    vec![Assignment::Impostor; assignments.len()]
+}
+
+// Divide the faces as assigned
+fn build_round(users: &UserFaces, assignments: &[Vec<Assignment>]) -> Result<Round> {
+    let mut round = Round {
+        gallery: Vec::new(),
+        genuine: Vec::new(),
+        impostors: Vec::new(),
+    };
+
+    for ((user_id, faces), assignments) in users.iter().zip(assignments) {
+        let mut train = Vec::new();
+        for (face, assignment) in faces.iter().zip(assignments) {
+            match assignment {
+                Assignment::Gallery => train.push(face.try_clone()?),
+                Assignment::Genuine => round.genuine.push((user_id.clone(), face.try_clone()?)),
+                Assignment::Impostor => round.impostors.push(face.try_clone()?),
+            }
+        }
+        if !train.is_empty() {
+            round.gallery.push((user_id.clone(), train));
+        }
+    }
+
+    Ok(round)
+}
+
+/// Write the curve as CSV; plot `far` against `tar` for ROC or against `frr` for DET
+pub fn write_curve_csv(curve: &[CurvePoint], path: &str) -> Result<()> {
+    let mut csv = String::from("threshold,far,frr,tar\n");
+    for p in curve {
+        csv.push_str(&format!("{:.3},{:.6},{:.6},{:.6}\n", p.threshold, p.far, p.frr, 1.0 - p.frr));
+    }
+    fs::write(path, csv)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Assignment::{Gallery, Genuine, Impostor};
+
+    fn attempts(genuine: &[(bool, f64)], impostors: &[f64]) -> Vec<Attempt> {
+        let genuine = genuine.iter().map(|&(correct, distance)| Attempt {
+            genuine: true,
+            correct,
+            distance,
+        });
+        let impostors = impostors.iter().map(|&distance| Attempt {
+            genuine: false,
+            correct: false,
+            distance,
+        });
+        genuine.chain(impostors).collect()
+    }
+
+    #[test]
+    fn finds_the_equal_error_rate() {
+        // Between 35 and 40 one of four impostors is accepted and one of four genuine probes
+        // rejected; everywhere else the rates differ
+        let attempts = attempts(&[(true, 10.0), (true, 20.0), (true, 30.0), (true, 40.0)], &[35.0, 45.0, 55.0, 65.0]);
+        let report = curve_report(&attempts, 3, 50.0, None).unwrap();
+
+        assert_eq!((report.genuine_probes, report.impostor_probes, report.identities), (4, 4, 3));
+        assert_eq!(report.rank1_accuracy, 1.0);
+        assert_eq!(report.curve.len(), CURVE_STEPS + 1);
+        assert_eq!(report.curve.last().unwrap().threshold, 65.0);
+        assert_eq!((report.eer.far, report.eer.frr), (0.25, 0.25));
+        assert!(report.eer.threshold > 35.0 && report.eer.threshold <= 40.0);
+        assert_eq!(report.recommended.threshold, report.eer.threshold);
+        assert_eq!((report.current.threshold, report.current.far, report.current.frr), (50.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn recommends_the_most_permissive_threshold_within_the_target() {
+        let attempts = attempts(&[(true, 10.0), (true, 20.0), (true, 30.0), (true, 40.0)], &[35.0, 45.0, 55.0, 65.0]);
+
+        let report = curve_report(&attempts, 3, 50.0, Some(0.0)).unwrap();
+        assert_eq!((report.recommended.far, report.recommended.frr), (0.0, 0.25));
+        assert!(report.recommended.threshold > 34.0 && report.recommended.threshold <= 35.0);
+
+        let report = curve_report(&attempts, 3, 50.0, Some(0.5)).unwrap();
+        assert_eq!((report.recommended.far, report.recommended.frr), (0.5, 0.0));
+        assert!(report.recommended.threshold > 54.0 && report.recommended.threshold <= 55.0);
+    }
+
+    #[test]
+    fn misidentified_probes_are_always_rejected() {
+        // The nearest identity of one genuine probe is someone else's, and one impostor is
+        // unmatched; its infinite distance does not stretch the curve
+        let attempts = attempts(&[(true, 10.0), (false, 5.0)], &[30.0, f64::MAX]);
+        let report = curve_report(&attempts, 2, 20.0, None).unwrap();
+
+        assert_eq!(report.rank1_accuracy, 0.5);
+        assert_eq!(report.curve.last().unwrap().threshold, 30.0);
+        assert!(report.curve.iter().all(|p| p.frr >= 0.5));
+        assert_eq!((report.current.far, report.current.frr), (0.0, 0.5));
+
+        assert!(curve_report(&attempts[..2], 2, 20.0, None).is_err());
+        assert!(curve_report(&attempts[2..], 2, 20.0, None).is_err());
+    }
+
+    #[test]
+    fn each_fold_holds_out_identities_and_images() {
+        let counts = [4, 1, 2, 3];
+
+        // Identity 1 has a single image, which is a probe in fold 0, so it is not enrolled
+        assert_eq!(
+            kfold_assignments(&counts, 2, 0),
+            [
+                vec![Impostor; 4],
+                vec![Impostor; 1],
+                vec![Impostor; 2],
+                vec![Genuine, Gallery, Genuine],
+            ]
+        );
+        assert_eq!(
+            kfold_assignments(&counts, 2, 1),
+            [
+                vec![Gallery, Genuine, Gallery, Genuine],
+                vec![Impostor; 1],
+                vec![Gallery, Genuine],
+                vec![Impostor; 3],
+            ]
+        );
+    }
+
+    #[test]
+    fn train_test_split_holds_out_a_share() {
+        let assignments = train_test_assignments(&[4, 4, 4, 4, 1], 0.75);
+
+        assert_eq!(
+            assignments,
+            [
+                vec![Gallery, Gallery, Gallery, Genuine],
+                vec![Gallery, Gallery, Gallery, Genuine],
+                vec![Gallery, Gallery, Gallery, Genuine],
+                vec![Impostor; 4],
+                vec![Gallery],
+            ]
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use anyhow::Result;
+use opencv::{
+    core::{Mat, Point, Scalar, Vector},
+    imgcodecs::{imencode, imread, IMREAD_COLOR, IMWRITE_JPEG_QUALITY},
+    imgproc::{put_text, rectangle, FONT_HERSHEY_SIMPLEX, LINE_8},
+    prelude::*,
+};
+
+use super::model::{FaceMatch, FaceRecognitionModel};
+
+// Colors are BGR
+const MATCHED_COLOR: (f64, f64, f64) = (0.0, 200.0, 0.0);
+const UNKNOWN_COLOR: (f64, f64, f64) = (0.0, 0.0, 220.0);
+
+const JPEG_QUALITY: i32 = 90;
+
+/// Draw a rectangle and a `user_id (confidence)` label for every face onto a BGR image
+pub fn draw_matches(img: &mut Mat, matches: &[FaceMatch]) -> Result<()> {
+    for face in matches {
+        let (b, g, r) = if face.user_id.is_some() {
+            MATCHED_COLOR
+        } else {
+            UNKNOWN_COLOR
+        };
+        let color = Scalar::new(b, g, r, 0.0);
+
+        rectangle(img, face.rect, color, 2, LINE_8, 0)?;
+
+        let label = format!(
+            "{} ({:.1})",
+            face.user_id.as_deref().unwrap_or("unknown"),
+            face.confidence
+        );
+        // Keep the label inside the image when the face touches the top edge
+        let text_y = if face.rect.y > 20 { face.rect.y - 8 } else { face.rect.y + 20 };
+        put_text(
+            img,
+            &label,
+            Point::new(face.rect.x, text_y),
+            FONT_HERSHEY_SIMPLEX,
+            0.6,
+            color,
+            2,
+            LINE_8,
+            false,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Identify every face in an image file and return it JPEG-encoded with the results drawn on.
+/// Faces refused by `quality.reject_probes` are left unidentified.
+pub fn annotate_image(model: &mut FaceRecognitionModel, image_path: &str) -> Result<(Vec<u8>, Vec<FaceMatch>)> {
+    let mut img = imread(image_path, IMREAD_COLOR)?;
+
+    if img.empty() {
+        return Err(anyhow::anyhow!("Failed to load image"));
+    }
+
+    let mut matches = model.predict_faces(&img)?;
+    for face in &mut matches {
+        if !model.quality_thresholds().probe_failures(&face.quality).is_empty() {
+            face.user_id = None;
+        }
+    }
+    draw_matches(&mut img, &matches)?;
+
+    Ok((encode_jpeg(&img)?, matches))
+}
+
+/// Encode a BGR image as JPEG
+pub fn encode_jpeg(img: &Mat) -> Result<Vec<u8>> {
+    let mut buf = Vector::<u8>::new();
+    let params = Vector::<i32>::from(vec![IMWRITE_JPEG_QUALITY, JPEG_QUALITY]);
+    imencode(".jpg", img, &mut buf, &params)?;
+
+    Ok(buf.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::face_recognition::quality::FaceQuality;
+    use opencv::core::{Rect, CV_8UC3};
+
+    const SIZE: i32 = 200;
+    const GRAY: u8 = 128;
+
+    fn face(rect: Rect, user_id: Option<&str>) -> FaceMatch {
+        FaceMatch {
+            rect,
+            user_id: user_id.map(str::to_string),
+            confidence: 42.0,
+            quality: FaceQuality {
+                sharpness: 100.0,
+                brightness: 120.0,
+                contrast: 40.0,
+                face_width: rect.width,
+                face_ratio: 0.1,
+                frontalness: 0.9,
+            },
+        }
+    }
+
+    fn pixel(img: &Mat, x: i32, y: i32) -> [u8; 3] {
+        let i = ((y * SIZE + x) * 3) as usize;
+        img.data_bytes().unwrap()[i..i + 3].try_into().unwrap()
+    }
+
+    fn color((b, g, r): (f64, f64, f64)) -> [u8; 3] {
+        [b as u8, g as u8, r as u8]
+    }
+
+    #[test]
+    fn draws_boxes_without_touching_the_faces() {
+        let matched = Rect::new(40, 60, 50, 50);
+        let unknown = Rect::new(120, 120, 50, 50);
+        let mut img = Mat::new_rows_cols_with_default(SIZE, SIZE, CV_8UC3, Scalar::all(GRAY as f64)).unwrap();
+
+        draw_matches(&mut img, &[face(matched, Some("alice")), face(unknown, None)]).unwrap();
+
+        assert_eq!(pixel(&img, matched.x, matched.y + 25), color(MATCHED_COLOR));
+        assert_eq!(pixel(&img, matched.x + 25, matched.y + matched.height - 1), color(MATCHED_COLOR));
+        assert_eq!(pixel(&img, unknown.x, unknown.y + 25), color(UNKNOWN_COLOR));
+
+        // The faces inside the boxes, and the image away from boxes and labels, are unchanged
+        for rect in [matched, unknown] {
+            for y in rect.y + 3..rect.y + rect.height - 3 {
+                for x in rect.x + 3..rect.x + rect.width - 3 {
+                    assert_eq!(pixel(&img, x, y), [GRAY; 3], "({}, {}) was drawn over", x, y);
+                }
+            }
+        }
+        for y in 0..SIZE {
+            for x in 0..matched.x - 2 {
+                assert_eq!(pixel(&img, x, y), [GRAY; 3], "({}, {}) was drawn over", x, y);
+            }
+        }
+        assert_eq!(pixel(&img, SIZE - 1, SIZE - 1), [GRAY; 3]);
+        assert_eq!(pixel(&img, SIZE - 1, 0), [GRAY; 3]);
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::Result;
+use opencv::{
+    core::{Mat, Rect, Scalar, Size, BORDER_DEFAULT},
+    imgproc::{gaussian_blur, rectangle, resize, FILLED, INTER_LINEAR, INTER_NEAREST, LINE_8},
+    prelude::*,
+};
+use std::str::FromStr;
+
+// Pixelated faces are reduced to this many blocks across
+const PIXEL_BLOCKS: i32 = 12;
+
+/// How a face region is redacted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RedactMethod {
+    Blur,
+    Pixelate,
+    Box,
+}
+
+impl FromStr for RedactMethod {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "blur" => Ok(Self::Blur),
+            "pixelate" => Ok(Self::Pixelate),
+            "box" => Ok(Self::Box),
+            other => Err(anyhow::anyhow!(
+                "Unknown method '{}', expected blur, pixelate or box",
+                other
+            )),
+        }
+    }
+}
+
+/// Redact every given region of a BGR image in place
+pub fn redact_faces(img: &mut Mat, rects: &[Rect], method: RedactMethod) -> Result<()> {
+    for &rect in rects {
+        match method {
+            RedactMethod::Box => {
+                rectangle(img, rect, Scalar::new(0.0, 0.0, 0.0, 0.0), FILLED, LINE_8, 0)?;
+            }
+            RedactMethod::Blur => {
+                // Kernel scales with the face so large faces are not just softened
+                let k = (rect.width.min(rect.height) / 2) | 1;
+                let mut redacted = Mat::default();
+                gaussian_blur(
+                    &Mat::roi(img, rect)?,
+                    &mut redacted,
+                    Size::new(k, k),
+                    0.0,
+                    0.0,
+                    BORDER_DEFAULT,
+                )?;
+                redacted.copy_to(&mut Mat::roi_mut(img, rect)?)?;
+            }
+            RedactMethod::Pixelate => {
+                let mut small = Mat::default();
+                resize(
+                    &Mat::roi(img, rect)?,
+                    &mut small,
+                    Size::new(PIXEL_BLOCKS, PIXEL_BLOCKS),
+                    0.0,
+                    0.0,
+                    INTER_LINEAR,
+                )?;
+                let mut redacted = Mat::default();
+                resize(&small, &mut redacted, rect.size(), 0.0, 0.0, INTER_NEAREST)?;
+                redacted.copy_to(&mut Mat::roi_mut(img, rect)?)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opencv::core::CV_8UC3;
+
+    const SIZE: i32 = 160;
+
+    // A 4px BGR checkerboard, which every method visibly changes
+    fn checkerboard() -> Mat {
+        let mut img = Mat::new_rows_cols_with_default(SIZE, SIZE, CV_8UC3, Scalar::all(0.0)).unwrap();
+        for (i, value) in img.data_typed_mut::<u8>().unwrap().iter_mut().enumerate() {
+            let (x, y) = ((i / 3) as i32 % SIZE, (i / 3) as i32 / SIZE);
+            *value = if (x / 4 + y / 4) % 2 == 0 { 255 } else { 0 };
+        }
+        img
+    }
+
+    // Pixels that differ between two images, inside and outside `rect`
+    fn changed(before: &Mat, after: &Mat, rect: Rect) -> (usize, usize) {
+        let (before, after) = (before.data_bytes().unwrap(), after.data_bytes().unwrap());
+        let (mut inside, mut outside) = (0, 0);
+        for (i, (a, b)) in before.chunks(3).zip(after.chunks(3)).enumerate() {
+            if a == b {
+                continue;
+            }
+            let (x, y) = (i as i32 % SIZE, i as i32 / SIZE);
+            if x >= rect.x && x < rect.x + rect.width && y >= rect.y && y < rect.y + rect.height {
+                inside += 1;
+            } else {
+                outside += 1;
+            }
+        }
+        (inside, outside)
+    }
+
+    #[test]
+    fn redacts_only_the_given_regions() {
+        let face = Rect::new(20, 30, 60, 60);
+        // Stays untouched, as an allowed face would
+        let allowed = Rect::new(100, 90, 40, 40);
+
+        for method in [RedactMethod::Blur, RedactMethod::Pixelate, RedactMethod::Box] {
+            let original = checkerboard();
+            let mut img = original.clone();
+            redact_faces(&mut img, &[face], method).unwrap();
+
+            let (inside, outside) = changed(&original, &img, face);
+            assert!(inside as i32 > face.area() / 2, "{:?} changed {} pixels", method, inside);
+            assert_eq!(outside, 0, "{:?} changed pixels outside the face", method);
+            assert_eq!(changed(&original, &img, allowed), (0, 0));
+
+            if method == RedactMethod::Box {
+                assert!(Mat::roi(&img, face).unwrap().try_clone().unwrap().data_bytes().unwrap().iter().all(|&v| v == 0));
+            }
+        }
+    }
+
+    #[test]
+    fn redact_method_parses() {
+        assert_eq!("blur".parse::<RedactMethod>().unwrap(), RedactMethod::Blur);
+        assert_eq!("pixelate".parse::<RedactMethod>().unwrap(), RedactMethod::Pixelate);
+        assert_eq!("box".parse::<RedactMethod>().unwrap(), RedactMethod::Box);
+        assert!("smudge".parse::<RedactMethod>().is_err());
+    }
+}
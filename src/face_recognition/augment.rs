@@ -0,0 +1,299 @@
+use anyhow::Result;
+use opencv::{
+    core::{add, flip, no_array, randn, Mat, Point2f, Scalar, BORDER_REPLICATE, CV_32F, CV_8U},
+    imgproc::{get_rotation_matrix_2d, warp_affine, INTER_LINEAR},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+/// A single way of deriving a training variant from a face crop.
+/// The value is the maximum strength; variants spread between it and its negative.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Augmentation {
+    /// Mirror horizontally
+    Flip,
+    /// Rotate by up to this many degrees
+    Rotate(f64),
+    /// Zoom in or out by up to this fraction
+    Scale(f64),
+    /// Shift gray levels by up to this amount
+    Brightness(f64),
+    /// Stretch gray levels around mid-gray by up to this fraction
+    Contrast(f64),
+    /// Add gaussian noise with this standard deviation
+    Noise(f64),
+}
+
+impl Augmentation {
+    /// Whether the maximum strength keeps every variant a valid image: scale and contrast
+    /// below 1 so neither factor reaches zero, rotation up to 90 degrees, brightness and
+    /// noise up to the full gray range
+    pub fn in_range(&self) -> bool {
+        match *self {
+            Self::Flip => true,
+            Self::Rotate(degrees) => degrees > 0.0 && degrees <= 90.0,
+            Self::Scale(fraction) | Self::Contrast(fraction) => fraction > 0.0 && fraction < 1.0,
+            Self::Brightness(value) | Self::Noise(value) => value > 0.0 && value <= 255.0,
+        }
+    }
+}
+
+impl FromStr for Augmentation {
+    type Err = anyhow::Error;
+
+    /// Parse `name` or `name:strength`, e.g. `flip`, `rotate:10`, `noise:8`
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, value) = match s.split_once(':') {
+            Some((name, value)) => (name, Some(value.parse::<f64>()?)),
+            None => (s, None),
+        };
+
+        match name {
+            "flip" => Ok(Self::Flip),
+            "rotate" => Ok(Self::Rotate(value.unwrap_or(10.0))),
+            "scale" => Ok(Self::Scale(value.unwrap_or(0.1))),
+            "brightness" => Ok(Self::Brightness(value.unwrap_or(30.0))),
+            "contrast" => Ok(Self::Contrast(value.unwrap_or(0.2))),
+            "noise" => Ok(Self::Noise(value.unwrap_or(8.0))),
+            other => Err(anyhow::anyhow!("Unknown augmentation '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for Augmentation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Flip => write!(f, "flip"),
+            Self::Rotate(v) => write!(f, "rotate:{}", v),
+            Self::Scale(v) => write!(f, "scale:{}", v),
+            Self::Brightness(v) => write!(f, "brightness:{}", v),
+            Self::Contrast(v) => write!(f, "contrast:{}", v),
+            Self::Noise(v) => write!(f, "noise:{}", v),
+        }
+    }
+}
+
+impl TryFrom<String> for Augmentation {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<Augmentation> for String {
+    fn from(recipe: Augmentation) -> Self {
+        recipe.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AugmentationConfig {
+    pub enabled: bool,
+    pub recipes: Vec<Augmentation>,
+    /// Extra variants generated per original face
+    pub variants_per_image: usize,
+    /// Only users with at most this many usable photos are augmented
+    pub max_user_images: usize,
+}
+
+impl Default for AugmentationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            recipes: vec![
+                Augmentation::Flip,
+                Augmentation::Rotate(10.0),
+                Augmentation::Scale(0.1),
+                Augmentation::Brightness(30.0),
+                Augmentation::Contrast(0.2),
+                Augmentation::Noise(8.0),
+            ],
+            variants_per_image: 4,
+            max_user_images: 5,
+        }
+    }
+}
+
+impl AugmentationConfig {
+    /// Whether a user with this many usable photos should get augmented variants
+    pub fn applies_to(&self, user_images: usize) -> bool {
+        self.enabled && !self.recipes.is_empty() && user_images <= self.max_user_images
+    }
+}
+
+/// Training summary returned after every (re)training
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct TrainingStats {
+    /// Enrolled users in the model
+    pub users: usize,
+    /// Original photos a face was found in
+    pub original_images: usize,
+    /// Synthetic variants added by augmentation
+    pub augmented_images: usize,
+    /// Photos skipped because they could not be read or had no face
+    pub failed_images: usize,
+}
+
+/// Generate up to `config.variants_per_image` variants of a face crop, as laid out by
+/// [`schedule`].
+pub fn augment(face: &Mat, config: &AugmentationConfig) -> Result<Vec<Mat>> {
+    schedule(config)
+        .into_iter()
+        .map(|(recipe, strength)| apply(face, recipe, strength))
+        .collect()
+}
+
+/// The recipe and strength (-1..=1) of each variant. Variants cycle through the recipes,
+/// alternating direction and growing towards full strength, so training on the same photos is
+/// reproducible (apart from the noise itself). Flip has no strength and is applied once; with
+/// only flip there is a single variant.
+pub fn schedule(config: &AugmentationConfig) -> Vec<(Augmentation, f64)> {
+    let recipes = &config.recipes;
+    let graded = recipes.iter().filter(|recipe| **recipe != Augmentation::Flip).count();
+    let count = if graded == 0 {
+        config.variants_per_image.min(recipes.len().min(1))
+    } else {
+        config.variants_per_image
+    };
+    // The first round applies every recipe, later ones only the graded recipes
+    let rounds = 1 + count.saturating_sub(recipes.len()).div_ceil(graded.max(1));
+
+    let mut variants = Vec::with_capacity(count);
+    for round in 0..rounds {
+        let sign = if round.is_multiple_of(2) { 1.0 } else { -1.0 };
+        let strength = sign * (round + 1) as f64 / rounds as f64;
+        for &recipe in recipes {
+            if variants.len() == count {
+                break;
+            }
+            if round == 0 || recipe != Augmentation::Flip {
+                variants.push((recipe, strength));
+            }
+        }
+    }
+
+    variants
+}
+
+// `strength` is in -1..=1 and scales the recipe's maximum
+fn apply(face: &Mat, recipe: Augmentation, strength: f64) -> Result<Mat> {
+    let mut out = Mat::default();
+
+    match recipe {
+        Augmentation::Flip => flip(face, &mut out, 1)?,
+        Augmentation::Rotate(degrees) => warp(face, &mut out, degrees * strength, 1.0)?,
+        Augmentation::Scale(fraction) => warp(face, &mut out, 0.0, 1.0 + fraction * strength)?,
+        Augmentation::Brightness(shift) => face.convert_to(&mut out, -1, 1.0, shift * strength)?,
+        Augmentation::Contrast(fraction) => {
+            let alpha = 1.0 + fraction * strength;
+            face.convert_to(&mut out, -1, alpha, 128.0 * (1.0 - alpha))?;
+        }
+        Augmentation::Noise(stddev) => {
+            let mut float = Mat::default();
+            face.convert_to(&mut float, CV_32F, 1.0, 0.0)?;
+            let mut noise = Mat::new_rows_cols_with_default(face.rows(), face.cols(), CV_32F, Scalar::all(0.0))?;
+            randn(&mut noise, &Scalar::all(0.0), &Scalar::all(stddev * strength.abs()))?;
+            let mut noisy = Mat::default();
+            add(&float, &noise, &mut noisy, &no_array(), -1)?;
+            noisy.convert_to(&mut out, CV_8U, 1.0, 0.0)?;
+        }
+    }
+
+    Ok(out)
+}
+
+// Rotate and scale around the center, filling the borders with edge pixels
+fn warp(face: &Mat, out: &mut Mat, angle: f64, scale: f64) -> Result<()> {
+    let center = Point2f::new(face.cols() as f32 / 2.0, face.rows() as f32 / 2.0);
+    let matrix = get_rotation_matrix_2d(center, angle, scale)?;
+    warp_affine(
+        face,
+        out,
+        &matrix,
+        face.size()?,
+        INTER_LINEAR,
+        BORDER_REPLICATE,
+        Scalar::default(),
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(recipes: &[&str], variants_per_image: usize) -> AugmentationConfig {
+        AugmentationConfig {
+            enabled: true,
+            recipes: recipes.iter().map(|recipe| recipe.parse().unwrap()).collect(),
+            variants_per_image,
+            ..AugmentationConfig::default()
+        }
+    }
+
+    fn names(schedule: &[(Augmentation, f64)]) -> Vec<String> {
+        schedule.iter().map(|(recipe, strength)| format!("{} x{}", recipe, strength)).collect()
+    }
+
+    #[test]
+    fn recipes_round_trip_through_text() {
+        for recipe in AugmentationConfig::default().recipes.into_iter().chain([Augmentation::Rotate(2.5)]) {
+            assert_eq!(recipe.to_string().parse::<Augmentation>().unwrap(), recipe);
+        }
+
+        assert_eq!("rotate".parse::<Augmentation>().unwrap(), Augmentation::Rotate(10.0));
+        assert_eq!("noise:3".parse::<Augmentation>().unwrap(), Augmentation::Noise(3.0));
+        assert_eq!(Augmentation::Scale(0.15).to_string(), "scale:0.15");
+        assert!("blur".parse::<Augmentation>().is_err());
+        assert!("rotate:ten".parse::<Augmentation>().is_err());
+    }
+
+    #[test]
+    fn strengths_must_keep_images_valid() {
+        for recipe in ["flip", "rotate:90", "scale:0.5", "contrast:0.99", "brightness:255", "noise:8"] {
+            assert!(recipe.parse::<Augmentation>().unwrap().in_range(), "{}", recipe);
+        }
+        for recipe in ["rotate:0", "rotate:-10", "rotate:120", "scale:1", "scale:1.5", "contrast:2", "brightness:300", "noise:-1", "noise:NaN"] {
+            assert!(!recipe.parse::<Augmentation>().unwrap().in_range(), "{}", recipe);
+        }
+    }
+
+    #[test]
+    fn variants_cycle_through_recipes_at_growing_strength() {
+        let recipes = ["flip", "rotate:10", "scale:0.1"];
+
+        assert_eq!(names(&schedule(&config(&recipes, 2))), ["flip x1", "rotate:10 x1"]);
+        assert_eq!(
+            names(&schedule(&config(&recipes, 7))),
+            [
+                "flip x0.3333333333333333",
+                "rotate:10 x0.3333333333333333",
+                "scale:0.1 x0.3333333333333333",
+                "rotate:10 x-0.6666666666666666",
+                "scale:0.1 x-0.6666666666666666",
+                "rotate:10 x1",
+                "scale:0.1 x1",
+            ]
+        );
+        assert_eq!(schedule(&config(&["brightness:30"], 4)).len(), 4);
+        assert_eq!(schedule(&AugmentationConfig::default()).len(), 4);
+    }
+
+    #[test]
+    fn flip_is_applied_once() {
+        assert_eq!(names(&schedule(&config(&["flip"], 4))), ["flip x1"]);
+        assert_eq!(
+            schedule(&config(&["flip", "noise:8"], 5)).iter().filter(|(recipe, _)| *recipe == Augmentation::Flip).count(),
+            1
+        );
+        assert_eq!(schedule(&config(&["flip", "noise:8"], 5)).len(), 5);
+        assert!(schedule(&config(&[], 4)).is_empty());
+    }
+}
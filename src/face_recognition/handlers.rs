@@ -1,18 +1,35 @@
 use axum::{
-    extract::{Multipart, State},
-    http::StatusCode,
+    body::Bytes,
+    extract::{Extension, Multipart, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
-use serde::Serialize;
+use opencv::{
+    core::{Mat, Vector},
+    imgcodecs::{imdecode, IMREAD_COLOR},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
-use super::model::FaceRecognitionModel;
+use super::annotate::{annotate_image, encode_jpeg};
+use super::anonymize::{redact_faces, RedactMethod};
+use super::augment::TrainingStats;
+use super::model::{is_image_file, FaceMatch, FaceRecognitionModel};
+use super::quality::FaceQuality;
+use crate::audit::AuditContext;
+use crate::config::{Config, SharedConfig};
 use crate::database::Database;
+use crate::encryption::Cipher;
+use crate::images::{Gallery, SharedImageStore};
+use crate::liveness::{active::Challenge, LivenessReport, SharedLiveness, MAX_CHALLENGE_FRAMES, MIN_CHALLENGE_FRAMES};
+use crate::tenants::{check_tenant_id, check_user_id, registry::SharedRegistry, TenantPaths, DEFAULT_TENANT};
+use crate::unknowns::{capture, UnknownsConfig};
 
 pub type SharedModel = Arc<Mutex<FaceRecognitionModel>>;
 pub type SharedDb = Arc<Database>;
@@ -21,12 +38,12 @@ pub type SharedDb = Arc<Database>;
 #[derive(Debug, Serialize, ToSchema)]
 pub struct ApiResponse<T> {
     /// Indicates if the request was successful
-    success: bool,
+    pub(crate) success: bool,
     /// Human-readable message describing the result
-    message: String,
+    pub(crate) message: String,
     /// Optional response data
     #[serde(skip_serializing_if = "Option::is_none")]
-    data: Option<T>,
+    pub(crate) data: Option<T>,
 }
 
 /// Response for adding face data
@@ -36,6 +53,18 @@ pub struct AddFaceResponse {
     user_id: String,
     /// Number of images successfully saved
     images_saved: usize,
+    /// Photos that were not stored because they failed the quality thresholds
+    rejected: Vec<RejectedPhoto>,
+    /// Summary of the retraining triggered by this upload
+    training: TrainingStats,
+}
+
+/// An enrollment photo refused by the quality check
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RejectedPhoto {
+    filename: String,
+    /// Why the photo was rejected
+    reasons: Vec<String>,
 }
 
 /// Response for face detection
@@ -45,6 +74,63 @@ pub struct DetectFaceResponse {
     user_id: Option<String>,
     /// Whether a face was detected
     detected: bool,
+    /// ID of the logged detection, used for `POST /detections/{id}/feedback`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detection_id: Option<i64>,
+    /// Liveness verdict, present when a liveness check was run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    liveness: Option<LivenessReport>,
+    /// Quality of the identified face
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quality: Option<FaceQuality>,
+}
+
+/// A single face found in an annotated image
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DetectedFace {
+    /// ID of the logged detection, used for `POST /detections/{id}/feedback`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detection_id: Option<i64>,
+    /// Matched user ID (null if the face is unknown)
+    user_id: Option<String>,
+    /// LBPH distance, lower is a better match
+    confidence: f64,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    quality: FaceQuality,
+    /// Why the face was not identified, when `quality.reject_probes` refused it
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    quality_failures: Vec<String>,
+}
+
+impl From<&FaceMatch> for DetectedFace {
+    fn from(face: &FaceMatch) -> Self {
+        Self {
+            detection_id: None,
+            user_id: face.user_id.clone(),
+            confidence: face.confidence,
+            x: face.rect.x,
+            y: face.rect.y,
+            width: face.rect.width,
+            height: face.rect.height,
+            quality: face.quality.clone(),
+            quality_failures: Vec::new(),
+        }
+    }
+}
+
+/// Query parameters for face detection
+#[derive(Debug, Deserialize)]
+pub struct DetectFaceQuery {
+    /// Return the uploaded image with boxes and names drawn on it instead of JSON
+    #[serde(default)]
+    annotate: bool,
+    /// Run a liveness check first: `passive`, `blink` or `head_turn`
+    liveness: Option<String>,
+    /// Camera or other client the probe came from, kept in the detection log
+    source: Option<String>,
 }
 
 impl<T: Serialize> IntoResponse for ApiResponse<T> {
@@ -62,30 +148,75 @@ impl<T: Serialize> IntoResponse for ApiResponse<T> {
 /// Add face data for training
 ///
 /// Upload multiple photos for a user and automatically train the face recognition model.
-/// Images are stored in `knowledge/{user_id}/` directory.
+/// Images are stored in the image store under `{knowledge_dir}/{user_id}/`. When `quality.reject_enrollment`
+/// is enabled, photos without a usable face are skipped and listed in `rejected`.
 #[utoipa::path(
     post,
     path = "/add-face",
     request_body(content_type = "multipart/form-data", description = "Upload face photos with user ID"),
     responses(
         (status = 200, description = "Face data added successfully", body = ApiResponse<AddFaceResponse>),
-        (status = 400, description = "Bad request - missing required fields"),
+        (status = 400, description = "Bad request - missing required fields or invalid user ID"),
         (status = 500, description = "Internal server error")
     ),
     tag = "Face Recognition"
 )]
 pub async fn add_face(
     State((model, db)): State<(SharedModel, SharedDb)>,
+    Extension(config): Extension<SharedConfig>,
+    Extension(images): Extension<SharedImageStore>,
+    Extension(audit): Extension<AuditContext>,
+    multipart: Multipart,
+) -> Result<Json<ApiResponse<AddFaceResponse>>, (StatusCode, String)> {
+    enroll(DEFAULT_TENANT, &model, &db, &images, &config, &audit, multipart).await
+}
+
+/// Add face data to a tenant's gallery
+///
+/// Same as `/add-face`, but the photos are stored in the tenant's own gallery and only the
+/// tenant's model is retrained.
+#[utoipa::path(
+    post,
+    path = "/tenants/{tenant}/add-face",
+    params(("tenant" = String, Path, description = "Tenant ID")),
+    request_body(content_type = "multipart/form-data", description = "Upload face photos with user ID"),
+    responses(
+        (status = 200, description = "Face data added successfully", body = ApiResponse<AddFaceResponse>),
+        (status = 400, description = "Bad request - invalid tenant or user ID, or missing required fields"),
+        (status = 404, description = "Tenant not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Tenants"
+)]
+pub async fn add_tenant_face(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+    Extension(config): Extension<SharedConfig>,
+    Extension(registry): Extension<SharedRegistry>,
+    Extension(images): Extension<SharedImageStore>,
+    Extension(audit): Extension<AuditContext>,
+    Path(tenant): Path<String>,
+    multipart: Multipart,
+) -> Result<Json<ApiResponse<AddFaceResponse>>, (StatusCode, String)> {
+    audit.target(&tenant);
+    let model = tenant_model(&registry, &tenant).await?;
+    enroll(&tenant, &model, &db, &images, &config, &audit, multipart).await
+}
+
+// Store the uploaded photos in the tenant's gallery and retrain its model
+async fn enroll(
+    tenant: &str,
+    model: &SharedModel,
+    db: &SharedDb,
+    images: &SharedImageStore,
+    config: &Config,
+    audit: &AuditContext,
     mut multipart: Multipart,
 ) -> Result<Json<ApiResponse<AddFaceResponse>>, (StatusCode, String)> {
     let mut user_id: Option<String> = None;
     let mut saved_count = 0;
     let mut saved_paths: Vec<String> = Vec::new();
-    let knowledge_path = "knowledge";
-
-    // Ensure knowledge directory exists
-    fs::create_dir_all(knowledge_path)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let mut rejected: Vec<RejectedPhoto> = Vec::new();
+    let paths = TenantPaths::new(config, tenant);
 
     while let Some(field) = multipart
         .next_field()
@@ -100,6 +231,8 @@ pub async fn add_face(
                 .text()
                 .await
                 .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+            audit.target(format!("{}/{}", tenant, data));
+            check_user_id(&data).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
             user_id = Some(data);
         } else if name == "photos" {
             // Get the user_id first
@@ -108,16 +241,7 @@ pub async fn add_face(
                 "User ID must be provided before photos".to_string(),
             ))?;
 
-            // Create user directory
-            let user_dir = format!("{}/{}", knowledge_path, uid);
-            fs::create_dir_all(&user_dir)
-                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-            // Get filename
-            let filename = field
-                .file_name()
-                .map(|s| s.to_string())
-                .unwrap_or_else(|| format!("{}.jpg", Uuid::new_v4()));
+            let filename = upload_file_name(field.file_name());
 
             // Get file data
             let data = field
@@ -125,10 +249,18 @@ pub async fn add_face(
                 .await
                 .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
 
+            if let Some(reasons) = enrollment_rejection(model, &data).await? {
+                println!("Rejected image {}: {}", filename, reasons.join(", "));
+                rejected.push(RejectedPhoto { filename, reasons });
+                continue;
+            }
+
             // Save file
-            let file_path = format!("{}/{}", user_dir, filename);
-            fs::write(&file_path, data)
-                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            let file_path = paths.image_key(&uid, &filename);
+            images
+                .put(&file_path, &data)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Image store error: {}", e)))?;
 
             saved_paths.push(file_path.clone());
             saved_count += 1;
@@ -141,6 +273,17 @@ pub async fn add_face(
         "User ID is required".to_string(),
     ))?;
 
+    if saved_count == 0 && !rejected.is_empty() {
+        let reasons: Vec<String> = rejected
+            .iter()
+            .map(|photo| format!("{}: {}", photo.filename, photo.reasons.join(", ")))
+            .collect();
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("All photos were rejected - {}", reasons.join("; ")),
+        ));
+    }
+
     if saved_count == 0 {
         return Err((
             StatusCode::BAD_REQUEST,
@@ -149,12 +292,12 @@ pub async fn add_face(
     }
 
     // Save to database
-    db.upsert_user(&uid)
+    db.upsert_user(tenant, &uid)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
 
     for image_path in &saved_paths {
-        db.insert_face_image(&uid, image_path)
+        db.insert_face_image(tenant, &uid, image_path)
             .await
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
     }
@@ -162,12 +305,16 @@ pub async fn add_face(
     println!("Saved {} images to database for user {}", saved_count, uid);
 
     // Train model after adding new data
+    let gallery = Gallery::load(db, images.as_ref(), tenant)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
     let mut model_guard = model.lock().await;
-    model_guard
-        .train(knowledge_path)
+    let training = model_guard
+        .train(&gallery)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Training failed: {}", e)))?;
 
     println!("Model retrained successfully");
+    audit.detail(format!("{} photos saved, {} rejected", saved_count, rejected.len()));
 
     Ok(Json(ApiResponse {
         success: true,
@@ -175,35 +322,152 @@ pub async fn add_face(
         data: Some(AddFaceResponse {
             user_id: uid,
             images_saved: saved_count,
+            rejected,
+            training,
         }),
     }))
 }
 
+// The final component of the client's file name, so an upload cannot land outside the
+// user's folder; anything that is not a plain image file name gets a random one
+fn upload_file_name(file_name: Option<&str>) -> String {
+    let name = file_name
+        .and_then(|name| name.rsplit(['/', '\\']).next())
+        .unwrap_or_default();
+    if name.is_empty() || name.starts_with('.') || !is_image_file(std::path::Path::new(name)) {
+        return format!("{}.jpg", Uuid::new_v4());
+    }
+
+    name.to_string()
+}
+
 /// Detect face and identify user
 ///
-/// Upload an image to detect and identify a face. Returns the user ID if a match is found.
+/// Upload an image to detect and identify a face. Returns the user ID if a match is found,
+/// together with sharpness, brightness, contrast, size and pose measurements of the face.
+/// When `quality.reject_probes` is enabled, low-quality faces are refused instead.
+///
+/// With `?annotate=true` (or `Accept: image/jpeg`) every face in the image is identified and the
+/// image is returned as JPEG with boxes, user IDs and confidences drawn on it. The per-face
+/// results are also sent as JSON in the `X-Detected-Faces` header. Faces refused by
+/// `quality.reject_probes` are drawn as unknown, list their `quality_failures` and are not logged.
+///
+/// With `?liveness=passive` the photo is first checked for print and screen attacks; with
+/// `?liveness=blink` or `?liveness=head_turn` a short sequence of `frames` must be uploaded in
+/// which the user performs the challenge. Probes scoring below the liveness threshold are
+/// refused without being identified.
+///
+/// When `unknowns.retain` is enabled, faces that match nobody are kept for clustering
+/// (see `GET /unknowns/clusters`).
 #[utoipa::path(
     post,
     path = "/detect-face",
+    params(
+        ("annotate" = Option<bool>, Query, description = "Return the annotated image as JPEG"),
+        ("liveness" = Option<String>, Query, description = "Liveness check: passive, blink or head_turn"),
+        ("source" = Option<String>, Query, description = "Camera or client the photo came from, kept in the detection log")
+    ),
     request_body(content_type = "multipart/form-data", description = "Upload photo for face detection"),
     responses(
-        (status = 200, description = "Detection completed", body = ApiResponse<DetectFaceResponse>),
-        (status = 400, description = "Bad request - no image uploaded"),
+        (status = 200, description = "Detection completed (JSON, or JPEG when annotating)",
+            content(
+                (ApiResponse<DetectFaceResponse> = "application/json"),
+                (Vec<u8> = "image/jpeg")
+            ),
+            headers(("X-Detected-Faces" = String, description = "JSON array of DetectedFace, annotated responses only"))),
+        (status = 400, description = "Bad request - no image uploaded, or liveness check failed", body = ApiResponse<DetectFaceResponse>),
         (status = 500, description = "Internal server error")
     ),
     tag = "Face Recognition"
 )]
 pub async fn detect_face(
     State((model, db)): State<(SharedModel, SharedDb)>,
-    mut multipart: Multipart,
-) -> Result<Json<ApiResponse<DetectFaceResponse>>, (StatusCode, String)> {
-    // Save uploaded image temporarily
-    let temp_dir = "temp";
-    fs::create_dir_all(temp_dir)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Query(mut query): Query<DetectFaceQuery>,
+    Extension(liveness): Extension<SharedLiveness>,
+    Extension(config): Extension<SharedConfig>,
+    headers: HeaderMap,
+    multipart: Multipart,
+) -> Result<Response, (StatusCode, String)> {
+    query.annotate |= accepts_jpeg(&headers);
+    detect(DEFAULT_TENANT, &model, &db, &liveness, &config, query, multipart).await
+}
 
-    let temp_filename = format!("{}/{}.jpg", temp_dir, Uuid::new_v4());
-    let mut image_saved = false;
+/// Detect face and identify user within a tenant
+///
+/// Same as `/detect-face`, but the probe is only compared with the tenant's own gallery, so it
+/// can never match an identity enrolled for another tenant. Unknown faces are not retained
+/// for tenants other than `default`.
+#[utoipa::path(
+    post,
+    path = "/tenants/{tenant}/detect-face",
+    params(
+        ("tenant" = String, Path, description = "Tenant ID"),
+        ("annotate" = Option<bool>, Query, description = "Return the annotated image as JPEG"),
+        ("liveness" = Option<String>, Query, description = "Liveness check: passive, blink or head_turn"),
+        ("source" = Option<String>, Query, description = "Camera or client the photo came from, kept in the detection log")
+    ),
+    request_body(content_type = "multipart/form-data", description = "Upload photo for face detection"),
+    responses(
+        (status = 200, description = "Detection completed (JSON, or JPEG when annotating)",
+            content(
+                (ApiResponse<DetectFaceResponse> = "application/json"),
+                (Vec<u8> = "image/jpeg")
+            ),
+            headers(("X-Detected-Faces" = String, description = "JSON array of DetectedFace, annotated responses only"))),
+        (status = 400, description = "Bad request - invalid tenant, no image uploaded, or liveness check failed", body = ApiResponse<DetectFaceResponse>),
+        (status = 404, description = "Tenant not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Tenants"
+)]
+pub async fn detect_tenant_face(
+    Path(tenant): Path<String>,
+    Query(mut query): Query<DetectFaceQuery>,
+    Extension(liveness): Extension<SharedLiveness>,
+    Extension(config): Extension<SharedConfig>,
+    Extension(registry): Extension<SharedRegistry>,
+    headers: HeaderMap,
+    multipart: Multipart,
+) -> Result<Response, (StatusCode, String)> {
+    let model = tenant_model(&registry, &tenant).await?;
+    query.annotate |= accepts_jpeg(&headers);
+    detect(&tenant, &model, registry.db(), &liveness, &config, query, multipart).await
+}
+
+pub(crate) async fn tenant_model(registry: &SharedRegistry, tenant: &str) -> Result<SharedModel, (StatusCode, String)> {
+    check_tenant_id(tenant).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    registry
+        .model(tenant)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to load model for tenant {}: {}", tenant, e),
+            )
+        })?
+        .ok_or((StatusCode::NOT_FOUND, format!("Tenant not found: {}", tenant)))
+}
+
+fn accepts_jpeg(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| accept.contains("image/jpeg"))
+}
+
+// Identify the uploaded probe against the tenant's model and log the result
+async fn detect(
+    tenant: &str,
+    model: &SharedModel,
+    db: &SharedDb,
+    liveness: &SharedLiveness,
+    config: &Config,
+    query: DetectFaceQuery,
+    mut multipart: Multipart,
+) -> Result<Response, (StatusCode, String)> {
+    let mut photo: Option<Bytes> = None;
+    let mut frames: Vec<Bytes> = Vec::new();
 
     while let Some(field) = multipart
         .next_field()
@@ -217,47 +481,175 @@ pub async fn detect_face(
                 .bytes()
                 .await
                 .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
-
-            fs::write(&temp_filename, data)
-                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-            image_saved = true;
-            break;
+            photo = Some(data);
+        } else if name == "frames" {
+            if frames.len() == MAX_CHALLENGE_FRAMES {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("At most {} frames are accepted", MAX_CHALLENGE_FRAMES),
+                ));
+            }
+            let data = field
+                .bytes()
+                .await
+                .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+            frames.push(data);
         }
     }
 
-    if !image_saved {
-        return Err((
+    // Challenge requests may send only frames, the middle one is then identified
+    let photo = photo
+        .or_else(|| frames.get(frames.len() / 2).cloned())
+        .ok_or((
             StatusCode::BAD_REQUEST,
             "No image uploaded. Use 'photo' or 'image' as field name".to_string(),
-        ));
+        ))?;
+
+    let liveness_mode = match query.liveness {
+        Some(mode) => Some(mode),
+        None if liveness.lock().await.is_required() => Some("passive".to_string()),
+        None => None,
+    };
+
+    let liveness_report = match liveness_mode.as_deref() {
+        Some(mode) => Some(check_liveness(model, liveness, mode, &photo, &frames).await?),
+        None => None,
+    };
+
+    if let Some(report) = &liveness_report
+        && !report.passed
+    {
+        return Ok(ApiResponse {
+            success: false,
+            message: "Liveness check failed".to_string(),
+            data: Some(DetectFaceResponse {
+                user_id: None,
+                detected: false,
+                detection_id: None,
+                liveness: liveness_report,
+                quality: None,
+            }),
+        }
+        .into_response());
     }
 
+    // Save uploaded image temporarily
+    let temp_dir = &config.paths.temp_dir;
+    fs::create_dir_all(temp_dir)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let temp_filename = format!("{}/{}.jpg", temp_dir, Uuid::new_v4());
+    fs::write(&temp_filename, &photo)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Unknown-face clusters are enrolled into the default gallery only
+    let retain_unknowns = config.unknowns.retain && tenant == DEFAULT_TENANT;
+
     // Predict using model
     let mut model_guard = model.lock().await;
+    let cipher = model_guard.cipher().clone();
+
+    if query.annotate {
+        let annotated = annotate_image(&mut model_guard, &temp_filename);
+        let thresholds = model_guard.quality_thresholds().clone();
+        drop(model_guard);
+
+        let (jpeg, matches) = annotated.map_err(|e| {
+            let _ = fs::remove_file(&temp_filename);
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("Detection failed: {}", e))
+        })?;
+
+        let mut faces: Vec<DetectedFace> = Vec::with_capacity(matches.len());
+        for face in &matches {
+            let mut detected = DetectedFace::from(face);
+            // Refused faces are reported but, as with single-face requests, not logged
+            detected.quality_failures = thresholds.probe_failures(&face.quality);
+            if !detected.quality_failures.is_empty() {
+                faces.push(detected);
+                continue;
+            }
+
+            detected.detection_id = db
+                .log_detection(
+                    tenant,
+                    face.user_id.as_deref(),
+                    Some(face.confidence),
+                    Some(&temp_filename),
+                    query.source.as_deref(),
+                )
+                .await
+                .map_err(|e| {
+                    eprintln!("Failed to log detection: {}", e);
+                })
+                .ok();
+            if face.user_id.is_none() && retain_unknowns {
+                retain_unknown(db, &config.unknowns, &cipher, &photo, face, detected.detection_id).await;
+            }
+            faces.push(detected);
+        }
+
+        let _ = fs::remove_file(&temp_filename);
+
+        let mut response = ([(header::CONTENT_TYPE, "image/jpeg")], jpeg).into_response();
+        // Non-ASCII user IDs cannot be sent in a header; the image still carries the labels
+        let faces_json = serde_json::to_string(&faces).unwrap_or_default();
+        if let Ok(value) = HeaderValue::from_str(&faces_json) {
+            response.headers_mut().insert("x-detected-faces", value);
+        }
+
+        return Ok(response);
+    }
     
-    let result = model_guard
+    let face = model_guard
         .predict(&temp_filename)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Detection failed: {}", e)))?;
 
+    let quality_failures = model_guard.quality_thresholds().probe_failures(&face.quality);
+    drop(model_guard);
+
+    if !quality_failures.is_empty() {
+        let _ = fs::remove_file(&temp_filename);
+
+        return Ok(ApiResponse {
+            success: false,
+            message: format!("Face quality too low: {}", quality_failures.join(", ")),
+            data: Some(DetectFaceResponse {
+                user_id: None,
+                detected: false,
+                detection_id: None,
+                liveness: liveness_report,
+                quality: Some(face.quality),
+            }),
+        }
+        .into_response());
+    }
+
+    let result = face.user_id.clone();
     let detected = result.is_some();
 
     // Log detection to database
-    db.log_detection(
-        result.as_deref(),
-        None, // confidence will be added later
-        Some(&temp_filename),
-    )
-    .await
-    .map_err(|e| {
-        eprintln!("Failed to log detection: {}", e);
-    })
-    .ok();
+    let detection_id = db
+        .log_detection(
+            tenant,
+            result.as_deref(),
+            Some(face.confidence),
+            Some(&temp_filename),
+            query.source.as_deref(),
+        )
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to log detection: {}", e);
+        })
+        .ok();
+
+    if !detected && retain_unknowns {
+        retain_unknown(db, &config.unknowns, &cipher, &photo, &face, detection_id).await;
+    }
 
     // Clean up temp file
     let _ = fs::remove_file(&temp_filename);
 
-    Ok(Json(ApiResponse {
+    Ok(ApiResponse {
         success: true,
         message: if detected {
             "Face detected successfully".to_string()
@@ -267,6 +659,319 @@ pub async fn detect_face(
         data: Some(DetectFaceResponse {
             user_id: result,
             detected,
+            detection_id,
+            liveness: liveness_report,
+            quality: Some(face.quality),
         }),
-    }))
+    }
+    .into_response())
+}
+
+/// Anonymize faces in a photo
+///
+/// Detects every face and redacts it with a gaussian blur, pixelation or a solid box.
+/// `redact=unknown` only redacts faces that do not match an enrolled user, and user IDs
+/// listed in `allow` are always left visible. Returns the redacted image as JPEG.
+#[utoipa::path(
+    post,
+    path = "/faces/anonymize",
+    request_body(content_type = "multipart/form-data", description = "Upload photo with optional method (blur|pixelate|box), redact (all|unknown) and allow (comma-separated user IDs)"),
+    responses(
+        (status = 200, description = "Redacted image", content_type = "image/jpeg",
+            headers(("X-Faces-Redacted" = usize, description = "Number of faces redacted"))),
+        (status = 400, description = "Bad request - no image uploaded or invalid option"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Face Recognition"
+)]
+pub async fn anonymize_faces(
+    State((model, _db)): State<(SharedModel, SharedDb)>,
+    mut multipart: Multipart,
+) -> Result<Response, (StatusCode, String)> {
+    let mut image_data = None;
+    let mut method = RedactMethod::Blur;
+    let mut redact_unknown_only = false;
+    let mut allow: Vec<String> = Vec::new();
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?
+    {
+        let name = field.name().unwrap_or("").to_string();
+
+        if name == "photo" || name == "image" {
+            let data = field
+                .bytes()
+                .await
+                .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+            image_data = Some(data);
+            continue;
+        }
+
+        let value = field
+            .text()
+            .await
+            .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+        match name.as_str() {
+            "method" => {
+                method = value
+                    .parse()
+                    .map_err(|e: anyhow::Error| (StatusCode::BAD_REQUEST, e.to_string()))?;
+            }
+            "redact" => {
+                redact_unknown_only = match value.as_str() {
+                    "all" => false,
+                    "unknown" => true,
+                    other => {
+                        return Err((
+                            StatusCode::BAD_REQUEST,
+                            format!("Unknown redact mode '{}', expected all or unknown", other),
+                        ))
+                    }
+                };
+            }
+            "allow" => {
+                allow.extend(
+                    value
+                        .split(',')
+                        .map(|id| id.trim().to_string())
+                        .filter(|id| !id.is_empty()),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    let image_data = image_data.ok_or((
+        StatusCode::BAD_REQUEST,
+        "No image uploaded. Use 'photo' or 'image' as field name".to_string(),
+    ))?;
+
+    let mut img = decode_image(&image_data)?;
+
+    let mut model_guard = model.lock().await;
+
+    // Identification is only needed when some faces may stay visible; with an empty gallery
+    // nobody can be identified, so every face is redacted
+    let rects = if (redact_unknown_only || !allow.is_empty()) && model_guard.is_trained() {
+        model_guard
+            .predict_faces(&img)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Detection failed: {}", e)))?
+            .into_iter()
+            .filter(|face| match &face.user_id {
+                Some(user_id) => !redact_unknown_only && !allow.contains(user_id),
+                None => true,
+            })
+            .map(|face| face.rect)
+            .collect()
+    } else {
+        model_guard
+            .detect_face_rects(&img)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Detection failed: {}", e)))?
+    };
+    drop(model_guard);
+
+    redact_faces(&mut img, &rects, method)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Redaction failed: {}", e)))?;
+
+    let jpeg = encode_jpeg(&img)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Encoding failed: {}", e)))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/jpeg".to_string()),
+            (header::HeaderName::from_static("x-faces-redacted"), rects.len().to_string()),
+        ],
+        jpeg,
+    )
+        .into_response())
+}
+
+/// Reasons an enrollment photo should not be stored, or None if it is acceptable
+async fn enrollment_rejection(model: &SharedModel, data: &[u8]) -> Result<Option<Vec<String>>, (StatusCode, String)> {
+    if !model.lock().await.quality_thresholds().reject_enrollment {
+        return Ok(None);
+    }
+
+    let img = match decode_image(data) {
+        Ok(img) => img,
+        Err((_, message)) => return Ok(Some(vec![message])),
+    };
+
+    let mut model_guard = model.lock().await;
+    let reasons = match model_guard
+        .assess_quality(&img)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Quality check failed: {}", e)))?
+    {
+        Some(quality) => model_guard.quality_thresholds().failures(&quality),
+        None => vec!["no face detected".to_string()],
+    };
+
+    Ok((!reasons.is_empty()).then_some(reasons))
+}
+
+fn decode_image(data: &[u8]) -> Result<Mat, (StatusCode, String)> {
+    let img = imdecode(&Vector::<u8>::from_slice(data), IMREAD_COLOR)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid image: {}", e)))?;
+
+    if img.empty() {
+        return Err((StatusCode::BAD_REQUEST, "Invalid image".to_string()));
+    }
+
+    Ok(img)
+}
+
+// Keep an unidentified face for clustering; a failure only loses this sample
+async fn retain_unknown(
+    db: &SharedDb,
+    unknowns: &UnknownsConfig,
+    cipher: &Cipher,
+    photo: &[u8],
+    face: &FaceMatch,
+    detection_id: Option<i64>,
+) {
+    let captured = decode_image(photo)
+        .map_err(|(_, message)| anyhow::anyhow!(message))
+        .and_then(|img| capture(unknowns, cipher, &img, face.rect));
+
+    let result = match captured {
+        Ok((image_path, features)) => db
+            .insert_unknown_face(&image_path, &features, detection_id, Some(face.confidence))
+            .await
+            .map(|_| ()),
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = result {
+        eprintln!("Failed to retain unknown face: {}", e);
+    }
+}
+
+/// Run the requested liveness check, on the photo for `passive` or on the frames for a challenge
+async fn check_liveness(
+    model: &SharedModel,
+    liveness: &SharedLiveness,
+    mode: &str,
+    photo: &[u8],
+    frames: &[Bytes],
+) -> Result<LivenessReport, (StatusCode, String)> {
+    if mode == "passive" {
+        let img = decode_image(photo)?;
+        let face = model
+            .lock()
+            .await
+            .detect_face_rects(&img)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Detection failed: {}", e)))?
+            .into_iter()
+            .max_by_key(|rect| rect.area())
+            .ok_or((StatusCode::BAD_REQUEST, "No face detected in image".to_string()))?;
+
+        return liveness
+            .lock()
+            .await
+            .check_passive(&img, face)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Liveness check failed: {}", e)));
+    }
+
+    let challenge: Challenge = mode
+        .parse()
+        .map_err(|e: anyhow::Error| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    if frames.len() < MIN_CHALLENGE_FRAMES {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("The {} challenge needs at least {} frames", challenge.name(), MIN_CHALLENGE_FRAMES),
+        ));
+    }
+
+    let images = frames
+        .iter()
+        .map(|data| decode_image(data))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut faces = Vec::with_capacity(images.len());
+    let mut model_guard = model.lock().await;
+    for img in &images {
+        let rects = model_guard
+            .detect_face_rects(img)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Detection failed: {}", e)))?;
+        faces.push(rects.into_iter().max_by_key(|rect| rect.area()));
+    }
+    drop(model_guard);
+
+    // Failures here are almost always unusable input (no face, no eye cascade)
+    liveness
+        .lock()
+        .await
+        .check_challenge(&images, &faces, challenge)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Liveness check failed: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, routing::post, Router};
+    use tower::ServiceExt;
+
+    #[test]
+    fn upload_file_name_keeps_only_the_final_component() {
+        assert_eq!(upload_file_name(Some("photo.jpg")), "photo.jpg");
+        assert_eq!(upload_file_name(Some("../../alice/photo.png")), "photo.png");
+        assert_eq!(upload_file_name(Some("..\\..\\alice\\photo.jpeg")), "photo.jpeg");
+
+        for name in [None, Some(""), Some("photos/"), Some(".."), Some(".hidden.jpg"), Some("model.yml")] {
+            let generated = upload_file_name(name);
+            assert!(generated.ends_with(".jpg") && generated.len() == 40, "{:?} -> {}", name, generated);
+        }
+    }
+
+    fn multipart(fields: &[(&str, &[u8])]) -> Request<Body> {
+        let mut body = Vec::new();
+        for (name, value) in fields {
+            body.extend_from_slice(format!("--boundary\r\nContent-Disposition: form-data; name=\"{}\"", name).as_bytes());
+            if *name == "photo" {
+                body.extend_from_slice(b"; filename=\"probe.jpg\"\r\nContent-Type: image/jpeg");
+            }
+            body.extend_from_slice(b"\r\n\r\n");
+            body.extend_from_slice(value);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(b"--boundary--\r\n");
+
+        Request::builder()
+            .method("POST")
+            .uri("/faces/anonymize")
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=boundary")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    // With an empty gallery nobody can be recognized, so keeping known faces redacts them all;
+    // detection needs OpenCV and its haarcascade
+    #[tokio::test]
+    async fn anonymizes_every_face_before_training() {
+        let mut config = Config::default();
+        config.paths.model_path = std::env::temp_dir()
+            .join(format!("anonymize-{}/lbph.yml", Uuid::new_v4()))
+            .to_string_lossy()
+            .to_string();
+        config.database.url = "sqlite::memory:".to_string();
+        config.database.max_connections = 1;
+        let model = FaceRecognitionModel::new(&config).unwrap();
+        assert!(!model.is_trained());
+        let db: SharedDb = Arc::new(Database::new(&config.database).await.unwrap());
+        let app = Router::new()
+            .route("/faces/anonymize", post(anonymize_faces))
+            .with_state((Arc::new(Mutex::new(model)), db));
+
+        let photo = fs::read(format!("{}/knowledge/nasri/photo_1.jpg", env!("CARGO_MANIFEST_DIR"))).unwrap();
+        for mode in [("redact", b"unknown" as &[u8]), ("allow", b"nasri"), ("redact", b"all")] {
+            let response = app.clone().oneshot(multipart(&[("photo", &photo), mode])).await.unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK, "{:?}", mode);
+            assert_eq!(response.headers()["x-faces-redacted"], "1", "{:?}", mode);
+        }
+    }
 }
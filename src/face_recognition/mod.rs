@@ -1,4 +1,8 @@
 pub mod model;
 pub mod handlers;
+pub mod annotate;
+pub mod augment;
+pub mod anonymize;
+pub mod quality;
 
-pub use model::FaceRecognitionModel;
+pub use model::{FaceMatch, FaceRecognitionModel};
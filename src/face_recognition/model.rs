@@ -2,56 +2,85 @@ use anyhow::Result;
 use opencv::{
     core::{Mat, Vector, Size, Rect},
     face::LBPHFaceRecognizer,
-    imgcodecs::{imread, IMREAD_GRAYSCALE},
-    imgproc::{resize, INTER_LINEAR},
+    imgcodecs::{imdecode, imread, IMREAD_GRAYSCALE},
+    imgproc::{cvt_color, resize, COLOR_BGR2GRAY, INTER_LINEAR},
     objdetect::CascadeClassifier,
     prelude::*,
 };
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::sync::Arc;
+use uuid::Uuid;
 
-const FACE_SIZE: (i32, i32) = (200, 200);
+use super::augment::{augment, AugmentationConfig, TrainingStats};
+use super::quality::{assess, FaceQuality, QualityThresholds};
+use crate::config::{Config, RecognitionConfig};
+use crate::encryption::{read_file, write_file, Cipher, SharedCipher};
+use crate::images::Gallery;
+
+/// Cropped training faces grouped by user ID
+pub type UserFaces = Vec<(String, Vec<Mat>)>;
+
+/// Cropped training faces with the key or path of the image they came from, grouped by user ID
+pub type UserFaceFiles = Vec<(String, Vec<(String, Mat)>)>;
+
+/// A single face found in an image together with its recognition result
+#[derive(Debug, Clone)]
+pub struct FaceMatch {
+    pub rect: Rect,
+    pub user_id: Option<String>,
+    pub confidence: f64,
+    pub quality: FaceQuality,
+}
 
 pub struct FaceRecognitionModel {
     recognizer: opencv::core::Ptr<LBPHFaceRecognizer>,
     labels_map: HashMap<i32, String>, // label -> user_id
     cascade: CascadeClassifier,
+    recognition: RecognitionConfig,
+    quality: QualityThresholds,
+    augmentation: AugmentationConfig,
+    match_threshold: f64,
+    model_path: String,
+    /// OpenCV only reads and writes model files by path, so encrypted models pass through here
+    temp_dir: String,
+    cipher: SharedCipher,
     is_trained: bool,
 }
 
 impl FaceRecognitionModel {
-    pub fn new() -> Result<Self> {
-        let recognizer = LBPHFaceRecognizer::create(1, 8, 8, 8, 123.0)?;
-        
-        // Try multiple possible locations for haarcascade file
-        let possible_paths = vec![
-            "/usr/local/share/opencv4/haarcascades/haarcascade_frontalface_default.xml",
-            "/opt/homebrew/share/opencv4/haarcascades/haarcascade_frontalface_default.xml",
-            "/usr/share/opencv4/haarcascades/haarcascade_frontalface_default.xml",
-            "./haarcascade_frontalface_default.xml", // Local fallback
-        ];
-        
-        let mut cascade_path = None;
-        for path in possible_paths {
-            if std::path::Path::new(path).exists() {
-                cascade_path = Some(path);
-                println!("Found haarcascade at: {}", path);
-                break;
-            }
-        }
+    pub fn new(config: &Config) -> Result<Self> {
+        Self::with_model_path(config, &config.paths.model_path)
+    }
+
+    /// Like `new`, but saving to `model_path` after training (used for tenant models)
+    pub fn with_model_path(config: &Config, model_path: &str) -> Result<Self> {
+        let recognition = config.recognition.clone();
+        let recognizer = LBPHFaceRecognizer::create(
+            recognition.lbph_radius,
+            recognition.lbph_neighbors,
+            recognition.lbph_grid_x,
+            recognition.lbph_grid_y,
+            recognition.lbph_max_distance,
+        )?;
         
-        let cascade_path = cascade_path.ok_or_else(|| {
-            anyhow::anyhow!(
-                "Haarcascade file not found. Please:\n\
-                1. Install OpenCV: brew install opencv\n\
-                2. Or download manually from:\n\
-                   https://raw.githubusercontent.com/opencv/opencv/master/data/haarcascades/haarcascade_frontalface_default.xml\n\
-                   Save to: ./haarcascade_frontalface_default.xml"
-            )
-        })?;
+        let cascade_path = recognition
+            .cascade_path
+            .clone()
+            .or_else(|| find_haarcascade("haarcascade_frontalface_default.xml"))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Haarcascade file not found. Please:\n\
+                    1. Install OpenCV: brew install opencv\n\
+                    2. Or download manually from:\n\
+                       https://raw.githubusercontent.com/opencv/opencv/master/data/haarcascades/haarcascade_frontalface_default.xml\n\
+                       Save to: ./haarcascade_frontalface_default.xml\n\
+                    3. Or set recognition.cascade_path / FACE_CASCADE_PATH"
+                )
+            })?;
         
-        let cascade = CascadeClassifier::new(cascade_path)?;
+        let cascade = CascadeClassifier::new(&cascade_path)?;
         
         // Verify cascade loaded successfully
         if cascade.empty()? {
@@ -65,142 +94,467 @@ impl FaceRecognitionModel {
             recognizer,
             labels_map: HashMap::new(),
             cascade,
+            match_threshold: recognition.match_threshold,
+            recognition,
+            quality: config.quality.clone(),
+            augmentation: config.augmentation.clone(),
+            model_path: model_path.to_string(),
+            temp_dir: config.paths.temp_dir.clone(),
+            cipher: Arc::new(Cipher::open(&config.encryption)?),
             is_trained: false,
         })
     }
 
-    pub fn train(&mut self, knowledge_path: &str) -> Result<()> {
-        let mut images = Vector::<Mat>::new();
-        let mut labels = Vector::<i32>::new();
-        let mut current_label = 0;
-        self.labels_map.clear();
+    /// Train on a tenant's gallery as listed in `face_images`, replacing the current model
+    pub fn train(&mut self, gallery: &Gallery) -> Result<TrainingStats> {
+        let (users, failed_images) = self.gallery_faces(gallery);
+        let mut stats = self.train_faces(without_sources(users))?;
+        stats.failed_images = failed_images + gallery.unreadable.len();
+
+        // Keep the saved model in step with the gallery so a restart serves the same model
+        if self.is_trained {
+            self.save(&self.model_path)?;
+        } else {
+            remove_saved_model(&self.model_path)?;
+        }
+
+        Ok(stats)
+    }
+
+    /// Write the trained recognizer to `path` and its label map to `{path}.labels.json`, both
+    /// encrypted when encryption is enabled
+    pub fn save(&self, path: &str) -> Result<()> {
+        if !self.is_trained {
+            return Err(anyhow::anyhow!("Model not trained yet"));
+        }
+
+        if let Some(parent) = Path::new(path).parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+
+        if self.cipher.is_enabled() {
+            let plain_path = self.temp_model_path(path)?;
+            let written = self
+                .recognizer
+                .write(&plain_path)
+                .map_err(anyhow::Error::from)
+                .and_then(|_| Ok(fs::read(&plain_path)?));
+            let _ = fs::remove_file(&plain_path);
+            write_file(&self.cipher, path, &written?)?;
+        } else {
+            self.recognizer.write(path)?;
+        }
+        write_file(&self.cipher, &labels_path(path), serde_json::to_string_pretty(&self.labels_map)?.as_bytes())?;
+
+        println!("Model saved to {}", path);
+        Ok(())
+    }
+
+    /// Load a model written by `save`, encrypted or not
+    pub fn load(&mut self, path: &str) -> Result<()> {
+        let labels = read_file(&self.cipher, &labels_path(path))?;
+        self.labels_map = serde_json::from_slice(&labels)?;
 
-        let knowledge_dir = Path::new(knowledge_path);
-        if !knowledge_dir.exists() {
-            fs::create_dir_all(knowledge_dir)?;
-            return Ok(()); // No data to train yet
+        let data = fs::read(path)?;
+        if Cipher::is_encrypted(&data) {
+            let plain_path = self.temp_model_path(path)?;
+            let read = fs::write(&plain_path, self.cipher.decrypt(&data)?)
+                .map_err(anyhow::Error::from)
+                .and_then(|_| Ok(self.recognizer.read(&plain_path)?));
+            let _ = fs::remove_file(&plain_path);
+            read?;
+        } else {
+            self.recognizer.read(path)?;
         }
+        self.is_trained = !self.labels_map.is_empty();
+
+        println!("Model loaded from {} with {} users", path, self.labels_map.len());
+        Ok(())
+    }
+
+    /// Detect and crop the face in every image under `{dir}/{user_id}/`.
+    /// Returns the faces grouped by user (sorted by user ID and file name) and the number of
+    /// images that could not be used.
+    pub fn load_faces(&mut self, dir: &str) -> Result<(UserFaces, usize)> {
+        let (users, failed_images) = self.load_face_files(dir)?;
+
+        Ok((without_sources(users), failed_images))
+    }
+
+    /// Detect and crop the face in every image of a gallery. Returns the faces with their
+    /// image keys and the number of images that could not be used.
+    pub fn gallery_faces(&mut self, gallery: &Gallery) -> (UserFaceFiles, usize) {
+        let mut users = Vec::new();
+        let mut failed_images = 0;
+
+        for (user_id, files) in &gallery.users {
+            let mut user_faces = Vec::new();
+            for (key, data) in files {
+                match self.process_face_bytes(data) {
+                    Ok(face_mat) => user_faces.push((key.clone(), face_mat)),
+                    Err(e) => {
+                        eprintln!("Failed to process {}: {}", key, e);
+                        failed_images += 1;
+                    }
+                }
+            }
+
+            if !user_faces.is_empty() {
+                users.push((user_id.clone(), user_faces));
+            }
+        }
+
+        (users, failed_images)
+    }
+
+    /// Same as `load_faces`, but keeps the path of the image each face was cropped from
+    pub fn load_face_files(&mut self, dir: &str) -> Result<(UserFaceFiles, usize)> {
+        let mut users = Vec::new();
+        let mut failed_images = 0;
+
+        let mut user_dirs: Vec<_> = fs::read_dir(dir)?
+            .collect::<std::io::Result<Vec<_>>>()?
+            .into_iter()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect();
+        user_dirs.sort();
 
         // Iterate through user directories
-        for entry in fs::read_dir(knowledge_dir)? {
-            let entry = entry?;
-            let path = entry.path();
+        for path in user_dirs {
+            let user_id = path.file_name()
+                .and_then(|s| s.to_str())
+                .unwrap_or("")
+                .to_string();
             
-            if path.is_dir() {
-                let user_id = path.file_name()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("")
-                    .to_string();
-                
-                if user_id.is_empty() {
-                    continue;
-                }
+            if user_id.is_empty() {
+                continue;
+            }
 
-                // Process all images in this user's directory
-                for img_entry in fs::read_dir(&path)? {
-                    let img_entry = img_entry?;
-                    let img_path = img_entry.path();
-                    
-                    if img_path.is_file() {
-                        if let Some(ext) = img_path.extension() {
-                            let ext_str = ext.to_string_lossy().to_lowercase();
-                            if ext_str == "jpg" || ext_str == "jpeg" || ext_str == "png" {
-                                match self.process_face_image(&img_path.to_string_lossy()) {
-                                    Ok(face_mat) => {
-                                        images.push(face_mat);
-                                        labels.push(current_label);
-                                    }
-                                    Err(e) => {
-                                        eprintln!("Failed to process {}: {}", img_path.display(), e);
-                                    }
-                                }
-                            }
-                        }
+            let mut img_paths: Vec<_> = fs::read_dir(&path)?
+                .collect::<std::io::Result<Vec<_>>>()?
+                .into_iter()
+                .map(|entry| entry.path())
+                .filter(|img_path| img_path.is_file() && is_image_file(img_path))
+                .collect();
+            img_paths.sort();
+
+            // Process all images in this user's directory
+            let mut user_faces = Vec::new();
+            for img_path in img_paths {
+                match self.process_face_image(&img_path.to_string_lossy()) {
+                    Ok(face_mat) => {
+                        user_faces.push((img_path.to_string_lossy().to_string(), face_mat));
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to process {}: {}", img_path.display(), e);
+                        failed_images += 1;
                     }
                 }
+            }
+
+            if !user_faces.is_empty() {
+                users.push((user_id, user_faces));
+            }
+        }
+
+        Ok((users, failed_images))
+    }
 
-                if !images.is_empty() {
-                    self.labels_map.insert(current_label, user_id);
-                    current_label += 1;
+    /// Train on already cropped faces grouped by user, replacing the current model
+    pub fn train_faces(&mut self, users: UserFaces) -> Result<TrainingStats> {
+        let mut images = Vector::<Mat>::new();
+        let mut labels = Vector::<i32>::new();
+        let mut stats = TrainingStats::default();
+        self.labels_map.clear();
+
+        for (current_label, (user_id, user_faces)) in (0..).zip(users) {
+            if user_faces.is_empty() {
+                continue;
+            }
+
+            stats.original_images += user_faces.len();
+
+            // Synthetic variants only ever go into training, never onto disk
+            let mut variants = Vec::new();
+            if self.augmentation.applies_to(user_faces.len()) {
+                for face_mat in &user_faces {
+                    variants.extend(augment(face_mat, &self.augmentation)?);
                 }
+                stats.augmented_images += variants.len();
             }
+
+            for face_mat in user_faces.into_iter().chain(variants) {
+                images.push(face_mat);
+                labels.push(current_label);
+            }
+
+            self.labels_map.insert(current_label, user_id);
         }
 
-        if !images.is_empty() {
+        stats.users = self.labels_map.len();
+
+        if images.is_empty() {
+            self.is_trained = false;
+        } else {
             self.recognizer.train(&images, &labels)?;
             self.is_trained = true;
-            println!("Model trained with {} images and {} users", images.len(), self.labels_map.len());
+            println!(
+                "Model trained with {} images ({} augmented) and {} users",
+                images.len(),
+                stats.augmented_images,
+                stats.users
+            );
         }
 
-        Ok(())
+        Ok(stats)
     }
 
-    fn process_face_image(&mut self, image_path: &str) -> Result<Mat> {
+    /// Load an image file and return its first face, cropped and resized for the recognizer
+    pub fn process_face_image(&mut self, image_path: &str) -> Result<Mat> {
         let img = imread(image_path, IMREAD_GRAYSCALE)?;
-        
+        self.first_face(&img)
+    }
+
+    /// Same as `process_face_image` for an encoded image held in memory
+    pub fn process_face_bytes(&mut self, data: &[u8]) -> Result<Mat> {
+        let img = imdecode(&Vector::<u8>::from_slice(data), IMREAD_GRAYSCALE)?;
+        self.first_face(&img)
+    }
+
+    fn first_face(&mut self, img: &Mat) -> Result<Mat> {
         if img.empty() {
             return Err(anyhow::anyhow!("Failed to load image"));
         }
 
-        // Detect face in the image
+        let faces = self.detect_faces(img)?;
+
+        if faces.is_empty() {
+            return Err(anyhow::anyhow!("No face detected in image"));
+        }
+
+        // Use the first detected face
+        crop_face(img, faces.get(0)?, self.recognition.face_size)
+    }
+
+    fn detect_faces(&mut self, gray: &Mat) -> Result<Vector<Rect>> {
         let mut faces = Vector::<Rect>::new();
         self.cascade.detect_multi_scale(
-            &img,
+            gray,
             &mut faces,
-            1.1,
-            3,
+            self.recognition.scale_factor,
+            self.recognition.min_neighbors,
             0,
-            Size::new(30, 30),
+            Size::new(self.recognition.min_face_size, self.recognition.min_face_size),
             Size::new(0, 0),
         )?;
 
+        Ok(faces)
+    }
+
+    /// Identify the first face in an image file
+    pub fn predict(&mut self, image_path: &str) -> Result<FaceMatch> {
+        if !self.is_trained {
+            return Err(anyhow::anyhow!("Model not trained yet"));
+        }
+
+        let img = imread(image_path, IMREAD_GRAYSCALE)?;
+
+        if img.empty() {
+            return Err(anyhow::anyhow!("Failed to load image"));
+        }
+
+        let faces = self.detect_faces(&img)?;
+
         if faces.is_empty() {
             return Err(anyhow::anyhow!("No face detected in image"));
         }
 
-        // Use the first detected face
-        let face_rect = faces.get(0)?;
-        let face_roi = Mat::roi(&img, face_rect)?;
-        
-        // Resize to standard size
-        let mut resized = Mat::default();
-        resize(
-            &face_roi,
-            &mut resized,
-            Size::new(FACE_SIZE.0, FACE_SIZE.1),
-            0.0,
-            0.0,
-            INTER_LINEAR,
-        )?;
+        // Use the first detected face, as training does
+        let rect = faces.get(0)?;
+        let face_mat = crop_face(&img, rect, self.recognition.face_size)?;
+        let (user_id, confidence) = self.predict_face(&face_mat)?;
 
-        Ok(resized)
+        Ok(FaceMatch {
+            rect,
+            user_id,
+            confidence,
+            quality: assess(&img, rect)?,
+        })
+    }
+
+    /// Measure the quality of the first face in a BGR or grayscale image, if there is one
+    pub fn assess_quality(&mut self, img: &Mat) -> Result<Option<FaceQuality>> {
+        let gray = to_gray(img)?;
+        let faces = self.detect_faces(&gray)?;
+
+        match faces.iter().next() {
+            Some(rect) => Ok(Some(assess(&gray, rect)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn quality_thresholds(&self) -> &QualityThresholds {
+        &self.quality
+    }
+
+    /// LBPH distance below which a face is accepted as a match
+    pub fn match_threshold(&self) -> f64 {
+        self.match_threshold
+    }
+
+    pub fn set_match_threshold(&mut self, threshold: f64) {
+        self.match_threshold = threshold;
     }
 
-    pub fn predict(&mut self, image_path: &str) -> Result<Option<String>> {
+    /// Detect every face in a BGR or grayscale image without identifying them
+    pub fn detect_face_rects(&mut self, img: &Mat) -> Result<Vec<Rect>> {
+        let gray = to_gray(img)?;
+
+        Ok(self.detect_faces(&gray)?.to_vec())
+    }
+
+    /// Detect every face in a BGR or grayscale image and identify each one
+    pub fn predict_faces(&mut self, img: &Mat) -> Result<Vec<FaceMatch>> {
+        if !self.is_trained {
+            return Err(anyhow::anyhow!("Model not trained yet"));
+        }
+
+        let gray = to_gray(img)?;
+
+        let mut matches = Vec::new();
+        for rect in self.detect_faces(&gray)? {
+            let face_mat = crop_face(&gray, rect, self.recognition.face_size)?;
+            let (user_id, confidence) = self.predict_face(&face_mat)?;
+            let quality = assess(&gray, rect)?;
+            matches.push(FaceMatch { rect, user_id, confidence, quality });
+        }
+
+        Ok(matches)
+    }
+
+    fn predict_face(&self, face_mat: &Mat) -> Result<(Option<String>, f64)> {
+        let (user_id, confidence) = self.nearest(face_mat)?;
+
+        println!("Predicted user: {:?}, confidence: {}", user_id, confidence);
+
+        if confidence < self.match_threshold {
+            return Ok((user_id, confidence));
+        }
+
+        Ok((None, confidence))
+    }
+
+    /// Closest enrolled user and LBPH distance for a cropped face, without applying the
+    /// match threshold
+    pub fn nearest(&self, face_mat: &Mat) -> Result<(Option<String>, f64)> {
         if !self.is_trained {
             return Err(anyhow::anyhow!("Model not trained yet"));
         }
 
-        let face_mat = self.process_face_image(image_path)?;
-        
         let mut label = 0;
         let mut confidence = 0.0;
-        self.recognizer.predict(&face_mat, &mut label, &mut confidence)?;
+        self.recognizer.predict(face_mat, &mut label, &mut confidence)?;
 
-        println!("Predicted label: {}, confidence: {}", label, confidence);
+        Ok((self.labels_map.get(&label).cloned(), confidence))
+    }
 
-        // Lower confidence means better match in LBPH
-        // Typical threshold is around 50-80
-        if confidence < 80.0 {
-            if let Some(user_id) = self.labels_map.get(&label) {
-                return Ok(Some(user_id.clone()));
-            }
+    pub fn is_trained(&self) -> bool {
+        self.is_trained
+    }
+
+    /// Cipher of the saved model, also used for the unknown faces retained from probes
+    pub fn cipher(&self) -> &SharedCipher {
+        &self.cipher
+    }
+
+    // A unique temporary file with the model's extension, which tells OpenCV the format
+    fn temp_model_path(&self, path: &str) -> Result<String> {
+        fs::create_dir_all(&self.temp_dir)?;
+        let file_name = Path::new(path).file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+
+        Ok(format!("{}/{}-{}", self.temp_dir, Uuid::new_v4(), file_name))
+    }
+}
+
+/// Look for a haarcascade file in the usual OpenCV install locations and the working directory
+pub fn find_haarcascade(file_name: &str) -> Option<String> {
+    // Try multiple possible locations for haarcascade file
+    let possible_dirs = [
+        "/usr/local/share/opencv4/haarcascades",
+        "/opt/homebrew/share/opencv4/haarcascades",
+        "/usr/share/opencv4/haarcascades",
+        ".", // Local fallback
+    ];
+
+    for dir in possible_dirs {
+        let path = format!("{}/{}", dir, file_name);
+        if Path::new(&path).exists() {
+            println!("Found haarcascade at: {}", path);
+            return Some(path);
         }
+    }
+
+    None
+}
+
+pub(crate) fn is_image_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| ext == "jpg" || ext == "jpeg" || ext == "png")
+}
+
+/// Drop the image each face came from
+pub(crate) fn without_sources(users: UserFaceFiles) -> UserFaces {
+    users
+        .into_iter()
+        .map(|(user_id, files)| (user_id, files.into_iter().map(|(_, face)| face).collect()))
+        .collect()
+}
+
+pub(crate) fn labels_path(model_path: &str) -> String {
+    format!("{}.labels.json", model_path)
+}
 
-        Ok(None)
+fn remove_saved_model(model_path: &str) -> Result<()> {
+    for path in [model_path.to_string(), labels_path(model_path)] {
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
     }
 
-    // pub fn is_trained(&self) -> bool {
-    //     self.is_trained
-    // }
+    Ok(())
+}
+
+/// Crop a face and resize it to a `size` x `size` square
+pub(crate) fn crop_face(gray: &Mat, face_rect: Rect, size: i32) -> Result<Mat> {
+    let face_roi = Mat::roi(gray, face_rect)?;
+    
+    // Resize to standard size
+    let mut resized = Mat::default();
+    resize(
+        &face_roi,
+        &mut resized,
+        Size::new(size, size),
+        0.0,
+        0.0,
+        INTER_LINEAR,
+    )?;
+
+    Ok(resized)
+}
+
+pub(crate) fn to_gray(img: &Mat) -> Result<Mat> {
+    if img.channels() == 1 {
+        return Ok(img.try_clone()?);
+    }
+
+    let mut gray = Mat::default();
+    cvt_color(img, &mut gray, COLOR_BGR2GRAY, 0)?;
+
+    Ok(gray)
 }
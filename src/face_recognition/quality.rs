@@ -0,0 +1,173 @@
+use anyhow::Result;
+use opencv::{
+    core::{abs_diff, flip, mean, mean_std_dev, no_array, Mat, Rect, Size, ToInputArray, BORDER_DEFAULT, CV_64F},
+    imgproc::{laplacian, resize, INTER_AREA},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Face crops are compared for symmetry at this size
+const SYMMETRY_SIZE: i32 = 64;
+
+// Mean left/right gray level difference at which a face is treated as fully turned away
+const PROFILE_SYMMETRY_DIFF: f64 = 50.0;
+
+/// Image quality measurements for a single detected face
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FaceQuality {
+    /// Laplacian variance of the face crop, higher is sharper
+    pub sharpness: f64,
+    /// Mean gray level of the face crop (0-255)
+    pub brightness: f64,
+    /// Standard deviation of gray levels in the face crop
+    pub contrast: f64,
+    /// Face width in pixels
+    pub face_width: i32,
+    /// Face area as a fraction of the whole image
+    pub face_ratio: f64,
+    /// Left/right symmetry of the face in 0..1, a rough proxy for a frontal pose
+    pub frontalness: f64,
+}
+
+/// Minimum quality required of probes and enrollment photos
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QualityThresholds {
+    pub min_sharpness: f64,
+    pub min_brightness: f64,
+    pub max_brightness: f64,
+    pub min_contrast: f64,
+    pub min_face_width: i32,
+    pub min_frontalness: f64,
+    /// Refuse to identify probes that fail the thresholds
+    pub reject_probes: bool,
+    /// Refuse to store enrollment photos that fail the thresholds
+    pub reject_enrollment: bool,
+}
+
+impl Default for QualityThresholds {
+    fn default() -> Self {
+        Self {
+            min_sharpness: 50.0,
+            min_brightness: 40.0,
+            max_brightness: 220.0,
+            min_contrast: 20.0,
+            min_face_width: 80,
+            min_frontalness: 0.5,
+            reject_probes: false,
+            reject_enrollment: false,
+        }
+    }
+}
+
+impl QualityThresholds {
+    /// Human-readable reasons the face fails the thresholds, empty if it passes
+    pub fn failures(&self, quality: &FaceQuality) -> Vec<String> {
+        let mut failures = Vec::new();
+
+        if quality.sharpness < self.min_sharpness {
+            failures.push(format!("too blurry (sharpness {:.1} < {:.1})", quality.sharpness, self.min_sharpness));
+        }
+        if quality.brightness < self.min_brightness {
+            failures.push(format!("too dark (brightness {:.1} < {:.1})", quality.brightness, self.min_brightness));
+        }
+        if quality.brightness > self.max_brightness {
+            failures.push(format!("overexposed (brightness {:.1} > {:.1})", quality.brightness, self.max_brightness));
+        }
+        if quality.contrast < self.min_contrast {
+            failures.push(format!("low contrast ({:.1} < {:.1})", quality.contrast, self.min_contrast));
+        }
+        if quality.face_width < self.min_face_width {
+            failures.push(format!("face too small ({}px < {}px)", quality.face_width, self.min_face_width));
+        }
+        if quality.frontalness < self.min_frontalness {
+            failures.push(format!("not frontal (frontalness {:.2} < {:.2})", quality.frontalness, self.min_frontalness));
+        }
+
+        failures
+    }
+
+    /// Reasons a probe is not identified: its failures when `reject_probes` is set, else none
+    pub fn probe_failures(&self, quality: &FaceQuality) -> Vec<String> {
+        if self.reject_probes {
+            self.failures(quality)
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Measure the quality of a face in a grayscale image
+pub fn assess(gray: &Mat, face: Rect) -> Result<FaceQuality> {
+    let crop = Mat::roi(gray, face)?;
+
+    let mut mean_mat = Mat::default();
+    let mut stddev_mat = Mat::default();
+    mean_std_dev(&crop, &mut mean_mat, &mut stddev_mat, &no_array())?;
+
+    let image_area = (gray.cols() as f64) * (gray.rows() as f64);
+
+    Ok(FaceQuality {
+        sharpness: sharpness(&crop)?,
+        brightness: *mean_mat.at_2d::<f64>(0, 0)?,
+        contrast: *stddev_mat.at_2d::<f64>(0, 0)?,
+        face_width: face.width,
+        face_ratio: face.area() as f64 / image_area,
+        frontalness: frontalness(&crop)?,
+    })
+}
+
+/// Variance of the Laplacian, a standard focus measure
+pub fn sharpness(gray_face: &impl ToInputArray) -> Result<f64> {
+    let mut lap = Mat::default();
+    laplacian(gray_face, &mut lap, CV_64F, 1, 1.0, 0.0, BORDER_DEFAULT)?;
+
+    let mut mean_mat = Mat::default();
+    let mut stddev = Mat::default();
+    mean_std_dev(&lap, &mut mean_mat, &mut stddev, &no_array())?;
+    let stddev = *stddev.at_2d::<f64>(0, 0)?;
+
+    Ok(stddev * stddev)
+}
+
+// Compares the face with its mirror image; turned heads are far less symmetric
+fn frontalness(gray_face: &impl ToInputArray) -> Result<f64> {
+    let mut small = Mat::default();
+    resize(gray_face, &mut small, Size::new(SYMMETRY_SIZE, SYMMETRY_SIZE), 0.0, 0.0, INTER_AREA)?;
+    let mut mirrored = Mat::default();
+    flip(&small, &mut mirrored, 1)?;
+    let mut diff = Mat::default();
+    abs_diff(&small, &mirrored, &mut diff)?;
+
+    let mean_diff = mean(&diff, &no_array())?[0];
+
+    Ok((1.0 - mean_diff / PROFILE_SYMMETRY_DIFF).clamp(0.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quality(sharpness: f64) -> FaceQuality {
+        FaceQuality {
+            sharpness,
+            brightness: 120.0,
+            contrast: 40.0,
+            face_width: 120,
+            face_ratio: 0.2,
+            frontalness: 0.9,
+        }
+    }
+
+    #[test]
+    fn probes_are_only_refused_when_configured() {
+        let mut thresholds = QualityThresholds::default();
+        assert_eq!(thresholds.failures(&quality(10.0)).len(), 1);
+        assert!(thresholds.probe_failures(&quality(10.0)).is_empty());
+
+        thresholds.reject_probes = true;
+        assert_eq!(thresholds.probe_failures(&quality(10.0)), thresholds.failures(&quality(10.0)));
+        assert!(thresholds.probe_failures(&quality(80.0)).is_empty());
+    }
+}
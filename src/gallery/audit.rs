@@ -0,0 +1,334 @@
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::face_recognition::model::UserFaceFiles;
+use crate::unknowns::features::{distance, face_descriptor};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GalleryAuditConfig {
+    /// Identity pairs closer than this (mean chi-square per LBP cell) are reported as possible duplicates
+    pub duplicate_distance: f64,
+    /// An image is misfiled when another identity is closer than its own by more than this
+    pub misfile_margin: f64,
+}
+
+impl Default for GalleryAuditConfig {
+    fn default() -> Self {
+        Self {
+            duplicate_distance: 0.2,
+            misfile_margin: 0.0,
+        }
+    }
+}
+
+/// An enrollment image that looks more like another identity than its own
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MisfiledImage {
+    /// Identity the image is enrolled under
+    user_id: String,
+    image_path: String,
+    /// Distance to the closest other image of the same identity
+    own_distance: f64,
+    /// Identity the image is closest to
+    closest_user_id: String,
+    closest_distance: f64,
+}
+
+/// Two identities whose images are suspiciously similar
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SimilarIdentities {
+    user_a: String,
+    user_b: String,
+    /// Mean distance from each image to the closest image of the other identity
+    distance: f64,
+    /// Mean distance between images of the same identity, for comparison; null if both
+    /// identities have a single image
+    intra_distance: Option<f64>,
+}
+
+/// Result of scoring every enrollment image against every identity
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct GalleryAuditReport {
+    identities: usize,
+    images: usize,
+    /// Images in which no face could be detected
+    failed_images: usize,
+    pub misfiled: Vec<MisfiledImage>,
+    pub similar_identities: Vec<SimilarIdentities>,
+    generated_at: String,
+}
+
+struct Identity {
+    user_id: String,
+    images: Vec<(String, Vec<f64>)>,
+}
+
+/// Score every image against all identities and flag misfiled images and likely duplicates
+pub fn audit(users: UserFaceFiles, failed_images: usize, config: &GalleryAuditConfig) -> Result<GalleryAuditReport> {
+    let mut identities = Vec::with_capacity(users.len());
+    for (user_id, files) in users {
+        let mut images = Vec::with_capacity(files.len());
+        for (path, face) in files {
+            images.push((path, face_descriptor(&face)?));
+        }
+        identities.push(Identity { user_id, images });
+    }
+
+    Ok(score(identities, failed_images, config))
+}
+
+// Flag misfiled images and likely duplicates from the face descriptor of every image
+fn score(identities: Vec<Identity>, failed_images: usize, config: &GalleryAuditConfig) -> GalleryAuditReport {
+    // nearest[i][j][k]: distance from image k of identity i to the closest image of identity j,
+    // excluding the image itself
+    let nearest: Vec<Vec<Vec<Option<f64>>>> = identities
+        .iter()
+        .enumerate()
+        .map(|(i, identity)| {
+            identities
+                .iter()
+                .enumerate()
+                .map(|(j, other)| {
+                    identity
+                        .images
+                        .iter()
+                        .enumerate()
+                        .map(|(k, (_, descriptor))| {
+                            other
+                                .images
+                                .iter()
+                                .enumerate()
+                                .filter(|(l, _)| i != j || *l != k)
+                                .map(|(_, (_, candidate))| distance(descriptor, candidate))
+                                .min_by(f64::total_cmp)
+                        })
+                        .collect()
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut misfiled = Vec::new();
+    for (i, identity) in identities.iter().enumerate() {
+        for (k, (image_path, _)) in identity.images.iter().enumerate() {
+            // A single image has nothing of its own to compare against
+            let Some(own_distance) = nearest[i][i][k] else {
+                continue;
+            };
+
+            let closest = (0..identities.len())
+                .filter(|&j| j != i)
+                .filter_map(|j| nearest[i][j][k].map(|d| (j, d)))
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+
+            if let Some((j, closest_distance)) = closest
+                && closest_distance + config.misfile_margin < own_distance
+            {
+                misfiled.push(MisfiledImage {
+                    user_id: identity.user_id.clone(),
+                    image_path: image_path.clone(),
+                    own_distance,
+                    closest_user_id: identities[j].user_id.clone(),
+                    closest_distance,
+                });
+            }
+        }
+    }
+
+    let mean = |values: Vec<f64>| (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64);
+    let intra: Vec<Option<f64>> = (0..identities.len())
+        .map(|i| mean(nearest[i][i].iter().flatten().copied().collect()))
+        .collect();
+
+    let mut similar_identities = Vec::new();
+    for a in 0..identities.len() {
+        for b in a + 1..identities.len() {
+            let cross: Vec<f64> = nearest[a][b].iter().chain(&nearest[b][a]).flatten().copied().collect();
+            let Some(distance) = mean(cross) else {
+                continue;
+            };
+
+            if distance < config.duplicate_distance {
+                similar_identities.push(SimilarIdentities {
+                    user_a: identities[a].user_id.clone(),
+                    user_b: identities[b].user_id.clone(),
+                    distance,
+                    intra_distance: mean(intra[a].into_iter().chain(intra[b]).collect()),
+                });
+            }
+        }
+    }
+
+    misfiled.sort_by(|a, b| (a.closest_distance - a.own_distance).total_cmp(&(b.closest_distance - b.own_distance)));
+    similar_identities.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+
+    GalleryAuditReport {
+        identities: identities.len(),
+        images: identities.iter().map(|identity| identity.images.len()).sum(),
+        failed_images,
+        misfiled,
+        similar_identities,
+        generated_at: Utc::now().to_rfc3339(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opencv::core::{Mat, Scalar, CV_8UC1};
+    use opencv::prelude::*;
+
+    const A1: [f64; 4] = [1.0, 0.0, 0.0, 0.0];
+    const A2: [f64; 4] = [0.9, 0.1, 0.0, 0.0];
+    const B1: [f64; 4] = [0.0, 0.0, 1.0, 0.0];
+    const B2: [f64; 4] = [0.0, 0.0, 0.99, 0.01];
+    // Filed under alice but much closer to bob
+    const STRAY: [f64; 4] = [0.0, 0.0, 0.8, 0.2];
+    const C1: [f64; 4] = [0.0, 1.0, 0.0, 0.0];
+
+    fn identity(user_id: &str, descriptors: &[[f64; 4]]) -> Identity {
+        Identity {
+            user_id: user_id.to_string(),
+            images: descriptors
+                .iter()
+                .enumerate()
+                .map(|(k, descriptor)| (format!("knowledge/{}/{}.jpg", user_id, k), descriptor.to_vec()))
+                .collect(),
+        }
+    }
+
+    fn gallery() -> Vec<Identity> {
+        vec![
+            identity("alice", &[A1, A2, STRAY]),
+            identity("bob", &[B1, B2]),
+            identity("carol", &[C1]),
+        ]
+    }
+
+    fn misfiled(report: &GalleryAuditReport) -> Vec<(&str, &str, &str)> {
+        report
+            .misfiled
+            .iter()
+            .map(|image| (image.user_id.as_str(), image.image_path.as_str(), image.closest_user_id.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn flags_images_closer_to_another_identity() {
+        let report = score(gallery(), 1, &GalleryAuditConfig::default());
+
+        assert_eq!((report.identities, report.images, report.failed_images), (3, 6, 1));
+        assert_eq!(misfiled(&report), [("alice", "knowledge/alice/2.jpg", "bob")]);
+        let stray = &report.misfiled[0];
+        assert_eq!(stray.own_distance, distance(&STRAY, &A2));
+        assert_eq!(stray.closest_distance, distance(&STRAY, &B2));
+        assert!(report.similar_identities.is_empty());
+    }
+
+    #[test]
+    fn misfiling_must_beat_the_margin() {
+        let gap = distance(&STRAY, &A2) - distance(&STRAY, &B2);
+        let report = |misfile_margin| {
+            let config = GalleryAuditConfig {
+                misfile_margin,
+                ..GalleryAuditConfig::default()
+            };
+            score(gallery(), 0, &config).misfiled.len()
+        };
+
+        assert_eq!(report(gap - 0.01), 1);
+        assert_eq!(report(gap), 0);
+        assert_eq!(report(gap + 0.01), 0);
+    }
+
+    #[test]
+    fn images_are_not_compared_with_themselves() {
+        // Identical images within an identity are each other's nearest, at distance 0; a
+        // single image has nothing of its own and is never misfiled
+        let report = score(
+            vec![identity("eve", &[B1, B1]), identity("frank", &[B1]), identity("carol", &[C1])],
+            0,
+            &GalleryAuditConfig::default(),
+        );
+
+        assert!(report.misfiled.is_empty());
+        assert_eq!(report.similar_identities.len(), 1);
+        let pair = &report.similar_identities[0];
+        assert_eq!((pair.user_a.as_str(), pair.user_b.as_str()), ("eve", "frank"));
+        assert_eq!((pair.distance, pair.intra_distance), (0.0, Some(0.0)));
+
+        // Two single-image identities have no intra distance to compare with
+        let report = score(vec![identity("frank", &[B1]), identity("grace", &[B2])], 0, &GalleryAuditConfig::default());
+        assert!(report.misfiled.is_empty());
+        assert_eq!(report.similar_identities[0].intra_distance, None);
+    }
+
+    #[test]
+    fn reports_identities_that_look_alike() {
+        let mut identities = gallery();
+        identities.push(identity("dave", &[[0.0, 0.5, 0.5, 0.0], [0.0, 0.5, 0.45, 0.05]]));
+        identities.push(identity("david", &[[0.0, 0.5, 0.48, 0.02]]));
+
+        let report = score(identities, 0, &GalleryAuditConfig::default());
+
+        let pairs: Vec<(&str, &str)> = report
+            .similar_identities
+            .iter()
+            .map(|pair| (pair.user_a.as_str(), pair.user_b.as_str()))
+            .collect();
+        assert_eq!(pairs, [("dave", "david")]);
+        let pair = &report.similar_identities[0];
+        let (d1, d2, e1) = ([0.0, 0.5, 0.5, 0.0], [0.0, 0.5, 0.45, 0.05], [0.0, 0.5, 0.48, 0.02]);
+        let nearest_to_e1 = distance(&e1, &d1).min(distance(&e1, &d2));
+        let expected = (distance(&d1, &e1) + distance(&d2, &e1) + nearest_to_e1) / 3.0;
+        assert!((pair.distance - expected).abs() < 1e-12);
+        assert_eq!(pair.intra_distance, Some(distance(&d1, &d2)));
+
+        let strict = GalleryAuditConfig {
+            duplicate_distance: pair.distance,
+            ..GalleryAuditConfig::default()
+        };
+        assert!(score(gallery(), 0, &strict).similar_identities.is_empty());
+    }
+
+    // 64x64 grayscale crop of stripes `period` pixels wide, horizontal or vertical; `phase`
+    // shifts them so no two crops are identical
+    fn stripes(vertical: bool, period: usize, phase: usize) -> Mat {
+        let mut crop = Mat::new_rows_cols_with_default(64, 64, CV_8UC1, Scalar::all(0.0)).unwrap();
+        for (i, value) in crop.data_typed_mut::<u8>().unwrap().iter_mut().enumerate() {
+            let position = if vertical { i % 64 } else { i / 64 } + phase;
+            *value = if (position / period).is_multiple_of(2) { 40 } else { 200 };
+        }
+        crop
+    }
+
+    // Descriptors are computed from the crops, so this needs OpenCV
+    #[test]
+    fn audits_synthetic_crops() {
+        // Bob's two photos are the same crop, so neither can be closer to alice's stray one
+        let users: UserFaceFiles = vec![
+            (
+                "alice".to_string(),
+                vec![
+                    ("alice/0.jpg".to_string(), stripes(false, 4, 0)),
+                    ("alice/1.jpg".to_string(), stripes(false, 4, 1)),
+                    ("alice/2.jpg".to_string(), stripes(true, 8, 3)),
+                ],
+            ),
+            (
+                "bob".to_string(),
+                vec![
+                    ("bob/0.jpg".to_string(), stripes(true, 8, 0)),
+                    ("bob/1.jpg".to_string(), stripes(true, 8, 0)),
+                ],
+            ),
+        ];
+
+        let report = audit(users, 0, &GalleryAuditConfig::default()).unwrap();
+
+        assert_eq!(misfiled(&report), [("alice", "alice/2.jpg", "bob")]);
+    }
+}
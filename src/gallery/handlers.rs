@@ -0,0 +1,163 @@
+use axum::{
+    extract::{Extension, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+
+use super::audit::{audit, GalleryAuditReport};
+use super::reconcile::{reconcile, OrphanAction, ReconcileFix, ReconcileReport};
+use crate::audit::AuditContext;
+use crate::config::SharedConfig;
+use crate::face_recognition::handlers::{tenant_model, ApiResponse, SharedDb, SharedModel};
+use crate::images::{Gallery, SharedImageStore};
+use crate::tenants::{registry::SharedRegistry, TenantPaths, DEFAULT_TENANT};
+
+/// Query parameters for gallery reconciliation
+#[derive(Debug, Deserialize)]
+pub struct ReconcileQuery {
+    /// Tenant to reconcile (default `default`)
+    tenant: Option<String>,
+    /// What to do with orphaned images when fixing (default `adopt`)
+    orphans: Option<OrphanAction>,
+    /// Also delete the images of users without a detectable face when fixing
+    #[serde(default)]
+    delete_images: bool,
+}
+
+/// Audit the enrollment gallery
+///
+/// Scores every enrolled image against all identities. Reports images that are
+/// closer to another identity than to their own (likely filed under the wrong user) and pairs
+/// of identities that are suspiciously similar (likely the same person enrolled twice).
+#[utoipa::path(
+    get,
+    path = "/admin/gallery-audit",
+    responses(
+        (status = 200, description = "Audit report", body = ApiResponse<GalleryAuditReport>),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Admin"
+)]
+pub async fn gallery_audit(
+    State((model, db)): State<(SharedModel, SharedDb)>,
+    Extension(config): Extension<SharedConfig>,
+    Extension(images): Extension<SharedImageStore>,
+) -> Result<Json<ApiResponse<GalleryAuditReport>>, (StatusCode, String)> {
+    let gallery = Gallery::load(&db, images.as_ref(), DEFAULT_TENANT)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let report = tokio::task::spawn_blocking(move || {
+        // Only face detection needs the model; scoring runs without holding it
+        let (users, failed_images) = model.blocking_lock().gallery_faces(&gallery);
+        audit(users, failed_images + gallery.unreadable.len(), &config.gallery_audit)
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Gallery audit failed: {}", e)))?;
+
+    let flagged = report.misfiled.len() + report.similar_identities.len();
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: if flagged == 0 {
+            "No problems found in the gallery".to_string()
+        } else {
+            format!("Found {} possible problems in the gallery", flagged)
+        },
+        data: Some(report),
+    }))
+}
+
+/// Compare the gallery with the image store
+///
+/// Reports images in the store that no `face_images` row lists (for example copied in by
+/// hand), rows whose image is gone, images that cannot be read, and users without a single
+/// image in which a face can be detected. Nothing is changed; `POST` fixes the problems.
+#[utoipa::path(
+    get,
+    path = "/admin/reconcile",
+    params(("tenant" = Option<String>, Query, description = "Tenant ID (default `default`)")),
+    responses(
+        (status = 200, description = "Reconciliation report", body = ApiResponse<ReconcileReport>),
+        (status = 400, description = "Invalid tenant"),
+        (status = 404, description = "Tenant not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Admin"
+)]
+pub async fn check_gallery(
+    Extension(config): Extension<SharedConfig>,
+    Extension(registry): Extension<SharedRegistry>,
+    Extension(images): Extension<SharedImageStore>,
+    Query(query): Query<ReconcileQuery>,
+) -> Result<Json<ApiResponse<ReconcileReport>>, (StatusCode, String)> {
+    run_reconcile(&config, &registry, &images, query, false).await
+}
+
+/// Reconcile the gallery with the image store
+///
+/// Same as `GET /admin/reconcile`, but fixes what it finds: rows of missing images are
+/// deleted, orphaned images are enrolled for the user folder they are in (`orphans=adopt`)
+/// or deleted (`orphans=delete`), users without usable images are deleted (with their images
+/// only when `delete_images=true`), and the model is retrained. Fails without changing
+/// anything while any image in the store cannot be read.
+#[utoipa::path(
+    post,
+    path = "/admin/reconcile",
+    params(
+        ("tenant" = Option<String>, Query, description = "Tenant ID (default `default`)"),
+        ("orphans" = Option<OrphanAction>, Query, description = "adopt (default) or delete orphaned images"),
+        ("delete_images" = Option<bool>, Query, description = "Also delete the images of users without a detectable face")
+    ),
+    responses(
+        (status = 200, description = "Reconciliation report", body = ApiResponse<ReconcileReport>),
+        (status = 400, description = "Invalid tenant"),
+        (status = 404, description = "Tenant not found"),
+        (status = 500, description = "Unreadable images or internal server error")
+    ),
+    tag = "Admin"
+)]
+pub async fn fix_gallery(
+    Extension(config): Extension<SharedConfig>,
+    Extension(registry): Extension<SharedRegistry>,
+    Extension(images): Extension<SharedImageStore>,
+    Extension(audit): Extension<AuditContext>,
+    Query(query): Query<ReconcileQuery>,
+) -> Result<Json<ApiResponse<ReconcileReport>>, (StatusCode, String)> {
+    audit.target(query.tenant.as_deref().unwrap_or(DEFAULT_TENANT));
+    run_reconcile(&config, &registry, &images, query, true).await
+}
+
+async fn run_reconcile(
+    config: &SharedConfig,
+    registry: &SharedRegistry,
+    images: &SharedImageStore,
+    query: ReconcileQuery,
+    fix: bool,
+) -> Result<Json<ApiResponse<ReconcileReport>>, (StatusCode, String)> {
+    let tenant = query.tenant.unwrap_or_else(|| DEFAULT_TENANT.to_string());
+    let model = tenant_model(registry, &tenant).await?;
+    let paths = TenantPaths::new(config, &tenant);
+    let fix = fix.then(|| ReconcileFix {
+        orphans: query.orphans.unwrap_or_default(),
+        delete_images: query.delete_images,
+    });
+
+    let mut model_guard = model.lock().await;
+    let report = reconcile(registry.db(), images.as_ref(), &mut model_guard, &paths, &tenant, fix)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Reconciliation failed: {}", e)))?;
+
+    let problems = report.problems();
+    Ok(Json(ApiResponse {
+        success: true,
+        message: match (problems, fix) {
+            (0, _) => "Gallery and image store agree".to_string(),
+            (_, None) => format!("Found {} problems in the gallery", problems),
+            (_, Some(_)) => format!("Fixed {} problems in the gallery", problems),
+        },
+        data: Some(report),
+    }))
+}
@@ -0,0 +1,3 @@
+pub mod audit;
+pub mod handlers;
+pub mod reconcile;
@@ -0,0 +1,426 @@
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+use crate::database::Database;
+use crate::face_recognition::augment::TrainingStats;
+use crate::face_recognition::model::is_image_file;
+use crate::face_recognition::FaceRecognitionModel;
+use crate::images::{Gallery, ImageStore};
+use crate::tenants::TenantPaths;
+
+/// What to do with images in the store that no `face_images` row lists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OrphanAction {
+    /// Enroll images found under `{knowledge_dir}/{user_id}/` for that user
+    #[default]
+    Adopt,
+    Delete,
+}
+
+impl FromStr for OrphanAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "adopt" => Ok(Self::Adopt),
+            "delete" => Ok(Self::Delete),
+            other => Err(anyhow::anyhow!("Unknown orphan action '{}'. Use adopt or delete", other)),
+        }
+    }
+}
+
+/// How `reconcile` fixes what it finds
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReconcileFix {
+    pub orphans: OrphanAction,
+    /// Also delete the stored images of users without a detectable face, not only their rows
+    pub delete_images: bool,
+}
+
+/// A `face_images` row whose image is not in the image store
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MissingImage {
+    pub user_id: String,
+    pub image_path: String,
+}
+
+/// Differences between a tenant's `face_images` rows and its image store
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReconcileReport {
+    tenant: String,
+    /// Images in the store that no `face_images` row lists
+    pub orphaned_files: Vec<String>,
+    /// Rows whose image is gone from the store
+    pub missing_files: Vec<MissingImage>,
+    /// Images in the store that could not be read, for example during an outage or when
+    /// encrypted with a key that is no longer configured. Nothing is fixed while there are any.
+    pub unreadable_files: Vec<String>,
+    /// Users whose images were all read and none has a detectable face; when fixing with
+    /// adopted orphans, judged after adopting them
+    pub users_without_images: Vec<String>,
+    /// Whether the problems above were fixed
+    fixed: bool,
+    /// Summary of the retraining after fixing, if anything changed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub training: Option<TrainingStats>,
+    generated_at: String,
+}
+
+impl ReconcileReport {
+    pub fn problems(&self) -> usize {
+        self.orphaned_files.len()
+            + self.missing_files.len()
+            + self.unreadable_files.len()
+            + self.users_without_images.len()
+    }
+}
+
+/// Compare a tenant's `face_images` rows with its image store. With `fix`, rows of missing
+/// images are deleted, orphaned images are adopted or deleted, users without usable images
+/// are deleted (their images too only with `delete_images`) and the model is retrained.
+/// Orphans outside a user folder are never adopted. Fixing fails without changing anything
+/// when an image in the store cannot be read, since its user cannot be judged.
+pub async fn reconcile(
+    db: &Database,
+    images: &dyn ImageStore,
+    model: &mut FaceRecognitionModel,
+    paths: &TenantPaths,
+    tenant: &str,
+    fix: Option<ReconcileFix>,
+) -> Result<ReconcileReport> {
+    let rows = db.get_face_images(tenant).await?;
+    let stored: HashSet<String> = images
+        .list(&paths.knowledge_dir)
+        .await?
+        .into_iter()
+        .filter(|key| is_image_file(Path::new(key)))
+        .collect();
+    let listed: HashSet<&String> = rows.iter().map(|(_, image_path)| image_path).collect();
+
+    let missing_files: Vec<MissingImage> = rows
+        .iter()
+        .filter(|(_, image_path)| !stored.contains(image_path))
+        .map(|(user_id, image_path)| MissingImage {
+            user_id: user_id.clone(),
+            image_path: image_path.clone(),
+        })
+        .collect();
+    let mut orphaned_files: Vec<String> = stored.iter().filter(|key| !listed.contains(key)).cloned().collect();
+    orphaned_files.sort();
+
+    let gallery = Gallery::load(db, images, tenant).await?;
+    let missing: HashSet<&String> = missing_files.iter().map(|missing| &missing.image_path).collect();
+    let unreadable_files: Vec<String> =
+        gallery.unreadable.iter().filter(|key| !missing.contains(key)).cloned().collect();
+    let mut users_without_images = users_without_faces(db, model, &gallery, &unreadable_files, tenant).await?;
+
+    let mut training = None;
+    if let Some(fix) = fix {
+        if !unreadable_files.is_empty() {
+            return Err(anyhow::anyhow!(
+                "{} images could not be read from the {} store; nothing was changed",
+                unreadable_files.len(),
+                images.name()
+            ));
+        }
+
+        for missing in &missing_files {
+            db.delete_face_image(tenant, &missing.image_path).await?;
+        }
+
+        let mut adopted = false;
+        for key in &orphaned_files {
+            match fix.orphans {
+                OrphanAction::Adopt => {
+                    if let Some(user_id) = orphan_owner(paths, key) {
+                        db.upsert_user(tenant, user_id).await?;
+                        db.insert_face_image(tenant, user_id, key).await?;
+                        adopted = true;
+                    }
+                }
+                OrphanAction::Delete => images.delete(key).await?,
+            }
+        }
+
+        // Adopted images can give a user a usable face, or enroll a user whose images have none
+        if adopted {
+            let gallery = Gallery::load(db, images, tenant).await?;
+            users_without_images = users_without_faces(db, model, &gallery, &gallery.unreadable, tenant).await?;
+        }
+
+        for user_id in &users_without_images {
+            if fix.delete_images {
+                for image_path in db.get_user_images(tenant, user_id).await? {
+                    images.delete(&image_path).await?;
+                }
+            }
+            db.delete_user(tenant, user_id).await?;
+        }
+
+        let changed = !missing_files.is_empty() || !orphaned_files.is_empty() || !users_without_images.is_empty();
+        if changed {
+            training = Some(model.train(&Gallery::load(db, images, tenant).await?)?);
+        }
+    }
+
+    Ok(ReconcileReport {
+        tenant: tenant.to_string(),
+        orphaned_files,
+        missing_files,
+        unreadable_files,
+        users_without_images,
+        fixed: fix.is_some(),
+        training,
+        generated_at: Utc::now().to_rfc3339(),
+    })
+}
+
+// Users whose every image fails face detection are as good as not enrolled; a user with an
+// image in `unreadable` is not judged
+async fn users_without_faces(
+    db: &Database,
+    model: &mut FaceRecognitionModel,
+    gallery: &Gallery,
+    unreadable: &[String],
+    tenant: &str,
+) -> Result<Vec<String>> {
+    let rows = db.get_face_images(tenant).await?;
+    let unjudged: HashSet<&String> = rows
+        .iter()
+        .filter(|(_, image_path)| unreadable.contains(image_path))
+        .map(|(user_id, _)| user_id)
+        .collect();
+    let (users, _) = model.gallery_faces(gallery);
+    let usable: HashSet<&String> = users.iter().map(|(user_id, _)| user_id).collect();
+
+    let mut users_without_images: Vec<String> = db
+        .get_all_users(tenant)
+        .await?
+        .into_iter()
+        .filter(|user_id| !usable.contains(user_id) && !unjudged.contains(user_id))
+        .collect();
+    users_without_images.sort();
+
+    Ok(users_without_images)
+}
+
+// The user an orphan at `{knowledge_dir}/{user_id}/{file}` belongs to
+fn orphan_owner<'a>(paths: &TenantPaths, key: &'a str) -> Option<&'a str> {
+    let relative = key.strip_prefix(paths.knowledge_dir.as_str())?.strip_prefix('/')?;
+    let (user_id, file_name) = relative.split_once('/')?;
+
+    (!user_id.is_empty() && !user_id.starts_with('.') && !file_name.contains('/')).then_some(user_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::images::local::FsImageStore;
+    use crate::tenants::DEFAULT_TENANT;
+    use async_trait::async_trait;
+    use uuid::Uuid;
+
+    // The file store, except that keys containing `locked` cannot be read
+    struct LockedStore;
+
+    #[async_trait]
+    impl ImageStore for LockedStore {
+        fn name(&self) -> &'static str {
+            "locked"
+        }
+
+        async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+            FsImageStore.put(key, data).await
+        }
+
+        async fn get(&self, key: &str) -> Result<Vec<u8>> {
+            if key.contains("locked") {
+                return Err(anyhow::anyhow!("key is no longer configured"));
+            }
+            FsImageStore.get(key).await
+        }
+
+        async fn delete(&self, key: &str) -> Result<()> {
+            FsImageStore.delete(key).await
+        }
+
+        async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+            FsImageStore.list(prefix).await
+        }
+    }
+
+    struct Setup {
+        dir: String,
+        db: Database,
+        model: FaceRecognitionModel,
+        paths: TenantPaths,
+    }
+
+    // Face detection needs OpenCV and its haarcascade
+    async fn setup() -> Setup {
+        let dir = std::env::temp_dir().join(format!("reconcile-{}", Uuid::new_v4()));
+        let dir = dir.to_string_lossy().into_owned();
+        let mut config = Config::default();
+        config.paths.knowledge_dir = format!("{}/knowledge", dir);
+        config.paths.tenants_dir = format!("{}/tenants", dir);
+        config.paths.model_path = format!("{}/models/lbph.yml", dir);
+        config.paths.temp_dir = format!("{}/temp", dir);
+        config.database.url = "sqlite::memory:".to_string();
+        config.database.max_connections = 1;
+
+        let db = Database::new(&config.database).await.unwrap();
+        let paths = TenantPaths::new(&config, DEFAULT_TENANT);
+        let model = FaceRecognitionModel::with_model_path(&config, &paths.model_path).unwrap();
+
+        Setup { dir, db, model, paths }
+    }
+
+    fn photo(file: &str) -> Vec<u8> {
+        std::fs::read(format!("{}/knowledge/{}", env!("CARGO_MANIFEST_DIR"), file)).unwrap()
+    }
+
+    async fn enrolled(setup: &Setup, images: &dyn ImageStore, user_id: &str, file_name: &str, data: &[u8]) -> String {
+        let key = setup.paths.image_key(user_id, file_name);
+        images.put(&key, data).await.unwrap();
+        setup.db.upsert_user(DEFAULT_TENANT, user_id).await.unwrap();
+        setup.db.insert_face_image(DEFAULT_TENANT, user_id, &key).await.unwrap();
+        key
+    }
+
+    async fn orphan(setup: &Setup, images: &dyn ImageStore, user_id: &str, file_name: &str, data: &[u8]) -> String {
+        let key = setup.paths.image_key(user_id, file_name);
+        images.put(&key, data).await.unwrap();
+        key
+    }
+
+    async fn rows(db: &Database) -> Vec<(String, String)> {
+        let mut rows = db.get_face_images(DEFAULT_TENANT).await.unwrap();
+        rows.sort();
+        rows
+    }
+
+    #[tokio::test]
+    async fn adopts_orphans_before_judging_users() {
+        let mut setup = setup().await;
+        let images = FsImageStore;
+        let nasri = enrolled(&setup, &images, "nasri", "1.jpg", &photo("nasri/photo_1.jpg")).await;
+        let missing = setup.paths.image_key("nasri", "missing.jpg");
+        setup.db.insert_face_image(DEFAULT_TENANT, "nasri", &missing).await.unwrap();
+        let nasri_orphan = orphan(&setup, &images, "nasri", "2.jpg", &photo("nasri/photo_2.jpg")).await;
+        // Elon is enrolled but only has a photo no row lists
+        setup.db.upsert_user(DEFAULT_TENANT, "elon").await.unwrap();
+        let elon = orphan(&setup, &images, "elon", "1.jpg", &photo("elon01/Elon Musk Colorado 2022.jpg")).await;
+        let ghost = enrolled(&setup, &images, "ghost", "1.jpg", b"not an image").await;
+        let stray = format!("{}/stray.jpg", setup.paths.knowledge_dir);
+        images.put(&stray, &photo("nasri/photo_3.jpg")).await.unwrap();
+
+        let report = reconcile(&setup.db, &images, &mut setup.model, &setup.paths, DEFAULT_TENANT, None)
+            .await
+            .unwrap();
+        assert_eq!(report.missing_files.len(), 1);
+        assert_eq!(report.missing_files[0].image_path, missing);
+        assert_eq!(report.orphaned_files, [elon.clone(), nasri_orphan.clone(), stray.clone()]);
+        assert!(report.unreadable_files.is_empty());
+        assert_eq!(report.users_without_images, ["elon", "ghost"]);
+        assert_eq!(rows(&setup.db).await.len(), 3);
+
+        let fix = ReconcileFix {
+            orphans: OrphanAction::Adopt,
+            delete_images: true,
+        };
+        let report = reconcile(&setup.db, &images, &mut setup.model, &setup.paths, DEFAULT_TENANT, Some(fix))
+            .await
+            .unwrap();
+        assert_eq!(report.users_without_images, ["ghost"]);
+        assert_eq!(report.training.unwrap().users, 2);
+
+        assert_eq!(setup.db.get_all_users(DEFAULT_TENANT).await.unwrap(), ["elon", "nasri"]);
+        assert_eq!(
+            rows(&setup.db).await,
+            [
+                ("elon".to_string(), elon.clone()),
+                ("nasri".to_string(), nasri.clone()),
+                ("nasri".to_string(), nasri_orphan.clone()),
+            ]
+        );
+        for key in [&elon, &nasri, &nasri_orphan, &stray] {
+            assert!(images.get(key).await.is_ok(), "{}", key);
+        }
+        assert!(images.get(&ghost).await.is_err());
+
+        // Only the orphan outside a user folder is left
+        let report = reconcile(&setup.db, &images, &mut setup.model, &setup.paths, DEFAULT_TENANT, None)
+            .await
+            .unwrap();
+        assert_eq!(report.problems(), 1);
+        assert_eq!(report.orphaned_files, [stray]);
+
+        std::fs::remove_dir_all(&setup.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn deletes_orphans_and_users_left_without_images() {
+        let mut setup = setup().await;
+        let images = FsImageStore;
+        let nasri = enrolled(&setup, &images, "nasri", "1.jpg", &photo("nasri/photo_1.jpg")).await;
+        let nasri_orphan = orphan(&setup, &images, "nasri", "2.jpg", &photo("nasri/photo_2.jpg")).await;
+        setup.db.upsert_user(DEFAULT_TENANT, "elon").await.unwrap();
+        let elon = orphan(&setup, &images, "elon", "1.jpg", &photo("elon01/Elon Musk Colorado 2022.jpg")).await;
+        let ghost = enrolled(&setup, &images, "ghost", "1.jpg", b"not an image").await;
+
+        let fix = ReconcileFix {
+            orphans: OrphanAction::Delete,
+            delete_images: false,
+        };
+        let report = reconcile(&setup.db, &images, &mut setup.model, &setup.paths, DEFAULT_TENANT, Some(fix))
+            .await
+            .unwrap();
+        assert_eq!(report.orphaned_files, [elon.clone(), nasri_orphan.clone()]);
+        assert_eq!(report.users_without_images, ["elon", "ghost"]);
+
+        assert_eq!(setup.db.get_all_users(DEFAULT_TENANT).await.unwrap(), ["nasri"]);
+        assert_eq!(rows(&setup.db).await, [("nasri".to_string(), nasri.clone())]);
+        assert!(images.get(&nasri).await.is_ok());
+        assert!(images.get(&elon).await.is_err());
+        assert!(images.get(&nasri_orphan).await.is_err());
+        // Without delete_images the image of a deleted user stays in the store
+        assert!(images.get(&ghost).await.is_ok());
+
+        std::fs::remove_dir_all(&setup.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn refuses_to_fix_while_an_image_cannot_be_read() {
+        let mut setup = setup().await;
+        let images = LockedStore;
+        enrolled(&setup, &images, "nasri", "1.jpg", &photo("nasri/photo_1.jpg")).await;
+        let locked = enrolled(&setup, &images, "elon", "locked.jpg", &photo("elon01/Elon Musk Colorado 2022.jpg")).await;
+        let nasri_orphan = orphan(&setup, &images, "nasri", "2.jpg", &photo("nasri/photo_2.jpg")).await;
+
+        let report = reconcile(&setup.db, &images, &mut setup.model, &setup.paths, DEFAULT_TENANT, None)
+            .await
+            .unwrap();
+        assert_eq!(report.unreadable_files, [locked.as_str()]);
+        // Elon's only image could not be read, so Elon is not judged
+        assert!(report.users_without_images.is_empty());
+
+        let fix = ReconcileFix {
+            orphans: OrphanAction::Delete,
+            delete_images: true,
+        };
+        let result = reconcile(&setup.db, &images, &mut setup.model, &setup.paths, DEFAULT_TENANT, Some(fix)).await;
+        assert!(result.unwrap_err().to_string().contains("nothing was changed"));
+        assert_eq!(rows(&setup.db).await.len(), 2);
+        assert!(images.get(&nasri_orphan).await.is_ok());
+        assert!(std::path::Path::new(&locked).exists());
+
+        std::fs::remove_dir_all(&setup.dir).unwrap();
+    }
+}
@@ -0,0 +1,42 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::ImageStore;
+use crate::face_recognition::handlers::SharedDb;
+
+/// Images as BLOBs in the `image_blobs` table, so the database is all there is to back up
+pub struct DbImageStore {
+    db: SharedDb,
+}
+
+impl DbImageStore {
+    pub fn new(db: SharedDb) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl ImageStore for DbImageStore {
+    fn name(&self) -> &'static str {
+        "database"
+    }
+
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        self.db.put_image_blob(key, data).await
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        self.db
+            .get_image_blob(key)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Image not found: {}", key))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.db.delete_image_blob(key).await
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        self.db.get_image_blob_keys(prefix).await
+    }
+}
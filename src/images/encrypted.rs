@@ -0,0 +1,84 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::{ImageStore, SharedImageStore};
+use crate::encryption::SharedCipher;
+
+/// Encrypts images on the way into another store and decrypts them on the way out. Images
+/// stored before encryption was enabled are returned as they are.
+pub struct EncryptedImageStore {
+    inner: SharedImageStore,
+    cipher: SharedCipher,
+}
+
+impl EncryptedImageStore {
+    pub fn new(inner: SharedImageStore, cipher: SharedCipher) -> Self {
+        Self { inner, cipher }
+    }
+}
+
+#[async_trait]
+impl ImageStore for EncryptedImageStore {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        self.inner.put(key, &self.cipher.encrypt(data)?).await
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        self.cipher.decrypt(&self.inner.get(key).await?)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.inner.delete(key).await
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        self.inner.list(prefix).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::{Cipher, EncryptionConfig};
+    use crate::images::s3::S3ImageStore;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use object_store::memory::InMemory;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn encrypts_into_and_decrypts_out_of_the_inner_store() {
+        let inner: SharedImageStore = Arc::new(S3ImageStore::with_store(Arc::new(InMemory::new())));
+        let cipher = Arc::new(
+            Cipher::open(&EncryptionConfig {
+                key: Some(STANDARD.encode([1u8; 32])),
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        let store = EncryptedImageStore::new(inner.clone(), cipher.clone());
+
+        store.put("knowledge/alice/1.jpg", b"face").await.unwrap();
+        let stored = inner.get("knowledge/alice/1.jpg").await.unwrap();
+        assert!(Cipher::is_encrypted(&stored));
+        assert_eq!(cipher.decrypt(&stored).unwrap(), b"face");
+        assert_eq!(store.get("knowledge/alice/1.jpg").await.unwrap(), b"face");
+
+        // Images stored before encryption was enabled
+        inner.put("knowledge/alice/2.jpg", b"plain").await.unwrap();
+        assert_eq!(store.get("knowledge/alice/2.jpg").await.unwrap(), b"plain");
+
+        assert_eq!(store.list("knowledge/alice").await.unwrap(), ["knowledge/alice/1.jpg", "knowledge/alice/2.jpg"]);
+        store.delete("knowledge/alice/1.jpg").await.unwrap();
+        assert!(inner.get("knowledge/alice/1.jpg").await.is_err());
+
+        // Tampered data fails instead of being returned
+        let mut tampered = stored.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        inner.put("knowledge/alice/3.jpg", &tampered).await.unwrap();
+        assert!(store.get("knowledge/alice/3.jpg").await.is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+use super::ImageStore;
+
+/// Images as plain files; the key is the path relative to the working directory
+pub struct FsImageStore;
+
+#[async_trait]
+impl ImageStore for FsImageStore {
+    fn name(&self) -> &'static str {
+        "fs"
+    }
+
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        if let Some(parent) = Path::new(key).parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(key, data).await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        Ok(fs::read(key).await?)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match fs::remove_file(key).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut dirs = vec![PathBuf::from(prefix)];
+        while let Some(dir) = dirs.pop() {
+            let mut entries = match fs::read_dir(&dir).await {
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                entries => entries?,
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if entry.file_type().await?.is_dir() {
+                    dirs.push(path);
+                } else {
+                    // Keys use `/` on every platform
+                    let relative = path.strip_prefix(prefix)?.to_string_lossy().replace('\\', "/");
+                    keys.push(format!("{}/{}", prefix, relative));
+                }
+            }
+        }
+        keys.sort();
+
+        Ok(keys)
+    }
+}
@@ -0,0 +1,205 @@
+pub mod db;
+pub mod encrypted;
+pub mod local;
+pub mod s3;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::database::Database;
+use crate::encryption::SharedCipher;
+use crate::face_recognition::handlers::SharedDb;
+
+/// Where enrollment images are kept. Keys are the `face_images.image_path` of the image,
+/// `{knowledge_dir}/{user_id}/{file_name}`, whatever the backend.
+#[async_trait]
+pub trait ImageStore: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Store `data` under `key`, replacing an existing image
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()>;
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>>;
+
+    /// Remove the image; removing a missing image is not an error
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// Keys of every image under `{prefix}/`, sorted
+    async fn list(&self, prefix: &str) -> Result<Vec<String>>;
+}
+
+pub type SharedImageStore = Arc<dyn ImageStore>;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImagesConfig {
+    pub backend: ImageBackend,
+    pub s3: S3Config,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageBackend {
+    /// Files under the knowledge directories
+    #[default]
+    Fs,
+    /// BLOBs in the `image_blobs` table of the service database
+    Database,
+    /// An S3-compatible object store
+    S3,
+}
+
+impl FromStr for ImageBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "fs" => Ok(Self::Fs),
+            "database" => Ok(Self::Database),
+            "s3" => Ok(Self::S3),
+            other => Err(anyhow::anyhow!("Unknown image backend '{}'. Use fs, database or s3", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct S3Config {
+    pub bucket: String,
+    pub region: String,
+    /// Endpoint of an S3-compatible service such as MinIO; AWS when unset
+    pub endpoint: Option<String>,
+    /// Credentials; taken from the standard `AWS_*` variables when unset
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    /// Allow a plain `http://` endpoint
+    pub allow_http: bool,
+}
+
+impl Default for S3Config {
+    fn default() -> Self {
+        Self {
+            bucket: String::new(),
+            region: "us-east-1".to_string(),
+            endpoint: None,
+            access_key_id: None,
+            secret_access_key: None,
+            allow_http: false,
+        }
+    }
+}
+
+/// The configured image store, encrypting and decrypting images with `cipher`
+pub fn open(config: &ImagesConfig, db: SharedDb, cipher: SharedCipher) -> Result<SharedImageStore> {
+    Ok(Arc::new(encrypted::EncryptedImageStore::new(open_raw(config, db)?, cipher)))
+}
+
+/// The configured image store, returning images exactly as stored
+pub fn open_raw(config: &ImagesConfig, db: SharedDb) -> Result<SharedImageStore> {
+    Ok(match config.backend {
+        ImageBackend::Fs => Arc::new(local::FsImageStore),
+        ImageBackend::Database => Arc::new(db::DbImageStore::new(db)),
+        ImageBackend::S3 => Arc::new(s3::S3ImageStore::new(&config.s3)?),
+    })
+}
+
+/// Encoded images by key, grouped by user ID
+pub type UserImages = Vec<(String, Vec<(String, Vec<u8>)>)>;
+
+/// A tenant's enrollment images as listed in `face_images`, read from the image store
+#[derive(Default)]
+pub struct Gallery {
+    /// Sorted by user ID and key
+    pub users: UserImages,
+    /// Keys listed in `face_images` that the store could not return, whether the image is gone
+    /// or could not be read (an outage, or a key that is no longer configured)
+    pub unreadable: Vec<String>,
+}
+
+impl Gallery {
+    pub async fn load(db: &Database, images: &dyn ImageStore, tenant: &str) -> Result<Self> {
+        let mut gallery = Self::default();
+
+        for (user_id, key) in db.get_face_images(tenant).await? {
+            let data = match images.get(&key).await {
+                Ok(data) => data,
+                Err(e) => {
+                    eprintln!("Failed to read {} from {} store: {}", key, images.name(), e);
+                    gallery.unreadable.push(key);
+                    continue;
+                }
+            };
+
+            match gallery.users.last_mut() {
+                Some((last_user, files)) if *last_user == user_id => files.push((key, data)),
+                _ => gallery.users.push((user_id, vec![(key, data)])),
+            }
+        }
+
+        Ok(gallery)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DatabaseConfig;
+    use object_store::memory::InMemory;
+    use uuid::Uuid;
+
+    // Every store behaves the same under `root`; `_` and `%` in keys must match literally
+    async fn round_trip(store: &dyn ImageStore, root: &str) {
+        let prefix = format!("{}/knowledge_1", root);
+        let lookalike = format!("{}/knowledgeX1", root);
+
+        store.put(&format!("{}/alice/1.jpg", prefix), b"one").await.unwrap();
+        store.put(&format!("{}/alice/1.jpg", prefix), b"two").await.unwrap();
+        store.put(&format!("{}/100%/2.jpg", prefix), b"three").await.unwrap();
+        store.put(&format!("{}/alice/1.jpg", lookalike), b"other").await.unwrap();
+        store.put(&format!("{}-old/alice/1.jpg", prefix), b"other").await.unwrap();
+
+        assert_eq!(store.get(&format!("{}/alice/1.jpg", prefix)).await.unwrap(), b"two");
+        assert_eq!(store.get(&format!("{}/100%/2.jpg", prefix)).await.unwrap(), b"three");
+        assert_eq!(
+            store.list(&prefix).await.unwrap(),
+            [format!("{}/100%/2.jpg", prefix), format!("{}/alice/1.jpg", prefix)]
+        );
+        assert_eq!(store.list(&format!("{}/100%", prefix)).await.unwrap(), [format!("{}/100%/2.jpg", prefix)]);
+        assert!(store.list(&format!("{}/bob", prefix)).await.unwrap().is_empty());
+
+        store.delete(&format!("{}/alice/1.jpg", prefix)).await.unwrap();
+        store.delete(&format!("{}/alice/1.jpg", prefix)).await.unwrap();
+        assert!(store.get(&format!("{}/alice/1.jpg", prefix)).await.is_err());
+        assert_eq!(store.list(&prefix).await.unwrap(), [format!("{}/100%/2.jpg", prefix)]);
+        assert_eq!(store.get(&format!("{}/alice/1.jpg", lookalike)).await.unwrap(), b"other");
+    }
+
+    #[tokio::test]
+    async fn fs_store_round_trip() {
+        let dir = std::env::temp_dir().join(format!("images-{}", Uuid::new_v4()));
+        let root = dir.to_string_lossy();
+
+        round_trip(&local::FsImageStore, &root).await;
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn database_store_round_trip() {
+        let config = DatabaseConfig {
+            url: "sqlite::memory:".to_string(),
+            max_connections: 1,
+        };
+        let db = Arc::new(Database::new(&config).await.unwrap());
+
+        round_trip(&db::DbImageStore::new(db), "tenants/acme").await;
+    }
+
+    #[tokio::test]
+    async fn s3_store_round_trip() {
+        round_trip(&s3::S3ImageStore::with_store(Arc::new(InMemory::new())), "tenants/acme").await;
+    }
+}
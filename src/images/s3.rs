@@ -0,0 +1,80 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use futures_util::TryStreamExt;
+use std::sync::Arc;
+use object_store::aws::AmazonS3Builder;
+use object_store::path::Path;
+use object_store::{ObjectStore, PutPayload};
+use percent_encoding::percent_decode_str;
+
+use super::{ImageStore, S3Config};
+
+/// Images as objects in an S3 bucket (AWS, MinIO or any other S3-compatible service)
+pub struct S3ImageStore {
+    store: Arc<dyn ObjectStore>,
+}
+
+impl S3ImageStore {
+    pub fn new(config: &S3Config) -> Result<Self> {
+        let mut builder = AmazonS3Builder::from_env()
+            .with_bucket_name(&config.bucket)
+            .with_region(&config.region)
+            .with_allow_http(config.allow_http);
+        if let Some(endpoint) = &config.endpoint {
+            builder = builder.with_endpoint(endpoint);
+        }
+        if let Some(access_key_id) = &config.access_key_id {
+            builder = builder.with_access_key_id(access_key_id);
+        }
+        if let Some(secret_access_key) = &config.secret_access_key {
+            builder = builder.with_secret_access_key(secret_access_key);
+        }
+
+        Ok(Self::with_store(Arc::new(builder.build()?)))
+    }
+
+    /// Keep the images in an already configured object store
+    pub fn with_store(store: Arc<dyn ObjectStore>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl ImageStore for S3ImageStore {
+    fn name(&self) -> &'static str {
+        "s3"
+    }
+
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        self.store
+            .put(&Path::from(key), PutPayload::from(data.to_vec()))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let object = self.store.get(&Path::from(key)).await?;
+
+        Ok(object.bytes().await?.to_vec())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match self.store.delete(&Path::from(key)).await {
+            Err(object_store::Error::NotFound { .. }) | Ok(()) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let objects: Vec<_> = self.store.list(Some(&Path::from(prefix))).try_collect().await?;
+        // Paths percent-encode characters S3 advises against, such as `%`; keys are the raw names
+        let mut keys = objects
+            .into_iter()
+            .map(|object| Ok(percent_decode_str(object.location.as_ref()).decode_utf8()?.into_owned()))
+            .collect::<Result<Vec<String>>>()?;
+        keys.sort();
+
+        Ok(keys)
+    }
+}
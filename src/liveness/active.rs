@@ -0,0 +1,130 @@
+use opencv::core::Rect;
+use std::str::FromStr;
+
+// Horizontal face movement, relative to face width, that counts as a full head turn
+const HEAD_TURN_MIN_SHIFT: f64 = 0.25;
+
+// Consecutive frames without eyes that make a blink; the eye detector misses single frames of
+// open eyes too often for one to count
+const BLINK_MIN_CLOSED_FRAMES: usize = 2;
+
+// Share of frames that must show open eyes, so a sequence in which the detector rarely finds
+// eyes at all does not pass as blinking
+const BLINK_MIN_OPEN_SHARE: f64 = 2.0 / 3.0;
+
+/// Action the user is asked to perform in front of the camera
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Challenge {
+    Blink,
+    HeadTurn,
+}
+
+impl FromStr for Challenge {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "blink" => Ok(Self::Blink),
+            "head_turn" => Ok(Self::HeadTurn),
+            other => Err(anyhow::anyhow!(
+                "Unknown challenge '{}', expected blink or head_turn",
+                other
+            )),
+        }
+    }
+}
+
+impl Challenge {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Blink => "blink",
+            Self::HeadTurn => "head_turn",
+        }
+    }
+}
+
+/// Whether the sequence of per-frame eye counts goes open, closed for at least
+/// `BLINK_MIN_CLOSED_FRAMES` frames in a row, and open again, with eyes visible in a clear
+/// majority of frames. A photo held up to the camera keeps its eyes in every frame.
+pub fn blink_detected(eye_counts: &[usize]) -> bool {
+    let open = eye_counts.iter().filter(|&&eyes| eyes > 0).count();
+    if eye_counts.is_empty() || (open as f64) < eye_counts.len() as f64 * BLINK_MIN_OPEN_SHARE {
+        return false;
+    }
+
+    let mut seen_open = false;
+    let mut closed_run = 0;
+    for &eyes in eye_counts {
+        if eyes > 0 {
+            if seen_open && closed_run >= BLINK_MIN_CLOSED_FRAMES {
+                return true;
+            }
+            seen_open = true;
+            closed_run = 0;
+        } else if seen_open {
+            closed_run += 1;
+        }
+    }
+
+    false
+}
+
+/// Score in 0..1 of how far the face moved sideways across the sequence.
+/// Faces are compared by center position relative to their average width, so the score
+/// does not depend on distance to the camera. Sliding a printed photo sideways moves the box
+/// too, so this is only meaningful together with the passive checks.
+pub fn head_turn_score(faces: &[Rect]) -> f64 {
+    if faces.len() < 2 {
+        return 0.0;
+    }
+
+    let mean_width = faces.iter().map(|f| f.width as f64).sum::<f64>() / faces.len() as f64;
+    let centers: Vec<f64> = faces
+        .iter()
+        .map(|f| (f.x as f64 + f.width as f64 / 2.0) / mean_width)
+        .collect();
+    let min = centers.iter().cloned().fold(f64::MAX, f64::min);
+    let max = centers.iter().cloned().fold(f64::MIN, f64::max);
+
+    ((max - min) / HEAD_TURN_MIN_SHIFT).min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blink_needs_consecutive_closed_frames() {
+        assert!(blink_detected(&[2, 2, 2, 0, 0, 2, 2, 2]));
+        assert!(blink_detected(&[2, 1, 2, 2, 0, 0, 0, 1, 2, 2]));
+
+        // Eyes in every frame, a single missed frame, or never open again
+        assert!(!blink_detected(&[2, 2, 2, 2, 2, 2]));
+        assert!(!blink_detected(&[2, 2, 0, 2, 2, 2]));
+        assert!(!blink_detected(&[2, 2, 0, 2, 2, 0, 2, 2, 2]));
+        assert!(!blink_detected(&[2, 2, 2, 2, 0, 0]));
+        // Frames before the eyes are first seen do not count as a blink
+        assert!(!blink_detected(&[0, 0, 2, 2, 2, 2]));
+        assert!(!blink_detected(&[]));
+    }
+
+    #[test]
+    fn blink_needs_eyes_in_most_frames() {
+        // Open, closed, open, but eyes are missing in half of the frames
+        assert!(!blink_detected(&[2, 0, 0, 0, 0, 2]));
+        assert!(!blink_detected(&[1, 0, 0, 1, 0, 0, 1, 0, 0]));
+        assert!(blink_detected(&[1, 1, 1, 0, 0, 1, 1]));
+    }
+
+    #[test]
+    fn head_turn_is_scored_relative_to_face_width() {
+        let face = |x: i32, width: i32| Rect::new(x, 100, width, width);
+
+        assert_eq!(head_turn_score(&[face(100, 100)]), 0.0);
+        assert_eq!(head_turn_score(&[face(100, 100), face(100, 100), face(100, 100)]), 0.0);
+        // A shift of an eighth of the face width is half a turn, at any distance
+        assert!((head_turn_score(&[face(100, 100), face(112, 100), face(106, 100)]) - 0.48).abs() < 1e-9);
+        assert!((head_turn_score(&[face(200, 200), face(224, 200)]) - 0.48).abs() < 1e-9);
+        assert_eq!(head_turn_score(&[face(100, 100), face(60, 100), face(150, 100)]), 1.0);
+    }
+}
@@ -0,0 +1,293 @@
+pub mod active;
+pub mod passive;
+
+use anyhow::Result;
+use opencv::{
+    core::{Mat, Rect, Size, Vector},
+    dnn::{read_net_from_onnx, Net},
+    objdetect::CascadeClassifier,
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use utoipa::ToSchema;
+
+use crate::face_recognition::model::{find_haarcascade, to_gray};
+use crate::face_recognition::quality::sharpness;
+use active::{blink_detected, head_turn_score, Challenge};
+use passive::{lbp_histogram, moire_peak_ratio, onnx_live_probability, LbpClassifier};
+
+pub type SharedLiveness = Arc<Mutex<LivenessChecker>>;
+
+// Laplacian variance below the first value scores 0, above the second scores 1
+const SHARPNESS_RANGE: (f64, f64) = (30.0, 150.0);
+
+// Moire peak ratio above the first value scores 0, below the second scores 1
+const MOIRE_RANGE: (f64, f64) = (60.0, 25.0);
+
+// Active challenges need at least this many frames with a detectable face
+pub const MIN_CHALLENGE_FRAMES: usize = 3;
+pub const MAX_CHALLENGE_FRAMES: usize = 30;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LivenessConfig {
+    /// Probes scoring below this are refused
+    pub threshold: f64,
+    /// Run passive checks on every /detect-face request even if the client did not ask
+    pub required: bool,
+    /// Optional ONNX anti-spoofing model
+    pub onnx_model_path: Option<String>,
+    pub onnx_input_size: i32,
+    /// Optional JSON weights for the LBP print-attack classifier
+    pub lbp_classifier_path: Option<String>,
+    /// Eye cascade for the blink challenge; searched in the OpenCV install locations if unset
+    pub eye_cascade_path: Option<String>,
+}
+
+impl Default for LivenessConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 0.5,
+            required: false,
+            onnx_model_path: None,
+            onnx_input_size: 128,
+            lbp_classifier_path: None,
+            eye_cascade_path: None,
+        }
+    }
+}
+
+/// Result of a single liveness check
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LivenessCheck {
+    /// Check name (sharpness, moire, texture, model, blink, head_turn)
+    name: String,
+    /// Score in 0..1, higher means more likely live
+    score: f64,
+    /// Raw measurement the score was derived from, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<f64>,
+}
+
+/// Combined liveness verdict for a probe
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LivenessReport {
+    /// Overall score in 0..1
+    pub score: f64,
+    /// Minimum score required to pass
+    pub threshold: f64,
+    /// Whether the probe may be identified
+    pub passed: bool,
+    /// Individual check results
+    pub checks: Vec<LivenessCheck>,
+}
+
+impl LivenessReport {
+    fn new(score: f64, threshold: f64, checks: Vec<LivenessCheck>) -> Self {
+        Self {
+            score,
+            threshold,
+            passed: score >= threshold,
+            checks,
+        }
+    }
+}
+
+/// Anti-spoofing checks shared by the detection endpoints
+pub struct LivenessChecker {
+    config: LivenessConfig,
+    net: Option<Net>,
+    lbp_classifier: Option<LbpClassifier>,
+    eye_cascade: Option<CascadeClassifier>,
+}
+
+impl LivenessChecker {
+    pub fn new(config: LivenessConfig) -> Result<Self> {
+        let net = match &config.onnx_model_path {
+            Some(path) => {
+                println!("Loading liveness model from: {}", path);
+                Some(read_net_from_onnx(path)?)
+            }
+            None => None,
+        };
+
+        let lbp_classifier = match &config.lbp_classifier_path {
+            Some(path) => Some(LbpClassifier::load(path)?),
+            None => None,
+        };
+
+        // The blink challenge is unavailable without the eye cascade, everything else still works
+        let eye_cascade_path = config
+            .eye_cascade_path
+            .clone()
+            .or_else(|| find_haarcascade("haarcascade_eye.xml"));
+        let eye_cascade = match eye_cascade_path {
+            Some(path) => Some(CascadeClassifier::new(&path)?),
+            None => {
+                eprintln!("haarcascade_eye.xml not found, blink challenge disabled");
+                None
+            }
+        };
+
+        Ok(Self {
+            config,
+            net,
+            lbp_classifier,
+            eye_cascade,
+        })
+    }
+
+    pub fn is_required(&self) -> bool {
+        self.config.required
+    }
+
+    /// Passive checks on a single BGR image for the given face
+    pub fn check_passive(&mut self, img: &Mat, face: Rect) -> Result<LivenessReport> {
+        let checks = self.passive_checks(img, face)?;
+        let score = mean_score(&checks);
+
+        Ok(LivenessReport::new(score, self.config.threshold, checks))
+    }
+
+    /// Active challenge over a short frame sequence, combined with passive checks on the
+    /// frame used for identification. `faces` holds the detected face for each frame.
+    pub fn check_challenge(
+        &mut self,
+        frames: &[Mat],
+        faces: &[Option<Rect>],
+        challenge: Challenge,
+    ) -> Result<LivenessReport> {
+        let detected: Vec<(&Mat, Rect)> = frames
+            .iter()
+            .zip(faces)
+            .filter_map(|(frame, face)| face.map(|f| (frame, f)))
+            .collect();
+
+        if detected.len() < MIN_CHALLENGE_FRAMES {
+            return Err(anyhow::anyhow!(
+                "Face found in {} frames, at least {} required",
+                detected.len(),
+                MIN_CHALLENGE_FRAMES
+            ));
+        }
+
+        let challenge_score = match challenge {
+            Challenge::Blink => {
+                let eye_counts = detected
+                    .iter()
+                    .map(|(frame, face)| self.count_eyes(frame, *face))
+                    .collect::<Result<Vec<_>>>()?;
+                if blink_detected(&eye_counts) { 1.0 } else { 0.0 }
+            }
+            Challenge::HeadTurn => {
+                let rects: Vec<Rect> = detected.iter().map(|(_, face)| *face).collect();
+                head_turn_score(&rects)
+            }
+        };
+
+        let (frame, face) = detected[detected.len() / 2];
+        let mut checks = self.passive_checks(frame, face)?;
+        let passive_score = mean_score(&checks);
+        checks.push(LivenessCheck {
+            name: challenge.name().to_string(),
+            score: challenge_score,
+            value: None,
+        });
+
+        // Both the challenge and the passive checks have to look live
+        let score = passive_score.min(challenge_score);
+
+        Ok(LivenessReport::new(score, self.config.threshold, checks))
+    }
+
+    fn passive_checks(&mut self, img: &Mat, face: Rect) -> Result<Vec<LivenessCheck>> {
+        let face_bgr = Mat::roi(img, face)?.try_clone()?;
+        let gray = to_gray(&face_bgr)?;
+        let mut checks = Vec::new();
+
+        let laplacian_var = sharpness(&gray)?;
+        checks.push(LivenessCheck {
+            name: "sharpness".to_string(),
+            score: ramp(laplacian_var, SHARPNESS_RANGE),
+            value: Some(laplacian_var),
+        });
+
+        let peak_ratio = moire_peak_ratio(&gray)?;
+        checks.push(LivenessCheck {
+            name: "moire".to_string(),
+            score: ramp(peak_ratio, MOIRE_RANGE),
+            value: Some(peak_ratio),
+        });
+
+        if let Some(classifier) = &self.lbp_classifier {
+            checks.push(LivenessCheck {
+                name: "texture".to_string(),
+                score: classifier.live_probability(&lbp_histogram(&gray)?),
+                value: None,
+            });
+        }
+
+        if let Some(net) = self.net.as_mut() {
+            checks.push(LivenessCheck {
+                name: "model".to_string(),
+                score: onnx_live_probability(net, &face_bgr, self.config.onnx_input_size)?,
+                value: None,
+            });
+        }
+
+        Ok(checks)
+    }
+
+    fn count_eyes(&mut self, img: &Mat, face: Rect) -> Result<usize> {
+        let cascade = self
+            .eye_cascade
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("Blink challenge unavailable: haarcascade_eye.xml not found"))?;
+
+        // Eyes are in the upper half of the face
+        let upper = Rect::new(face.x, face.y, face.width, face.height / 2);
+        let gray = to_gray(&Mat::roi(img, upper)?.try_clone()?)?;
+        let min_eye = face.width / 8;
+
+        let mut eyes = Vector::<Rect>::new();
+        cascade.detect_multi_scale(
+            &gray,
+            &mut eyes,
+            1.1,
+            5,
+            0,
+            Size::new(min_eye, min_eye),
+            Size::new(0, 0),
+        )?;
+
+        Ok(eyes.len())
+    }
+}
+
+fn mean_score(checks: &[LivenessCheck]) -> f64 {
+    checks.iter().map(|c| c.score).sum::<f64>() / checks.len() as f64
+}
+
+// Linear score between a bad and a good value, clamped to 0..1; works for either direction
+fn ramp(value: f64, (bad, good): (f64, f64)) -> f64 {
+    ((value - bad) / (good - bad)).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ramp_scores_in_either_direction() {
+        assert_eq!(ramp(10.0, SHARPNESS_RANGE), 0.0);
+        assert_eq!(ramp(90.0, SHARPNESS_RANGE), 0.5);
+        assert_eq!(ramp(500.0, SHARPNESS_RANGE), 1.0);
+
+        // Lower moire peaks are better
+        assert_eq!(ramp(80.0, MOIRE_RANGE), 0.0);
+        assert!((ramp(42.5, MOIRE_RANGE) - 0.5).abs() < 1e-9);
+        assert_eq!(ramp(10.0, MOIRE_RANGE), 1.0);
+    }
+}
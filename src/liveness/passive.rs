@@ -0,0 +1,299 @@
+use anyhow::Result;
+use opencv::{
+    core::{dft, magnitude, split, Mat, Scalar, Size, Vector, CV_32F, DFT_COMPLEX_OUTPUT},
+    dnn::{blob_from_image, Net},
+    imgproc::{resize, INTER_AREA},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+// Face crops are normalized to these sizes before measuring
+const TEXTURE_SIZE: i32 = 64;
+const SPECTRUM_SIZE: i32 = 128;
+
+// Frequencies inside this radius carry the face itself rather than print or screen artifacts
+const LOW_FREQUENCY_RADIUS: i32 = 16;
+
+// 58 uniform patterns plus one bin for all non-uniform ones
+pub const LBP_BINS: usize = 59;
+
+// Batch gradient descent settings for fitting the LBP classifier
+const TRAIN_EPOCHS: usize = 2000;
+const TRAIN_LEARNING_RATE: f64 = 0.5;
+const TRAIN_L2: f64 = 1e-3;
+
+// Neighbors in circular order, starting top-left
+const LBP_NEIGHBORS: [(i32, i32); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, 1), (1, 1), (1, 0), (1, -1), (0, -1)];
+
+/// Ratio between the strongest high-frequency peak and the mean high-frequency energy.
+/// Re-photographed screens and halftone prints produce isolated periodic peaks (moire).
+pub fn moire_peak_ratio(gray_face: &Mat) -> Result<f64> {
+    let mut small = Mat::default();
+    resize(gray_face, &mut small, Size::new(SPECTRUM_SIZE, SPECTRUM_SIZE), 0.0, 0.0, INTER_AREA)?;
+    let mut float = Mat::default();
+    small.convert_to(&mut float, CV_32F, 1.0, 0.0)?;
+
+    let mut complex = Mat::default();
+    dft(&float, &mut complex, DFT_COMPLEX_OUTPUT, 0)?;
+    let mut planes = Vector::<Mat>::new();
+    split(&complex, &mut planes)?;
+    let mut spectrum = Mat::default();
+    magnitude(&planes.get(0)?, &planes.get(1)?, &mut spectrum)?;
+
+    let mut sum = 0.0;
+    let mut count = 0usize;
+    let mut peak = 0.0f64;
+    for y in 0..SPECTRUM_SIZE {
+        for x in 0..SPECTRUM_SIZE {
+            // The spectrum is not shifted, low frequencies sit in the corners
+            let fy = y.min(SPECTRUM_SIZE - y);
+            let fx = x.min(SPECTRUM_SIZE - x);
+            if fx * fx + fy * fy < LOW_FREQUENCY_RADIUS * LOW_FREQUENCY_RADIUS {
+                continue;
+            }
+
+            let value = *spectrum.at_2d::<f32>(y, x)? as f64;
+            sum += value;
+            count += 1;
+            peak = peak.max(value);
+        }
+    }
+
+    let mean = sum / count as f64;
+    if mean <= f64::EPSILON {
+        return Ok(0.0);
+    }
+
+    Ok(peak / mean)
+}
+
+/// Normalized histogram of uniform local binary patterns over the face
+pub fn lbp_histogram(gray_face: &Mat) -> Result<Vec<f64>> {
+    let mut small = Mat::default();
+    resize(gray_face, &mut small, Size::new(TEXTURE_SIZE, TEXTURE_SIZE), 0.0, 0.0, INTER_AREA)?;
+
+    let bins = uniform_bins();
+    let mut histogram = vec![0.0; LBP_BINS];
+    for y in 1..TEXTURE_SIZE - 1 {
+        for x in 1..TEXTURE_SIZE - 1 {
+            let center = *small.at_2d::<u8>(y, x)?;
+            let mut code = 0u8;
+            for (bit, (dy, dx)) in LBP_NEIGHBORS.iter().enumerate() {
+                if *small.at_2d::<u8>(y + dy, x + dx)? >= center {
+                    code |= 1 << bit;
+                }
+            }
+            histogram[bins[code as usize]] += 1.0;
+        }
+    }
+
+    let total: f64 = histogram.iter().sum();
+    for value in histogram.iter_mut() {
+        *value /= total;
+    }
+
+    Ok(histogram)
+}
+
+// Maps each 8-bit pattern to its bin; patterns with more than two 0/1 transitions share the last bin
+fn uniform_bins() -> [usize; 256] {
+    let mut bins = [LBP_BINS - 1; 256];
+    let mut next = 0;
+    for code in 0..=255u8 {
+        if (code ^ code.rotate_left(1)).count_ones() <= 2 {
+            bins[code as usize] = next;
+            next += 1;
+        }
+    }
+    bins
+}
+
+/// Logistic regression over the LBP histogram, trained offline on live and print-attack crops.
+///
+/// Loaded from a JSON file `{"weights": [59 numbers], "bias": number}`, as written by the
+/// `train-liveness` command; the output is the probability that the face is live.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LbpClassifier {
+    weights: Vec<f64>,
+    bias: f64,
+}
+
+impl LbpClassifier {
+    pub fn load(path: &str) -> Result<Self> {
+        let classifier: Self = serde_json::from_str(&fs::read_to_string(path)?)?;
+
+        if classifier.weights.len() != LBP_BINS {
+            return Err(anyhow::anyhow!(
+                "LBP classifier in {} has {} weights, expected {}",
+                path,
+                classifier.weights.len(),
+                LBP_BINS
+            ));
+        }
+
+        Ok(classifier)
+    }
+
+    /// Fit the classifier to the LBP histograms of live (`true`) and spoofed (`false`) faces by
+    /// L2-regularized logistic regression
+    pub fn train(samples: &[(Vec<f64>, bool)]) -> Result<Self> {
+        if !samples.iter().any(|(_, live)| *live) || !samples.iter().any(|(_, live)| !*live) {
+            return Err(anyhow::anyhow!("Training needs both live and spoofed faces"));
+        }
+        if let Some((histogram, _)) = samples.iter().find(|(histogram, _)| histogram.len() != LBP_BINS) {
+            return Err(anyhow::anyhow!("Histogram has {} bins, expected {}", histogram.len(), LBP_BINS));
+        }
+
+        // Bins are fitted standardized, so rare patterns learn as fast as common ones, and the
+        // result is folded back into weights over raw histograms
+        let n = samples.len() as f64;
+        let mean: Vec<f64> = (0..LBP_BINS)
+            .map(|bin| samples.iter().map(|(histogram, _)| histogram[bin]).sum::<f64>() / n)
+            .collect();
+        let scale: Vec<f64> = (0..LBP_BINS)
+            .map(|bin| {
+                let variance = samples.iter().map(|(histogram, _)| (histogram[bin] - mean[bin]).powi(2)).sum::<f64>() / n;
+                if variance > f64::EPSILON { variance.sqrt() } else { 1.0 }
+            })
+            .collect();
+        let features: Vec<(Vec<f64>, f64)> = samples
+            .iter()
+            .map(|(histogram, live)| {
+                let x = (0..LBP_BINS).map(|bin| (histogram[bin] - mean[bin]) / scale[bin]).collect();
+                (x, if *live { 1.0 } else { 0.0 })
+            })
+            .collect();
+
+        let mut weights = vec![0.0; LBP_BINS];
+        let mut bias = 0.0;
+        for _ in 0..TRAIN_EPOCHS {
+            let mut gradient = vec![0.0; LBP_BINS];
+            let mut bias_gradient = 0.0;
+            for (x, label) in &features {
+                let z: f64 = weights.iter().zip(x).map(|(w, v)| w * v).sum::<f64>() + bias;
+                let error = sigmoid(z) - label;
+                for (g, v) in gradient.iter_mut().zip(x) {
+                    *g += error * v;
+                }
+                bias_gradient += error;
+            }
+            for (w, g) in weights.iter_mut().zip(&gradient) {
+                *w -= TRAIN_LEARNING_RATE * (g / n + TRAIN_L2 * *w);
+            }
+            bias -= TRAIN_LEARNING_RATE * bias_gradient / n;
+        }
+
+        let weights: Vec<f64> = weights.iter().zip(&scale).map(|(w, s)| w / s).collect();
+        let bias = bias - weights.iter().zip(&mean).map(|(w, m)| w * m).sum::<f64>();
+
+        Ok(Self { weights, bias })
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    pub fn live_probability(&self, histogram: &[f64]) -> f64 {
+        let z: f64 = self
+            .weights
+            .iter()
+            .zip(histogram)
+            .map(|(w, h)| w * h)
+            .sum::<f64>()
+            + self.bias;
+
+        sigmoid(z)
+    }
+}
+
+/// Run a local ONNX anti-spoofing model on a BGR face crop and return the live probability.
+///
+/// Models with a single output are read as a live logit or probability; models with several
+/// outputs are read as class probabilities or logits with "live" at index 1.
+pub fn onnx_live_probability(net: &mut Net, face: &Mat, input_size: i32) -> Result<f64> {
+    let blob = blob_from_image(
+        face,
+        1.0 / 255.0,
+        Size::new(input_size, input_size),
+        Scalar::default(),
+        true,
+        false,
+        CV_32F,
+    )?;
+    net.set_input(&blob, "", 1.0, Scalar::default())?;
+    let output = net.forward_single("")?;
+    let values: Vec<f64> = output.data_typed::<f32>()?.iter().map(|&v| v as f64).collect();
+
+    match values.as_slice() {
+        [] => Err(anyhow::anyhow!("Liveness model returned no output")),
+        [value] if (0.0..=1.0).contains(value) => Ok(*value),
+        [logit] => Ok(sigmoid(*logit)),
+        scores if scores.iter().all(|s| (0.0..=1.0).contains(s))
+            && (scores.iter().sum::<f64>() - 1.0).abs() < 1e-3 =>
+        {
+            Ok(scores[1])
+        }
+        scores => {
+            let max = scores.iter().cloned().fold(f64::MIN, f64::max);
+            let exp: Vec<f64> = scores.iter().map(|s| (s - max).exp()).collect();
+            Ok(exp[1] / exp.iter().sum::<f64>())
+        }
+    }
+}
+
+fn sigmoid(z: f64) -> f64 {
+    1.0 / (1.0 + (-z).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Live faces have most of their texture in the first patterns, prints in the last ones
+    fn histogram(live: bool, variant: usize) -> Vec<f64> {
+        let mut histogram = vec![0.01; LBP_BINS];
+        let bins = if live { 0..10 } else { 40..50 };
+        for bin in bins {
+            histogram[bin] += 0.04 + 0.002 * ((bin + variant) % 5) as f64;
+        }
+        let total: f64 = histogram.iter().sum();
+        histogram.iter().map(|value| value / total).collect()
+    }
+
+    #[test]
+    fn uniform_patterns_fill_all_but_the_last_bin() {
+        let bins = uniform_bins();
+        assert_eq!(*bins.iter().filter(|&&bin| bin != LBP_BINS - 1).max().unwrap(), LBP_BINS - 2);
+        assert_eq!(bins[0b0000_0000], 0);
+        assert_ne!(bins[0b0000_1111], LBP_BINS - 1);
+        assert_eq!(bins[0b0101_0101], LBP_BINS - 1);
+    }
+
+    #[test]
+    fn trains_a_classifier_that_separates_the_classes() {
+        let samples: Vec<(Vec<f64>, bool)> = (0..20).map(|i| (histogram(i % 2 == 0, i), i % 2 == 0)).collect();
+        let classifier = LbpClassifier::train(&samples).unwrap();
+
+        for variant in 20..25 {
+            assert!(classifier.live_probability(&histogram(true, variant)) > 0.9);
+            assert!(classifier.live_probability(&histogram(false, variant)) < 0.1);
+        }
+
+        let path = std::env::temp_dir().join(format!("lbp-{}.json", uuid::Uuid::new_v4()));
+        let path = path.to_string_lossy();
+        classifier.save(&path).unwrap();
+        let loaded = LbpClassifier::load(&path).unwrap();
+        assert_eq!(loaded.live_probability(&histogram(true, 0)), classifier.live_probability(&histogram(true, 0)));
+        std::fs::remove_file(&*path).unwrap();
+    }
+
+    #[test]
+    fn training_needs_both_classes() {
+        let live: Vec<(Vec<f64>, bool)> = (0..4).map(|i| (histogram(true, i), true)).collect();
+        assert!(LbpClassifier::train(&live).is_err());
+        assert!(LbpClassifier::train(&[(vec![0.5; 3], true), (vec![0.5; 3], false)]).is_err());
+    }
+}
@@ -1,22 +1,93 @@
+mod admin;
+mod attendance;
+mod audit;
+mod auth;
+mod cli;
+mod config;
 mod face_recognition;
 mod database;
+mod detections;
+mod encryption;
+mod erasure;
+mod evaluation;
+mod gallery;
+mod images;
+mod liveness;
+mod retention;
+mod tenants;
+mod unknowns;
+mod video;
 
 use axum::{
-    routing::{get, post},
-    Router,
+    middleware,
+    routing::{delete, get, post, MethodRouter},
+    Extension, Router,
 };
+use axum::http::HeaderValue;
 use dotenv::dotenv;
 use std::env;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
-use tower_http::cors::{CorsLayer, Any};
-use utoipa::OpenApi;
+use tower_http::cors::{AllowOrigin, CorsLayer, Any};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
 use utoipa_swagger_ui::SwaggerUi;
 
-use database::Database;
+use admin::handlers::get_config;
+use attendance::{
+    handlers::{attendance_report, attendance_summary, AttendanceReport, AttendanceSummary, ReportFormat},
+    AttendanceRecord, UserAttendance,
+};
+use audit::{
+    handlers::{list_audit_events, AuditPage},
+};
+use auth::{
+    handlers::{create_api_key, list_api_keys, revoke_api_key, CreateApiKeyRequest, CreateApiKeyResponse},
+    middleware::{require_role, Authenticator, SharedAuthenticator, API_KEY_HEADER},
+    Role,
+};
+use config::{Config, SharedConfig};
+use database::{ApiKey as StoredApiKey, AuditEvent, Database, DetectionLog, DetectionSort, ErasureCertificate, SortOrder, TenantSummary, UnknownCluster, UnknownFace, VideoAppearance, VideoJob, VideoJobProgress};
+use detections::{
+    calibration::{CalibrationReport, FeedbackVerdict},
+    handlers::{
+        apply_threshold, get_detection, list_detections, submit_feedback, suggest_threshold, DetectionPage, FeedbackRequest,
+        FeedbackResponse,
+    },
+    apply_stored_threshold,
+};
+use encryption::{Cipher, SharedCipher};
+use erasure::{
+    handlers::{erase_user, get_erasure, list_erasures},
+    DetectionErasure,
+};
 use face_recognition::{
     FaceRecognitionModel,
-    handlers::{add_face, detect_face, SharedModel, SharedDb, ApiResponse, AddFaceResponse, DetectFaceResponse},
+    augment::TrainingStats,
+    quality::FaceQuality,
+    handlers::{add_face, add_tenant_face, anonymize_faces, detect_face, detect_tenant_face, SharedModel, SharedDb, ApiResponse, AddFaceResponse, DetectFaceResponse, DetectedFace, RejectedPhoto},
+};
+use gallery::{
+    audit::{GalleryAuditReport, MisfiledImage, SimilarIdentities},
+    handlers::{check_gallery, fix_gallery, gallery_audit},
+    reconcile::{MissingImage, OrphanAction, ReconcileReport},
+};
+use images::SharedImageStore;
+use liveness::{LivenessCheck, LivenessChecker, LivenessReport, SharedLiveness};
+use retention::{clean_temp_dir, handlers::purge_data, spawn_sweeper, PurgeReport};
+use tenants::{
+    handlers::{create_tenant, list_tenants, CreateTenantRequest},
+    registry::{ModelRegistry, SharedRegistry},
+    DEFAULT_TENANT,
+};
+use unknowns::{
+    cluster::spawn_clustering,
+    handlers::{enroll_cluster, list_clusters, EnrollClusterRequest, EnrollClusterResponse, UnknownClusterResponse},
+};
+use video::handlers::{
+    analyze_video, get_video_job, get_video_output, AnalyzeVideoRequest, AnalyzeVideoResponse, VideoJobResponse,
 };
 
 #[derive(OpenApi)]
@@ -24,12 +95,61 @@ use face_recognition::{
     paths(
         face_recognition::handlers::add_face,
         face_recognition::handlers::detect_face,
+        face_recognition::handlers::anonymize_faces,
+        video::handlers::analyze_video,
+        video::handlers::get_video_job,
+        video::handlers::get_video_output,
+        detections::handlers::list_detections,
+        detections::handlers::get_detection,
+        detections::handlers::submit_feedback,
+        detections::handlers::suggest_threshold,
+        detections::handlers::apply_threshold,
+        attendance::handlers::attendance_report,
+        attendance::handlers::attendance_summary,
+        unknowns::handlers::list_clusters,
+        unknowns::handlers::enroll_cluster,
+        gallery::handlers::gallery_audit,
+        gallery::handlers::check_gallery,
+        gallery::handlers::fix_gallery,
+        retention::handlers::purge_data,
+        erasure::handlers::erase_user,
+        erasure::handlers::list_erasures,
+        erasure::handlers::get_erasure,
+        audit::handlers::list_audit_events,
+        admin::handlers::get_config,
+        auth::handlers::create_api_key,
+        auth::handlers::list_api_keys,
+        auth::handlers::revoke_api_key,
+        face_recognition::handlers::add_tenant_face,
+        face_recognition::handlers::detect_tenant_face,
+        tenants::handlers::list_tenants,
+        tenants::handlers::create_tenant,
     ),
     components(
-        schemas(ApiResponse<AddFaceResponse>, ApiResponse<DetectFaceResponse>, AddFaceResponse, DetectFaceResponse)
+        schemas(ApiResponse<AddFaceResponse>, ApiResponse<DetectFaceResponse>, AddFaceResponse, DetectFaceResponse, DetectedFace, RejectedPhoto, TrainingStats, FaceQuality, LivenessReport, LivenessCheck),
+        schemas(ApiResponse<AnalyzeVideoResponse>, ApiResponse<VideoJobResponse>, AnalyzeVideoRequest, AnalyzeVideoResponse, VideoJobResponse, VideoJob, VideoJobProgress, VideoAppearance),
+        schemas(ApiResponse<DetectionPage>, ApiResponse<DetectionLog>, DetectionPage, DetectionLog, DetectionSort, SortOrder),
+        schemas(ApiResponse<FeedbackResponse>, ApiResponse<CalibrationReport>, FeedbackRequest, FeedbackResponse, FeedbackVerdict, CalibrationReport),
+        schemas(ApiResponse<AttendanceReport>, ApiResponse<AttendanceSummary>, AttendanceReport, AttendanceSummary, AttendanceRecord, UserAttendance, ReportFormat),
+        schemas(ApiResponse<Vec<UnknownClusterResponse>>, ApiResponse<EnrollClusterResponse>, UnknownClusterResponse, UnknownCluster, UnknownFace, EnrollClusterRequest, EnrollClusterResponse),
+        schemas(ApiResponse<GalleryAuditReport>, GalleryAuditReport, MisfiledImage, SimilarIdentities),
+        schemas(ApiResponse<ReconcileReport>, ReconcileReport, MissingImage, OrphanAction),
+        schemas(ApiResponse<PurgeReport>, PurgeReport),
+        schemas(ApiResponse<ErasureCertificate>, ApiResponse<Vec<ErasureCertificate>>, ErasureCertificate, DetectionErasure),
+        schemas(ApiResponse<AuditPage>, AuditPage, AuditEvent),
+        schemas(ApiResponse<CreateApiKeyResponse>, ApiResponse<Vec<StoredApiKey>>, ApiResponse<StoredApiKey>, CreateApiKeyRequest, CreateApiKeyResponse, StoredApiKey, Role),
+        schemas(ApiResponse<Vec<TenantSummary>>, ApiResponse<TenantSummary>, TenantSummary, CreateTenantRequest)
     ),
+    modifiers(&SecurityAddon),
+    security(("api_key" = []), ("bearer" = [])),
     tags(
-        (name = "Face Recognition", description = "Face recognition and detection endpoints")
+        (name = "Face Recognition", description = "Face recognition and detection endpoints"),
+        (name = "Video", description = "Video file and stream analysis"),
+        (name = "Detections", description = "Detection history, feedback and match threshold calibration"),
+        (name = "Reports", description = "Attendance and presence built on detection logs"),
+        (name = "Unknown Faces", description = "Clusters of repeated unidentified visitors"),
+        (name = "Admin", description = "Gallery maintenance, service configuration and API keys"),
+        (name = "Tenants", description = "Per-tenant galleries and models")
     ),
     info(
         title = "Face Recognition API",
@@ -43,17 +163,53 @@ use face_recognition::{
 )]
 struct ApiDoc;
 
+// Declares the `X-API-Key` header and bearer tokens so Swagger UI can send them
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "api_key",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(API_KEY_HEADER))),
+            );
+            components.add_security_scheme(
+                "bearer",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()),
+            );
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() {
     dotenv().ok();
 
-    let host = env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
-    let port = env::var("PORT").unwrap_or_else(|_| "3000".to_string());
-    let addr = format!("{}:{}", host, port);
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(String::as_str) {
+        None | Some("serve") => serve(config).await,
+        Some(command) => {
+            if let Err(e) = cli::run(command, &args[2..], &config).await {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+async fn serve(config: Config) {
+    let addr = format!("{}:{}", config.server.host, config.server.port);
 
     // Initialize database
-    let database_url = env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:face_recognition.db".to_string());
-    let db = match Database::new(&database_url).await {
+    let db = match Database::new(&config.database).await {
         Ok(db) => {
             println!("Database initialized successfully");
             db
@@ -65,8 +221,34 @@ async fn main() {
     };
     let shared_db: SharedDb = Arc::new(db);
 
+    // Images, models and unknown faces are encrypted at rest once encryption.key is set
+    let cipher: SharedCipher = match Cipher::open(&config.encryption) {
+        Ok(cipher) => {
+            if cipher.is_enabled() {
+                println!("Encrypting enrollment images, models and unknown faces at rest");
+            }
+            Arc::new(cipher)
+        }
+        Err(e) => {
+            eprintln!("Failed to load encryption keys: {}", e);
+            return;
+        }
+    };
+
+    // Enrollment images live wherever images.backend says
+    let shared_images: SharedImageStore = match images::open(&config.images, shared_db.clone(), cipher.clone()) {
+        Ok(store) => {
+            println!("Storing enrollment images in the {} image store", store.name());
+            store
+        }
+        Err(e) => {
+            eprintln!("Failed to initialize image store: {}", e);
+            return;
+        }
+    };
+
     // Initialize face recognition model
-    let model = match FaceRecognitionModel::new() {
+    let mut model = match FaceRecognitionModel::new(&config) {
         Ok(m) => {
             println!("Face recognition model initialized successfully");
             m
@@ -78,20 +260,144 @@ async fn main() {
         }
     };
 
+    // Serve the model saved by the last training, if any
+    let model_path = &config.paths.model_path;
+    if Path::new(model_path).exists()
+        && let Err(e) = model.load(model_path)
+    {
+        eprintln!("Failed to load saved model {}: {}", model_path, e);
+    }
+
+    apply_stored_threshold(&shared_db, DEFAULT_TENANT, &mut model).await;
+
     let shared_model: SharedModel = Arc::new(Mutex::new(model));
 
+    // Initialize liveness checks
+    let liveness = match LivenessChecker::new(config.liveness.clone()) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("Failed to initialize liveness checks: {}", e);
+            return;
+        }
+    };
+    let shared_liveness: SharedLiveness = Arc::new(Mutex::new(liveness));
+
+    // Check callers against their API key's role
+    if !config.auth.enabled {
+        println!("Warning: authentication is disabled, every endpoint is open");
+    } else if !config.auth.jwt.enabled()
+        && let Ok(keys) = shared_db.get_api_keys().await
+        && keys.iter().all(|key| key.revoked_at.is_some())
+    {
+        println!("No active API keys; create one with `create-api-key <name> admin`");
+    }
+    let authenticator: SharedAuthenticator = match Authenticator::new(shared_db.clone(), config.auth.clone()) {
+        Ok(a) => Arc::new(a),
+        Err(e) => {
+            eprintln!("Failed to initialize authentication: {}", e);
+            return;
+        }
+    };
+    let require = |role: Role| middleware::from_fn_with_state((authenticator.clone(), role), require_role);
+    // Mutating requests are recorded in the audit log, including the ones the role check refuses
+    let audited = |role: Role, action: &'static str, route: MethodRouter<(SharedModel, SharedDb)>| {
+        audit::audited(route, &authenticator, role, &shared_db, action)
+    };
+
+    // Group retained unknown faces in the background
+    if config.unknowns.retain {
+        spawn_clustering(shared_db.clone(), config.unknowns.clone(), cipher.clone());
+    }
+
+    // Files leaked by an earlier run; the temporary directory may be shared with other instances,
+    // so only files past the retention age are removed
+    let temp_max_age = Duration::from_secs(config.retention.temp_max_age_mins * 60);
+    match clean_temp_dir(&config.paths.temp_dir, temp_max_age, false) {
+        Ok(0) => {}
+        Ok(removed) => println!("Removed {} stale temporary files", removed),
+        Err(e) => eprintln!("Failed to clean {}: {}", config.paths.temp_dir, e),
+    }
+    if config.retention.sweep_interval_secs > 0 {
+        spawn_sweeper(shared_db.clone(), config.retention.clone(), config.paths.temp_dir.clone());
+    }
+
     // Configure CORS
+    let origins = &config.server.cors_allowed_origins;
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::from(Any)
+    } else {
+        AllowOrigin::list(origins.iter().filter_map(|origin| HeaderValue::from_str(origin).ok()))
+    };
     let cors = CorsLayer::new()
-        .allow_origin(Any)
+        .allow_origin(allow_origin)
         .allow_methods(Any)
         .allow_headers(Any);
 
+    let shared_config: SharedConfig = Arc::new(config);
+
+    // Tenant models are loaded on first use; the default tenant keeps the model above
+    let shared_registry: SharedRegistry = Arc::new(ModelRegistry::new(
+        shared_config.clone(),
+        shared_db.clone(),
+        shared_images.clone(),
+        shared_model.clone(),
+    ));
+
+    // Enrollment, thresholds and administration. Audited routes are added after the group's role
+    // check, as they check the role inside their audit record.
+    let admin_routes = Router::new()
+        .route("/admin/gallery-audit", get(gallery_audit))
+        .route("/admin/config", get(get_config))
+        .route("/admin/erasures", get(list_erasures))
+        .route("/admin/erasures/:id", get(get_erasure))
+        .route("/admin/audit", get(list_audit_events))
+        .route_layer(require(Role::Admin))
+        .route("/add-face", audited(Role::Admin, "enroll", post(add_face)))
+        .route("/calibration/threshold", audited(Role::Admin, "set_threshold", post(apply_threshold)))
+        .route("/unknowns/clusters/:id/enroll", audited(Role::Admin, "enroll_cluster", post(enroll_cluster)))
+        .route("/admin/reconcile", audited(Role::Admin, "reconcile", get(check_gallery).post(fix_gallery)))
+        .route("/admin/purge", audited(Role::Admin, "purge", post(purge_data)))
+        .route("/admin/users/:user_id/erase", audited(Role::Admin, "erase", post(erase_user)))
+        .route(
+            "/admin/api-keys",
+            audited(Role::Admin, "create_api_key", get(list_api_keys).post(create_api_key)),
+        )
+        .route("/admin/api-keys/:id", audited(Role::Admin, "revoke_api_key", delete(revoke_api_key)))
+        .route("/tenants", audited(Role::Admin, "create_tenant", get(list_tenants).post(create_tenant)))
+        .route("/tenants/:tenant/add-face", audited(Role::Admin, "enroll", post(add_tenant_face)));
+
+    // Detection and verification
+    let operator_routes = Router::new()
+        .route("/detect-face", post(detect_face))
+        .route("/faces/anonymize", post(anonymize_faces))
+        .route("/tenants/:tenant/detect-face", post(detect_tenant_face))
+        .route_layer(require(Role::Operator))
+        .route("/videos/analyze", audited(Role::Operator, "analyze_video", post(analyze_video)))
+        .route("/detections/:id/feedback", audited(Role::Operator, "feedback", post(submit_feedback)));
+
+    // Reading results
+    let viewer_routes = Router::new()
+        .route("/videos/:job_id", get(get_video_job))
+        .route("/videos/:job_id/output", get(get_video_output))
+        .route("/calibration/threshold", get(suggest_threshold))
+        .route("/detections", get(list_detections))
+        .route("/detections/:id", get(get_detection))
+        .route("/reports/attendance", get(attendance_report))
+        .route("/reports/attendance/summary", get(attendance_summary))
+        .route("/unknowns/clusters", get(list_clusters))
+        .route_layer(require(Role::Viewer));
+
     // Build router with face recognition endpoints
     let app = Router::new()
         .route("/", get(|| async { "Face Recognition API - Visit /swagger-ui for documentation" }))
-        .route("/add-face", post(add_face))
-        .route("/detect-face", post(detect_face))
+        .merge(admin_routes)
+        .merge(operator_routes)
+        .merge(viewer_routes)
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
+        .layer(Extension(shared_liveness))
+        .layer(Extension(shared_config))
+        .layer(Extension(shared_registry))
+        .layer(Extension(shared_images))
         .layer(cors)
         .with_state((shared_model, shared_db));
 
@@ -99,7 +405,35 @@ async fn main() {
     println!("Endpoints:");
     println!("  GET  /swagger-ui         - Swagger UI documentation");
     println!("  POST /add-face           - Add face data (multipart: id, photos)");
-    println!("  POST /detect-face        - Detect face (multipart: photo)");
+    println!("  POST /detect-face        - Detect face (multipart: photo, ?annotate=true for JPEG)");
+    println!("  POST /faces/anonymize    - Blur/pixelate/box faces (multipart: photo, method, redact, allow)");
+    println!("  POST /videos/analyze     - Analyze video file or stream (json: source, frame_stride, annotate)");
+    println!("  GET  /videos/:job_id     - Video analysis status and appearances");
+    println!("  GET  /videos/:job_id/output - Annotated output video");
+    println!("  GET  /detections         - Detection history (?user_id, matched, confidence, time, source, cursor)");
+    println!("  GET  /detections/:id     - A single logged detection");
+    println!("  POST /detections/:id/feedback - Mark a detection correct/incorrect/unknown_person");
+    println!("  GET  /calibration/threshold - Suggest a match threshold for ?target_far");
+    println!("  POST /calibration/threshold - Apply the suggested match threshold");
+    println!("  GET  /reports/attendance - Daily first-in/last-out and absentees (?from, to, timezone, format=csv)");
+    println!("  GET  /reports/attendance/summary - Days present/absent and presence per user");
+    println!("  GET  /unknowns/clusters  - Groups of repeated unknown faces");
+    println!("  POST /unknowns/clusters/:id/enroll - Enroll a cluster as a new user (json: user_id)");
+    println!("  GET  /admin/gallery-audit - Find misfiled photos and duplicate identities");
+    println!("  GET  /admin/reconcile    - Compare face_images rows with the image store (?tenant)");
+    println!("  POST /admin/reconcile    - Fix orphaned/missing images and empty users (?tenant, ?orphans=adopt|delete)");
+    println!("  POST /admin/purge        - Apply the retention policy now (?dry_run=true to only report)");
+    println!("  POST /admin/users/:user_id/erase - Erase a user's biometric data everywhere (?tenant, ?detections=pseudonymize|delete)");
+    println!("  GET  /admin/erasures     - Erasure certificates (?tenant)");
+    println!("  GET  /admin/erasures/:id - A single erasure certificate");
+    println!("  GET  /admin/audit        - Audit log of changes (?actor, action, target, outcome, request_id, time, cursor)");
+    println!("  GET  /admin/config       - Effective configuration with secrets hidden");
+    println!("  GET  /admin/api-keys     - List API keys");
+    println!("  POST /admin/api-keys     - Create an API key (json: name, role)");
+    println!("  DELETE /admin/api-keys/:id - Revoke an API key");
+    println!("  GET  /tenants            - Tenants with their user and image counts");
+    println!("  POST /tenants/:tenant/add-face - Add face data to a tenant's gallery");
+    println!("  POST /tenants/:tenant/detect-face - Detect face among a tenant's users");
 
     // Run server
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
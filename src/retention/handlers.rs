@@ -0,0 +1,58 @@
+use axum::{
+    extract::{Extension, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+
+use super::{purge, PurgeReport};
+use crate::audit::AuditContext;
+use crate::config::SharedConfig;
+use crate::face_recognition::handlers::{ApiResponse, SharedDb, SharedModel};
+
+/// Query parameters for a purge
+#[derive(Debug, Deserialize)]
+pub struct PurgeQuery {
+    /// Only report what would be removed
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Apply the retention policy now
+///
+/// Removes detection logs, retained unknown faces and temporary files that the `retention`
+/// settings no longer keep, as the background sweeper does. With `dry_run=true` nothing is
+/// removed and the report counts what would be.
+#[utoipa::path(
+    post,
+    path = "/admin/purge",
+    params(("dry_run" = Option<bool>, Query, description = "Only count what would be removed")),
+    responses(
+        (status = 200, description = "Purge report", body = ApiResponse<PurgeReport>),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Admin"
+)]
+pub async fn purge_data(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+    Extension(config): Extension<SharedConfig>,
+    Extension(audit): Extension<AuditContext>,
+    Query(query): Query<PurgeQuery>,
+) -> Result<Json<ApiResponse<PurgeReport>>, (StatusCode, String)> {
+    let report = purge(&db, &config.retention, &config.paths.temp_dir, query.dry_run)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Purge failed: {}", e)))?;
+
+    let message = if query.dry_run {
+        format!("Would remove {} items", report.total())
+    } else {
+        format!("Removed {} items", report.total())
+    };
+    audit.detail(&message);
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message,
+        data: Some(report),
+    }))
+}
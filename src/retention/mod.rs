@@ -0,0 +1,261 @@
+pub mod handlers;
+
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::time::{Duration, SystemTime};
+use utoipa::ToSchema;
+
+use crate::database::{Database, DETECTED_AT_FORMAT};
+use crate::face_recognition::handlers::SharedDb;
+
+/// How long detection logs, retained unknown faces and temporary files are kept. A limit of 0
+/// keeps data forever.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    /// Days a detection is kept
+    pub detection_max_age_days: u32,
+    /// Newest detections kept per tenant
+    pub detection_max_rows: u64,
+    /// Days a retained unknown face and its crop are kept
+    pub unknown_max_age_days: u32,
+    /// Newest unknown faces kept
+    pub unknown_max_rows: u64,
+    /// Minutes after which a file left in `paths.temp_dir` is considered leaked
+    pub temp_max_age_mins: u64,
+    /// Seconds between background purges; 0 only purges on request
+    pub sweep_interval_secs: u64,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            detection_max_age_days: 0,
+            detection_max_rows: 0,
+            unknown_max_age_days: 0,
+            unknown_max_rows: 0,
+            temp_max_age_mins: 60,
+            sweep_interval_secs: 3600,
+        }
+    }
+}
+
+/// What a purge removed, or with `dry_run` would remove
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PurgeReport {
+    dry_run: bool,
+    /// Detection logs past their age or beyond the row limit of their tenant
+    pub detections: u64,
+    /// Retained unknown faces, each with its crop
+    pub unknown_faces: u64,
+    /// Files in the temporary directory older than `temp_max_age_mins`
+    pub temp_files: u64,
+    generated_at: String,
+}
+
+impl PurgeReport {
+    pub fn total(&self) -> u64 {
+        self.detections + self.unknown_faces + self.temp_files
+    }
+}
+
+/// Remove everything the retention policy no longer keeps
+pub async fn purge(db: &Database, config: &RetentionConfig, temp_dir: &str, dry_run: bool) -> Result<PurgeReport> {
+    purge_at(db, config, temp_dir, dry_run, Utc::now()).await
+}
+
+// `purge` with rows aged relative to `now`
+async fn purge_at(
+    db: &Database,
+    config: &RetentionConfig,
+    temp_dir: &str,
+    dry_run: bool,
+    now: DateTime<Utc>,
+) -> Result<PurgeReport> {
+    let before = (config.detection_max_age_days > 0)
+        .then(|| (now - ChronoDuration::days(config.detection_max_age_days as i64)).format(DETECTED_AT_FORMAT).to_string());
+    let mut detections = 0;
+    if before.is_some() || config.detection_max_rows > 0 {
+        for tenant in db.get_detection_tenants().await? {
+            let up_to = match config.detection_max_rows {
+                0 => None,
+                keep => db.get_detection_overflow_id(&tenant, keep as i64).await?,
+            };
+            detections += db.purge_detections(&tenant, before.as_deref(), up_to, dry_run).await?;
+        }
+    }
+
+    // Unknown faces are stored with RFC 3339 times
+    let before = (config.unknown_max_age_days > 0)
+        .then(|| (now - ChronoDuration::days(config.unknown_max_age_days as i64)).to_rfc3339());
+    let up_to = match config.unknown_max_rows {
+        0 => None,
+        keep => db.get_unknown_face_overflow_id(keep as i64).await?,
+    };
+    let mut unknown_faces = 0;
+    if before.is_some() || up_to.is_some() {
+        for (id, image_path) in db.get_expired_unknown_faces(before.as_deref(), up_to).await? {
+            if !dry_run {
+                remove_file(&image_path)?;
+                db.delete_unknown_face(id).await?;
+            }
+            unknown_faces += 1;
+        }
+        if !dry_run && unknown_faces > 0 {
+            db.delete_empty_unknown_clusters().await?;
+        }
+    }
+
+    let temp_files = clean_temp_dir(temp_dir, Duration::from_secs(config.temp_max_age_mins * 60), dry_run)?;
+
+    Ok(PurgeReport {
+        dry_run,
+        detections,
+        unknown_faces,
+        temp_files,
+        generated_at: now.to_rfc3339(),
+    })
+}
+
+/// Remove files in `dir` last modified more than `max_age` ago; a missing directory is empty
+pub fn clean_temp_dir(dir: &str, max_age: Duration, dry_run: bool) -> Result<u64> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+
+    let now = SystemTime::now();
+    let mut removed = 0;
+    for entry in entries {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .unwrap_or_default();
+        if !metadata.is_file() || age < max_age {
+            continue;
+        }
+
+        if !dry_run {
+            remove_file(&entry.path().to_string_lossy())?;
+        }
+        removed += 1;
+    }
+
+    Ok(removed)
+}
+
+/// Purge every `sweep_interval_secs`
+pub fn spawn_sweeper(db: SharedDb, config: RetentionConfig, temp_dir: String) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(config.sweep_interval_secs.max(1)));
+        loop {
+            interval.tick().await;
+            match purge(&db, &config, &temp_dir, false).await {
+                Ok(report) if report.total() == 0 => {}
+                Ok(report) => println!(
+                    "Purged {} detections, {} unknown faces and {} temporary files",
+                    report.detections, report.unknown_faces, report.temp_files
+                ),
+                Err(e) => eprintln!("Retention purge failed: {}", e),
+            }
+        }
+    });
+}
+
+// Removing a file that is already gone is not an error
+fn remove_file(path: &str) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DatabaseConfig;
+    use std::fs::File;
+    use std::path::Path;
+    use uuid::Uuid;
+
+    const TENANT: &str = "acme";
+
+    fn temp_dir() -> String {
+        let dir = std::env::temp_dir().join(format!("retention-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.to_string_lossy().into_owned()
+    }
+
+    fn aged(path: &str, age: Duration) {
+        File::create(path).unwrap().set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    #[tokio::test]
+    async fn purges_rows_past_their_age() {
+        let dir = temp_dir();
+        let config = DatabaseConfig {
+            url: "sqlite::memory:".to_string(),
+            max_connections: 1,
+        };
+        let db = Database::new(&config).await.unwrap();
+        let crop = format!("{}/crop.jpg", dir);
+        fs::write(&crop, b"crop").unwrap();
+        let inserted = Utc::now();
+        db.log_detection(TENANT, Some("alice"), Some(40.0), None, None).await.unwrap();
+        db.insert_unknown_face(&crop, b"features", None, Some(90.0)).await.unwrap();
+
+        let config = RetentionConfig {
+            detection_max_age_days: 30,
+            unknown_max_age_days: 30,
+            ..RetentionConfig::default()
+        };
+        let temp = format!("{}/temp", dir);
+
+        // A minute short of the age both rows are kept. The cutoffs compare as text, so a cutoff
+        // in the other table's format would be off by most of a day.
+        let early = inserted + ChronoDuration::days(30) - ChronoDuration::minutes(1);
+        let report = purge_at(&db, &config, &temp, false, early).await.unwrap();
+        assert_eq!((report.detections, report.unknown_faces), (0, 0));
+
+        let late = inserted + ChronoDuration::days(30) + ChronoDuration::minutes(1);
+        let report = purge_at(&db, &config, &temp, true, late).await.unwrap();
+        assert_eq!((report.detections, report.unknown_faces), (1, 1));
+        assert!(Path::new(&crop).exists());
+
+        let report = purge_at(&db, &config, &temp, false, late).await.unwrap();
+        assert_eq!((report.detections, report.unknown_faces), (1, 1));
+        assert!(!Path::new(&crop).exists());
+        assert_eq!(db.get_expired_unknown_faces(None, Some(i64::MAX)).await.unwrap(), []);
+        assert_eq!(purge_at(&db, &config, &temp, true, late).await.unwrap().total(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cleans_files_past_the_max_age() {
+        let dir = temp_dir();
+        aged(&format!("{}/leaked.jpg", dir), Duration::from_secs(2 * 3600));
+        aged(&format!("{}/in-flight.jpg", dir), Duration::ZERO);
+        fs::create_dir(format!("{}/old-dir", dir)).unwrap();
+        let max_age = Duration::from_secs(3600);
+
+        assert_eq!(clean_temp_dir(&dir, max_age, true).unwrap(), 1);
+        assert!(Path::new(&format!("{}/leaked.jpg", dir)).exists());
+
+        assert_eq!(clean_temp_dir(&dir, max_age, false).unwrap(), 1);
+        assert!(!Path::new(&format!("{}/leaked.jpg", dir)).exists());
+        assert!(Path::new(&format!("{}/in-flight.jpg", dir)).exists());
+        assert!(Path::new(&format!("{}/old-dir", dir)).exists());
+
+        assert_eq!(clean_temp_dir(&format!("{}/missing", dir), max_age, false).unwrap(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use super::check_tenant_id;
+use crate::audit::AuditContext;
+use crate::database::TenantSummary;
+use crate::face_recognition::handlers::{ApiResponse, SharedDb, SharedModel};
+
+/// Request body for registering a tenant
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateTenantRequest {
+    /// 1-64 lowercase letters, digits, `-` or `_`
+    tenant_id: String,
+}
+
+/// List tenants
+///
+/// Returns every registered tenant, with the number of users and images in its gallery.
+#[utoipa::path(
+    get,
+    path = "/tenants",
+    responses(
+        (status = 200, description = "Tenants", body = ApiResponse<Vec<TenantSummary>>),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Tenants"
+)]
+pub async fn list_tenants(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+) -> Result<Json<ApiResponse<Vec<TenantSummary>>>, (StatusCode, String)> {
+    let tenants = db
+        .get_tenants()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Found {} tenants", tenants.len()),
+        data: Some(tenants),
+    }))
+}
+
+/// Register a tenant
+///
+/// Tenant routes and the `tenant` parameter only accept registered tenants, so a tenant has to
+/// be created before photos can be enrolled in it.
+#[utoipa::path(
+    post,
+    path = "/tenants",
+    request_body = CreateTenantRequest,
+    responses(
+        (status = 200, description = "Tenant created", body = ApiResponse<TenantSummary>),
+        (status = 400, description = "Invalid tenant ID"),
+        (status = 409, description = "Tenant already exists"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Tenants"
+)]
+pub async fn create_tenant(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+    Extension(audit): Extension<AuditContext>,
+    Json(request): Json<CreateTenantRequest>,
+) -> Result<Json<ApiResponse<TenantSummary>>, (StatusCode, String)> {
+    let tenant = request.tenant_id;
+    audit.target(&tenant);
+    check_tenant_id(&tenant).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    let created = db
+        .create_tenant(&tenant)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    if !created {
+        return Err((StatusCode::CONFLICT, format!("Tenant already exists: {}", tenant)));
+    }
+    println!("Tenant {} created", tenant);
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Created tenant {}", tenant),
+        data: Some(TenantSummary {
+            tenant_id: tenant,
+            users: 0,
+            images: 0,
+        }),
+    }))
+}
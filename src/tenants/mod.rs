@@ -0,0 +1,67 @@
+pub mod handlers;
+pub mod registry;
+
+use anyhow::Result;
+
+use crate::config::Config;
+
+/// Tenant served by the routes without a `/tenants/{tenant}` prefix. It keeps the
+/// top-level `paths.knowledge_dir` and `paths.model_path`.
+pub const DEFAULT_TENANT: &str = "default";
+
+/// Where a tenant's gallery and trained model live
+#[derive(Debug, Clone)]
+pub struct TenantPaths {
+    pub knowledge_dir: String,
+    pub model_path: String,
+}
+
+impl TenantPaths {
+    pub fn new(config: &Config, tenant: &str) -> Self {
+        if tenant == DEFAULT_TENANT {
+            return Self {
+                knowledge_dir: config.paths.knowledge_dir.clone(),
+                model_path: config.paths.model_path.clone(),
+            };
+        }
+
+        let tenant_dir = format!("{}/{}", config.paths.tenants_dir, tenant);
+        Self {
+            knowledge_dir: format!("{}/knowledge", tenant_dir),
+            model_path: format!("{}/model.yml", tenant_dir),
+        }
+    }
+
+    /// Image store key, and `face_images.image_path`, of an enrollment photo
+    pub fn image_key(&self, user_id: &str, file_name: &str) -> String {
+        format!("{}/{}/{}", self.knowledge_dir, user_id, file_name)
+    }
+}
+
+// User IDs double as folder names in the image store; anything else could reach other users'
+// images
+pub fn check_user_id(user_id: &str) -> Result<()> {
+    if user_id.is_empty() || user_id.contains(['/', '\\']) || user_id.starts_with('.') {
+        return Err(anyhow::anyhow!("Invalid user ID: {}", user_id));
+    }
+
+    Ok(())
+}
+
+// Tenant IDs double as folder names under `paths.tenants_dir`
+pub fn check_tenant_id(tenant: &str) -> Result<()> {
+    let valid = !tenant.is_empty()
+        && tenant.len() <= 64
+        && tenant
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+
+    if !valid {
+        return Err(anyhow::anyhow!(
+            "Invalid tenant ID '{}': use 1-64 lowercase letters, digits, '-' or '_'",
+            tenant
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{Mutex, OnceCell};
+
+use super::{TenantPaths, DEFAULT_TENANT};
+use crate::config::SharedConfig;
+use crate::detections::apply_stored_threshold;
+use crate::face_recognition::handlers::{SharedDb, SharedModel};
+use crate::face_recognition::FaceRecognitionModel;
+use crate::images::{Gallery, SharedImageStore};
+
+// Tenant models kept in memory at once. Beyond this the least recently used model that no
+// request holds is dropped; its saved model is loaded again on next use.
+const MAX_LOADED_MODELS: usize = 32;
+
+// A tenant's model slot. The cell is filled once by whichever request gets there first; the
+// others wait for it without holding the map lock.
+struct LoadedModel {
+    cell: Arc<OnceCell<SharedModel>>,
+    last_used: Instant,
+}
+
+impl LoadedModel {
+    // Nothing but the map refers to the slot or its model
+    fn is_idle(&self) -> bool {
+        Arc::strong_count(&self.cell) == 1 && self.cell.get().is_none_or(|model| Arc::strong_count(model) == 1)
+    }
+}
+
+/// One trained model per tenant. A tenant's model is only ever trained on that tenant's
+/// gallery, so its probes cannot match identities of another tenant.
+pub struct ModelRegistry {
+    config: SharedConfig,
+    db: SharedDb,
+    images: SharedImageStore,
+    default_model: SharedModel,
+    models: Mutex<HashMap<String, LoadedModel>>,
+}
+
+pub type SharedRegistry = Arc<ModelRegistry>;
+
+impl ModelRegistry {
+    pub fn new(config: SharedConfig, db: SharedDb, images: SharedImageStore, default_model: SharedModel) -> Self {
+        Self {
+            config,
+            db,
+            images,
+            default_model,
+            models: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The database shared by all tenants; rows are scoped by `tenant_id`
+    pub fn db(&self) -> &SharedDb {
+        &self.db
+    }
+
+    /// The tenant's model, loaded from its saved model (or trained from its gallery) on first
+    /// use. `None` when the tenant is not registered.
+    pub async fn model(&self, tenant: &str) -> Result<Option<SharedModel>> {
+        if tenant == DEFAULT_TENANT {
+            return Ok(Some(self.default_model.clone()));
+        }
+
+        let loaded = self.models.lock().await.contains_key(tenant);
+        if !loaded && !self.db.tenant_exists(tenant).await? {
+            return Ok(None);
+        }
+
+        let cell = {
+            let mut models = self.models.lock().await;
+            let entry = models.entry(tenant.to_string()).or_insert_with(|| LoadedModel {
+                cell: Arc::new(OnceCell::new()),
+                last_used: Instant::now(),
+            });
+            entry.last_used = Instant::now();
+            let cell = entry.cell.clone();
+            evict_idle(&mut models);
+            cell
+        };
+
+        let model = cell.get_or_try_init(|| self.load(tenant)).await?;
+
+        Ok(Some(model.clone()))
+    }
+
+    async fn load(&self, tenant: &str) -> Result<SharedModel> {
+        let paths = TenantPaths::new(&self.config, tenant);
+        let mut model = FaceRecognitionModel::with_model_path(&self.config, &paths.model_path)?;
+        if Path::new(&paths.model_path).exists() {
+            model.load(&paths.model_path)?;
+        } else {
+            let gallery = Gallery::load(&self.db, self.images.as_ref(), tenant).await?;
+            if !gallery.users.is_empty() {
+                model.train(&gallery)?;
+            }
+        }
+        apply_stored_threshold(&self.db, tenant, &mut model).await;
+        println!("Loaded model for tenant {}", tenant);
+
+        Ok(Arc::new(Mutex::new(model)))
+    }
+}
+
+// Drop the least recently used idle models until at most `MAX_LOADED_MODELS` are left
+fn evict_idle(models: &mut HashMap<String, LoadedModel>) {
+    while models.len() > MAX_LOADED_MODELS {
+        let Some(tenant) = models
+            .iter()
+            .filter(|(_, model)| model.is_idle())
+            .min_by_key(|(_, model)| model.last_used)
+            .map(|(tenant, _)| tenant.clone())
+        else {
+            return;
+        };
+        models.remove(&tenant);
+        println!("Unloaded model for tenant {}", tenant);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn evicts_least_recently_used_idle_models() {
+        let start = Instant::now();
+        let mut models: HashMap<String, LoadedModel> = (0..=MAX_LOADED_MODELS + 1)
+            .map(|i| {
+                let model = LoadedModel {
+                    cell: Arc::new(OnceCell::new()),
+                    last_used: start + Duration::from_secs(i as u64),
+                };
+                (format!("tenant-{}", i), model)
+            })
+            .collect();
+        // The oldest slot is in use by a request, so the next two oldest go instead
+        let in_use = models["tenant-0"].cell.clone();
+
+        evict_idle(&mut models);
+
+        assert_eq!(models.len(), MAX_LOADED_MODELS);
+        assert!(models.contains_key("tenant-0"));
+        assert!(!models.contains_key("tenant-1"));
+        assert!(!models.contains_key("tenant-2"));
+        assert!(models.contains_key("tenant-3"));
+        drop(in_use);
+    }
+}
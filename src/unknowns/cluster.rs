@@ -0,0 +1,219 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use super::features::{decode_features, distance};
+use super::UnknownsConfig;
+use crate::database::UnknownFaceFeatures;
+use crate::encryption::SharedCipher;
+use crate::face_recognition::handlers::SharedDb;
+
+/// Where a face should go after a clustering pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Target {
+    Existing(i64),
+    /// A cluster created in this pass, numbered from 0
+    New(usize),
+}
+
+/// Assign every unclustered face to the cluster of its nearest neighbour within `max_distance`,
+/// or start a new cluster with that neighbour if it is unclustered too. Existing assignments are
+/// never changed, so cluster IDs stay stable between passes.
+pub fn plan(faces: &[UnknownFaceFeatures], max_distance: f64) -> Vec<(i64, Target)> {
+    let descriptors: Vec<Vec<f64>> = faces.iter().map(|face| decode_features(&face.features)).collect();
+    let mut targets: Vec<Option<Target>> = faces.iter().map(|face| face.cluster_id.map(Target::Existing)).collect();
+    let mut new_clusters = 0;
+    let mut assignments = Vec::new();
+
+    for i in 0..faces.len() {
+        if targets[i].is_some() {
+            continue;
+        }
+
+        let nearest = (0..faces.len())
+            .filter(|&j| j != i && descriptors[j].len() == descriptors[i].len())
+            .map(|j| (j, distance(&descriptors[i], &descriptors[j])))
+            .filter(|(_, d)| *d <= max_distance)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+
+        let Some((j, _)) = nearest else {
+            continue;
+        };
+
+        let target = match targets[j] {
+            Some(target) => target,
+            None => {
+                let target = Target::New(new_clusters);
+                new_clusters += 1;
+                targets[j] = Some(target);
+                assignments.push((faces[j].id, target));
+                target
+            }
+        };
+        targets[i] = Some(target);
+        assignments.push((faces[i].id, target));
+    }
+
+    assignments
+}
+
+/// Run one clustering pass over the retained unknown faces. Returns the number of faces assigned.
+pub async fn run_clustering(db: &SharedDb, cipher: &SharedCipher, max_distance: f64) -> Result<usize> {
+    // A face that cannot be decrypted (its key is no longer configured) is left out of the
+    // pass rather than stopping clustering for everyone else
+    let faces: Vec<UnknownFaceFeatures> = db
+        .get_unknown_face_features()
+        .await?
+        .into_iter()
+        .filter_map(|mut face| match cipher.decrypt(&face.features) {
+            Ok(features) => {
+                face.features = features;
+                Some(face)
+            }
+            Err(e) => {
+                eprintln!("Skipping unknown face {} in clustering: {}", face.id, e);
+                None
+            }
+        })
+        .collect();
+    let assignments = tokio::task::spawn_blocking(move || plan(&faces, max_distance)).await?;
+
+    let mut created: HashMap<usize, i64> = HashMap::new();
+    for (face_id, target) in &assignments {
+        let cluster_id = match *target {
+            Target::Existing(id) => id,
+            Target::New(n) => match created.get(&n) {
+                Some(id) => *id,
+                None => {
+                    let id = db.create_unknown_cluster().await?;
+                    created.insert(n, id);
+                    id
+                }
+            },
+        };
+        db.assign_unknown_face(*face_id, cluster_id).await?;
+    }
+
+    Ok(assignments.len())
+}
+
+/// Cluster retained unknown faces every `cluster_interval_secs`
+pub fn spawn_clustering(db: SharedDb, config: UnknownsConfig, cipher: SharedCipher) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(config.cluster_interval_secs.max(1)));
+        loop {
+            interval.tick().await;
+            match run_clustering(&db, &cipher, config.cluster_distance).await {
+                Ok(0) => {}
+                Ok(assigned) => println!("Clustered {} unknown faces", assigned),
+                Err(e) => eprintln!("Unknown face clustering failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DatabaseConfig;
+    use crate::database::Database;
+    use crate::encryption::{Cipher, EncryptionConfig};
+    use crate::unknowns::features::encode_features;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use std::sync::Arc;
+
+    const SAME: [f64; 4] = [1.0, 0.0, 0.0, 0.0];
+    // 0.105 from SAME
+    const CLOSE: [f64; 4] = [0.9, 0.1, 0.0, 0.0];
+    // 2.0 from both
+    const FAR: [f64; 4] = [0.0, 0.0, 1.0, 0.0];
+
+    fn face(id: i64, cluster_id: Option<i64>, descriptor: &[f64]) -> UnknownFaceFeatures {
+        UnknownFaceFeatures {
+            id,
+            cluster_id,
+            features: encode_features(descriptor),
+        }
+    }
+
+    #[test]
+    fn a_close_pair_starts_a_new_cluster() {
+        let faces = [face(1, None, &SAME), face(2, None, &FAR), face(3, None, &CLOSE), face(4, None, &SAME)];
+
+        // Face 1's nearest is its duplicate 4; 3 then joins them. 2 has no neighbour.
+        assert_eq!(
+            plan(&faces, 0.5),
+            [(4, Target::New(0)), (1, Target::New(0)), (3, Target::New(0))]
+        );
+        assert_eq!(plan(&faces, 0.05), [(4, Target::New(0)), (1, Target::New(0))]);
+        assert!(plan(&faces[..2], 0.5).is_empty());
+    }
+
+    #[test]
+    fn faces_join_the_nearest_existing_cluster() {
+        let faces = [face(1, Some(7), &CLOSE), face(2, Some(9), &SAME), face(3, None, &SAME), face(4, None, &FAR)];
+
+        assert_eq!(plan(&faces, 0.5), [(3, Target::Existing(9))]);
+        assert!(plan(&faces, 0.05).iter().all(|(_, target)| *target == Target::Existing(9)));
+
+        // Descriptors of another length, from an older format, are never compared
+        let faces = [face(1, Some(7), &SAME), face(2, None, &[1.0, 0.0])];
+        assert!(plan(&faces, 2.0).is_empty());
+    }
+
+    #[test]
+    fn cluster_ids_stay_stable_across_passes() {
+        let mut faces = vec![face(1, None, &SAME), face(2, None, &SAME), face(3, None, &FAR), face(4, None, &FAR)];
+        let first = plan(&faces, 0.5);
+        assert_eq!(
+            first,
+            [(2, Target::New(0)), (1, Target::New(0)), (4, Target::New(1)), (3, Target::New(1))]
+        );
+
+        // Apply the pass as run_clustering would, then add a face close to the first cluster
+        for (id, target) in first {
+            let Target::New(n) = target else { unreachable!() };
+            faces.iter_mut().find(|face| face.id == id).unwrap().cluster_id = Some(100 + n as i64);
+        }
+        faces.push(face(5, None, &CLOSE));
+
+        assert_eq!(plan(&faces, 0.5), [(5, Target::Existing(100))]);
+        faces[4].cluster_id = Some(100);
+        assert!(plan(&faces, 0.5).is_empty());
+        assert!(plan(&faces, 2.0).is_empty());
+    }
+
+    #[tokio::test]
+    async fn faces_that_cannot_be_decrypted_are_skipped() {
+        let config = DatabaseConfig {
+            url: "sqlite::memory:".to_string(),
+            max_connections: 1,
+        };
+        let db: SharedDb = Arc::new(Database::new(&config).await.unwrap());
+        let cipher = |key: u8| {
+            Cipher::open(&EncryptionConfig {
+                key: Some(STANDARD.encode([key; 32])),
+                ..Default::default()
+            })
+            .unwrap()
+        };
+        let (current, retired) = (cipher(1), cipher(2));
+
+        let first = db.insert_unknown_face("a.jpg", &current.encrypt(&encode_features(&SAME)).unwrap(), None, None).await.unwrap();
+        let lost = db.insert_unknown_face("b.jpg", &retired.encrypt(&encode_features(&SAME)).unwrap(), None, None).await.unwrap();
+        let second = db.insert_unknown_face("c.jpg", &current.encrypt(&encode_features(&CLOSE)).unwrap(), None, None).await.unwrap();
+
+        assert_eq!(run_clustering(&db, &Arc::new(current), 0.5).await.unwrap(), 2);
+
+        let clusters: HashMap<i64, Option<i64>> = db
+            .get_unknown_face_features()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|face| (face.id, face.cluster_id))
+            .collect();
+        assert!(clusters[&first].is_some());
+        assert_eq!(clusters[&first], clusters[&second]);
+        assert_eq!(clusters[&lost], None);
+    }
+}
@@ -0,0 +1,53 @@
+use anyhow::Result;
+use opencv::{
+    core::{Mat, Rect},
+    prelude::*,
+};
+
+use crate::liveness::passive::{lbp_histogram, LBP_BINS};
+
+// The face is split into GRID x GRID cells with one LBP histogram each, as LBPH does
+const GRID: i32 = 4;
+
+/// Concatenated per-cell LBP histograms of a cropped grayscale face
+pub fn face_descriptor(face: &Mat) -> Result<Vec<f64>> {
+    let cell_width = face.cols() / GRID;
+    let cell_height = face.rows() / GRID;
+
+    let mut descriptor = Vec::with_capacity((GRID * GRID) as usize * LBP_BINS);
+    for row in 0..GRID {
+        for col in 0..GRID {
+            let cell = Mat::roi(face, Rect::new(col * cell_width, row * cell_height, cell_width, cell_height))?;
+            descriptor.extend(lbp_histogram(&cell.try_clone()?)?);
+        }
+    }
+
+    Ok(descriptor)
+}
+
+/// Mean chi-square distance between matching cells; 0 for identical faces, at most 2
+pub fn distance(a: &[f64], b: &[f64]) -> f64 {
+    let chi_square: f64 = a
+        .iter()
+        .zip(b)
+        .filter(|(x, y)| *x + *y > 0.0)
+        .map(|(x, y)| (x - y).powi(2) / (x + y))
+        .sum();
+
+    chi_square / (a.len() / LBP_BINS).max(1) as f64
+}
+
+/// Store a descriptor as little-endian f32 values
+pub fn encode_features(descriptor: &[f64]) -> Vec<u8> {
+    descriptor
+        .iter()
+        .flat_map(|value| (*value as f32).to_le_bytes())
+        .collect()
+}
+
+pub fn decode_features(bytes: &[u8]) -> Vec<f64> {
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as f64)
+        .collect()
+}
@@ -0,0 +1,189 @@
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use utoipa::ToSchema;
+
+use crate::audit::AuditContext;
+use crate::config::SharedConfig;
+use crate::database::{UnknownCluster, UnknownFace};
+use crate::encryption::read_file;
+use crate::face_recognition::augment::TrainingStats;
+use crate::face_recognition::handlers::{ApiResponse, SharedDb, SharedModel};
+use crate::images::{Gallery, SharedImageStore};
+use crate::tenants::{check_user_id, TenantPaths, DEFAULT_TENANT};
+
+/// An unknown-face cluster together with its faces
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UnknownClusterResponse {
+    #[serde(flatten)]
+    cluster: UnknownCluster,
+    faces: Vec<UnknownFace>,
+}
+
+/// Query parameters for listing unknown-face clusters
+#[derive(Debug, Deserialize)]
+pub struct ClustersQuery {
+    /// Overrides `unknowns.min_cluster_size`
+    min_size: Option<i64>,
+}
+
+/// Request body for enrolling a cluster
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct EnrollClusterRequest {
+    /// ID of the new user
+    user_id: String,
+}
+
+/// Response for enrolling a cluster
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EnrollClusterResponse {
+    user_id: String,
+    /// Number of cluster images copied into the user's training folder
+    images_saved: usize,
+    /// Summary of the retraining triggered by the enrollment
+    training: TrainingStats,
+}
+
+/// List unknown-face clusters
+///
+/// Faces that matched no enrolled user are kept when `unknowns.retain` is enabled and grouped
+/// periodically; each cluster is likely one repeated unknown visitor.
+#[utoipa::path(
+    get,
+    path = "/unknowns/clusters",
+    params(("min_size" = Option<i64>, Query, description = "Only list clusters with at least this many faces")),
+    responses(
+        (status = 200, description = "Clusters, largest first", body = ApiResponse<Vec<UnknownClusterResponse>>),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Unknown Faces"
+)]
+pub async fn list_clusters(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+    Extension(config): Extension<SharedConfig>,
+    Query(query): Query<ClustersQuery>,
+) -> Result<Json<ApiResponse<Vec<UnknownClusterResponse>>>, (StatusCode, String)> {
+    let clusters = db
+        .get_unknown_clusters(query.min_size.unwrap_or(config.unknowns.min_cluster_size))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let mut response = Vec::with_capacity(clusters.len());
+    for cluster in clusters {
+        let faces = db
+            .get_unknown_cluster_faces(cluster.id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+        response.push(UnknownClusterResponse { cluster, faces });
+    }
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Found {} clusters", response.len()),
+        data: Some(response),
+    }))
+}
+
+/// Enroll an unknown-face cluster as a new user
+///
+/// Copies the cluster's face crops into the user's folder in the knowledge directory, registers the user, removes
+/// the cluster and retrains the model.
+#[utoipa::path(
+    post,
+    path = "/unknowns/clusters/{id}/enroll",
+    params(("id" = i64, Path, description = "Cluster ID")),
+    request_body = EnrollClusterRequest,
+    responses(
+        (status = 200, description = "Cluster enrolled", body = ApiResponse<EnrollClusterResponse>),
+        (status = 400, description = "Invalid user ID"),
+        (status = 404, description = "Cluster not found"),
+        (status = 409, description = "User already exists"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Unknown Faces"
+)]
+pub async fn enroll_cluster(
+    State((model, db)): State<(SharedModel, SharedDb)>,
+    Extension(config): Extension<SharedConfig>,
+    Extension(images): Extension<SharedImageStore>,
+    Extension(audit): Extension<AuditContext>,
+    Path(id): Path<i64>,
+    Json(request): Json<EnrollClusterRequest>,
+) -> Result<Json<ApiResponse<EnrollClusterResponse>>, (StatusCode, String)> {
+    let user_id = request.user_id.trim().to_string();
+    audit.target(format!("{}/{}", DEFAULT_TENANT, user_id));
+    audit.detail(format!("unknown cluster {}", id));
+    check_user_id(&user_id).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    let faces = db
+        .get_unknown_cluster_faces(id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    if faces.is_empty() {
+        return Err((StatusCode::NOT_FOUND, format!("Cluster not found: {}", id)));
+    }
+
+    let existing = db
+        .get_user_images(DEFAULT_TENANT, &user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    if !existing.is_empty() {
+        return Err((StatusCode::CONFLICT, format!("User already exists: {}", user_id)));
+    }
+
+    let paths = TenantPaths::new(&config, DEFAULT_TENANT);
+    let cipher = model.lock().await.cipher().clone();
+    let mut saved_paths = Vec::with_capacity(faces.len());
+    for face in &faces {
+        let file_path = paths.image_key(&user_id, &format!("unknown_{}.jpg", face.id));
+        let data = read_file(&cipher, &face.image_path)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read {}: {}", face.image_path, e)))?;
+        images
+            .put(&file_path, &data)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Image store error: {}", e)))?;
+        saved_paths.push(file_path);
+    }
+
+    db.upsert_user(DEFAULT_TENANT, &user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    for image_path in &saved_paths {
+        db.insert_face_image(DEFAULT_TENANT, &user_id, image_path)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    }
+
+    db.delete_unknown_cluster(id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    for face in &faces {
+        let _ = fs::remove_file(&face.image_path);
+    }
+
+    println!("Enrolled unknown cluster {} as user {} ({} images)", id, user_id, saved_paths.len());
+
+    let gallery = Gallery::load(&db, images.as_ref(), DEFAULT_TENANT)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    let mut model_guard = model.lock().await;
+    let training = model_guard
+        .train(&gallery)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Training failed: {}", e)))?;
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Cluster enrolled as {} and model trained successfully", user_id),
+        data: Some(EnrollClusterResponse {
+            user_id,
+            images_saved: saved_paths.len(),
+            training,
+        }),
+    }))
+}
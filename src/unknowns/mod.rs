@@ -0,0 +1,79 @@
+pub mod cluster;
+pub mod features;
+pub mod handlers;
+
+use anyhow::Result;
+use opencv::{
+    core::{Mat, Rect, Vector},
+    imgcodecs::imencode,
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use uuid::Uuid;
+
+use crate::encryption::{write_file, Cipher};
+use crate::face_recognition::model::{crop_face, to_gray};
+use features::{encode_features, face_descriptor};
+
+// Context kept around a retained face so the crop can be re-detected when enrolled
+const CROP_PADDING: f64 = 0.25;
+
+// Descriptors are always computed at this size so they stay comparable if the recognizer's changes
+const DESCRIPTOR_FACE_SIZE: i32 = 200;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UnknownsConfig {
+    /// Keep crops and features of faces that matched nobody
+    pub retain: bool,
+    /// Directory the crops are written to
+    pub dir: String,
+    /// Seconds between clustering passes
+    pub cluster_interval_secs: u64,
+    /// Mean chi-square distance per LBP cell below which two faces are the same person
+    pub cluster_distance: f64,
+    /// Clusters smaller than this are not listed
+    pub min_cluster_size: i64,
+}
+
+impl Default for UnknownsConfig {
+    fn default() -> Self {
+        Self {
+            retain: false,
+            dir: "unknowns".to_string(),
+            cluster_interval_secs: 300,
+            cluster_distance: 0.25,
+            min_cluster_size: 2,
+        }
+    }
+}
+
+/// Save a padded crop of an unknown face and compute its descriptor.
+/// Returns the crop path and the encoded features, both encrypted when encryption is enabled.
+pub fn capture(config: &UnknownsConfig, cipher: &Cipher, img: &Mat, rect: Rect) -> Result<(String, Vec<u8>)> {
+    let gray = to_gray(img)?;
+    let descriptor = face_descriptor(&crop_face(&gray, rect, DESCRIPTOR_FACE_SIZE)?)?;
+
+    let pad_x = (rect.width as f64 * CROP_PADDING) as i32;
+    let pad_y = (rect.height as f64 * CROP_PADDING) as i32;
+    let x = (rect.x - pad_x).max(0);
+    let y = (rect.y - pad_y).max(0);
+    let padded = Rect::new(
+        x,
+        y,
+        (rect.x + rect.width + pad_x).min(img.cols()) - x,
+        (rect.y + rect.height + pad_y).min(img.rows()) - y,
+    );
+
+    let mut jpeg = Vector::<u8>::new();
+    if !imencode(".jpg", &Mat::roi(img, padded)?, &mut jpeg, &Vector::new())? {
+        return Err(anyhow::anyhow!("Failed to encode unknown face"));
+    }
+
+    fs::create_dir_all(&config.dir)?;
+    let path = format!("{}/{}.jpg", config.dir, Uuid::new_v4());
+    write_file(cipher, &path, &jpeg.to_vec())?;
+
+    Ok((path, cipher.encrypt(&encode_features(&descriptor))?))
+}
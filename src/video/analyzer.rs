@@ -0,0 +1,314 @@
+use anyhow::Result;
+use opencv::{
+    core::{Mat, Size},
+    prelude::*,
+    videoio::{
+        VideoCapture, VideoWriter, CAP_ANY, CAP_PROP_FPS, CAP_PROP_FRAME_HEIGHT, CAP_PROP_FRAME_WIDTH,
+    },
+};
+use std::collections::HashMap;
+use tokio::runtime::Handle;
+
+use crate::database::{VideoAppearance, VideoJobProgress};
+use crate::face_recognition::{annotate::draw_matches, FaceMatch};
+use crate::face_recognition::handlers::{SharedDb, SharedModel};
+
+// Used when the container does not report a frame rate (common for live streams)
+const FALLBACK_FPS: f64 = 25.0;
+
+// An appearance is closed once the user has not been seen for this long
+const APPEARANCE_GAP_MS: i64 = 2000;
+
+// Persist progress counters and ended appearances every n analyzed frames
+const PROGRESS_INTERVAL: i64 = 25;
+
+pub struct AnalyzeOptions {
+    pub source: String,
+    pub frame_stride: i64,
+    pub output_path: Option<String>,
+    /// Stop after this much video time
+    pub max_duration_secs: u64,
+}
+
+/// Groups per-frame recognitions into continuous appearances per user
+struct AppearanceTracker {
+    gap_ms: i64,
+    open: HashMap<String, VideoAppearance>,
+    closed: Vec<VideoAppearance>,
+}
+
+impl AppearanceTracker {
+    fn new(gap_ms: i64) -> Self {
+        Self {
+            gap_ms,
+            open: HashMap::new(),
+            closed: Vec::new(),
+        }
+    }
+
+    fn observe(&mut self, user_id: &str, frame: i64, timestamp_ms: i64, confidence: f64) {
+        if let Some(current) = self.open.get_mut(user_id)
+            && timestamp_ms - current.last_seen_ms <= self.gap_ms
+        {
+            current.last_frame = frame;
+            current.last_seen_ms = timestamp_ms;
+            current.detections += 1;
+            current.best_confidence = current.best_confidence.min(confidence);
+            return;
+        }
+
+        let next = VideoAppearance {
+            user_id: user_id.to_string(),
+            first_frame: frame,
+            last_frame: frame,
+            first_seen_ms: timestamp_ms,
+            last_seen_ms: timestamp_ms,
+            detections: 1,
+            best_confidence: confidence,
+        };
+        if let Some(previous) = self.open.insert(user_id.to_string(), next) {
+            self.closed.push(previous);
+        }
+    }
+
+    /// Close the appearances of users not seen within the gap before `timestamp_ms` and hand
+    /// over every closed appearance
+    fn take_closed(&mut self, timestamp_ms: i64) -> Vec<VideoAppearance> {
+        let ended: Vec<String> = self
+            .open
+            .iter()
+            .filter(|(_, appearance)| timestamp_ms - appearance.last_seen_ms > self.gap_ms)
+            .map(|(user_id, _)| user_id.clone())
+            .collect();
+        for user_id in ended {
+            if let Some(appearance) = self.open.remove(&user_id) {
+                self.closed.push(appearance);
+            }
+        }
+
+        let mut closed = std::mem::take(&mut self.closed);
+        closed.sort_by_key(|a| a.first_seen_ms);
+        closed
+    }
+
+    fn finish(mut self) -> Vec<VideoAppearance> {
+        self.closed.extend(self.open.into_values());
+        self.closed.sort_by_key(|a| a.first_seen_ms);
+        self.closed
+    }
+}
+
+/// Run a video analysis job to completion, or until `max_duration_secs` of video were read.
+///
+/// This blocks on OpenCV I/O for the whole video, so it must be called from
+/// `spawn_blocking`. The model lock is only held while a single frame is analyzed,
+/// so HTTP requests can still be served while a job runs. Appearances are saved as soon as
+/// they end, so results of a long video or stream show up while it is analyzed.
+pub fn run_job(job_id: &str, options: &AnalyzeOptions, model: SharedModel, db: SharedDb, handle: Handle) -> Result<()> {
+    let mut capture = VideoCapture::from_file(&options.source, CAP_ANY)?;
+    if !capture.is_opened()? {
+        return Err(anyhow::anyhow!("Failed to open video source: {}", options.source));
+    }
+
+    let fps = match capture.get(CAP_PROP_FPS)? {
+        fps if fps > 0.0 => fps,
+        _ => FALLBACK_FPS,
+    };
+
+    let mut writer = match &options.output_path {
+        Some(path) => {
+            let size = Size::new(
+                capture.get(CAP_PROP_FRAME_WIDTH)? as i32,
+                capture.get(CAP_PROP_FRAME_HEIGHT)? as i32,
+            );
+            let fourcc = VideoWriter::fourcc('M', 'J', 'P', 'G')?;
+            let writer = VideoWriter::new(path, fourcc, fps, size, true)?;
+            if !writer.is_opened()? {
+                return Err(anyhow::anyhow!("Failed to open output video: {}", path));
+            }
+            Some(writer)
+        }
+        None => None,
+    };
+
+    handle.block_on(db.update_video_job(job_id, "running", &VideoJobProgress::default()))?;
+
+    let mut tracker = AppearanceTracker::new(APPEARANCE_GAP_MS);
+    let mut progress = VideoJobProgress::default();
+    let mut last_matches: Vec<FaceMatch> = Vec::new();
+    let mut frame = Mat::default();
+    let max_duration_ms = options.max_duration_secs.saturating_mul(1000) as f64;
+
+    while capture.read(&mut frame)? && !frame.empty() {
+        let frame_index = progress.frames_read;
+        let position_ms = frame_index as f64 * 1000.0 / fps;
+        if position_ms >= max_duration_ms {
+            println!("Video job {} stopped after {} s of video", job_id, options.max_duration_secs);
+            break;
+        }
+        progress.frames_read += 1;
+
+        if frame_index % options.frame_stride == 0 {
+            let timestamp_ms = position_ms as i64;
+
+            last_matches = model.blocking_lock().predict_faces(&frame)?;
+
+            progress.frames_analyzed += 1;
+            progress.faces_detected += last_matches.len() as i64;
+            for face in &last_matches {
+                match &face.user_id {
+                    Some(user_id) => tracker.observe(user_id, frame_index, timestamp_ms, face.confidence),
+                    None => progress.unknown_faces += 1,
+                }
+            }
+
+            if progress.frames_analyzed % PROGRESS_INTERVAL == 0 {
+                for appearance in tracker.take_closed(timestamp_ms) {
+                    handle.block_on(db.insert_video_appearance(job_id, &appearance))?;
+                }
+                handle.block_on(db.update_video_job(job_id, "running", &progress))?;
+            }
+        }
+
+        if let Some(writer) = writer.as_mut() {
+            // Frames between analyzed ones reuse the latest boxes so the output stays smooth
+            draw_matches(&mut frame, &last_matches)?;
+            writer.write(&frame)?;
+        }
+    }
+
+    if let Some(mut writer) = writer {
+        writer.release()?;
+    }
+
+    for appearance in tracker.finish() {
+        handle.block_on(db.insert_video_appearance(job_id, &appearance))?;
+    }
+
+    handle.block_on(db.update_video_job(job_id, "running", &progress))?;
+    println!(
+        "Video job {} analyzed {} of {} frames",
+        job_id, progress.frames_analyzed, progress.frames_read
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::database::Database;
+    use crate::face_recognition::FaceRecognitionModel;
+    use crate::images::{local::FsImageStore, Gallery, ImageStore};
+    use crate::tenants::{TenantPaths, DEFAULT_TENANT};
+    use opencv::{
+        core::{Scalar, CV_8UC3},
+        imgcodecs::{imread, IMREAD_COLOR},
+    };
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+    use uuid::Uuid;
+
+    fn users(appearances: &[VideoAppearance]) -> Vec<(&str, i64, i64, i64)> {
+        appearances
+            .iter()
+            .map(|a| (a.user_id.as_str(), a.first_seen_ms, a.last_seen_ms, a.detections))
+            .collect()
+    }
+
+    #[test]
+    fn takes_appearances_once_they_end() {
+        let mut tracker = AppearanceTracker::new(1000);
+        tracker.observe("alice", 0, 0, 40.0);
+        tracker.observe("alice", 1, 500, 35.0);
+        tracker.observe("bob", 3, 1500, 50.0);
+        // Seen again after more than the gap, so a second appearance starts
+        tracker.observe("alice", 4, 2000, 45.0);
+
+        assert_eq!(users(&tracker.take_closed(2000)), [("alice", 0, 500, 2)]);
+        assert!(tracker.take_closed(2000).is_empty());
+        assert_eq!(users(&tracker.take_closed(2600)), [("bob", 1500, 1500, 1)]);
+
+        tracker.observe("bob", 6, 3000, 48.0);
+        assert_eq!(users(&tracker.finish()), [("alice", 2000, 2000, 1), ("bob", 3000, 3000, 1)]);
+    }
+
+    #[test]
+    fn keeps_the_best_confidence() {
+        let mut tracker = AppearanceTracker::new(1000);
+        for (frame, confidence) in [(0, 40.0), (1, 30.0), (2, 45.0)] {
+            tracker.observe("alice", frame, frame * 100, confidence);
+        }
+
+        let appearances = tracker.finish();
+        assert_eq!(appearances.len(), 1);
+        assert_eq!((appearances[0].first_frame, appearances[0].last_frame), (0, 2));
+        assert_eq!(appearances[0].best_confidence, 30.0);
+    }
+
+    // Needs OpenCV with its haarcascade and an MJPG-capable video backend
+    #[tokio::test(flavor = "multi_thread")]
+    async fn analyzes_a_short_video_up_to_the_limit() {
+        let dir = std::env::temp_dir().join(format!("video-{}", Uuid::new_v4()));
+        let dir = dir.to_string_lossy();
+        let mut config = Config::default();
+        config.paths.knowledge_dir = format!("{}/knowledge", dir);
+        config.paths.tenants_dir = format!("{}/tenants", dir);
+        config.paths.model_path = format!("{}/models/lbph.yml", dir);
+        config.paths.temp_dir = format!("{}/temp", dir);
+        config.database.url = "sqlite::memory:".to_string();
+        config.database.max_connections = 1;
+        std::fs::create_dir_all(&*dir).unwrap();
+
+        let db = Arc::new(Database::new(&config.database).await.unwrap());
+        let images = FsImageStore;
+        let paths = TenantPaths::new(&config, DEFAULT_TENANT);
+        db.upsert_user(DEFAULT_TENANT, "nasri").await.unwrap();
+        for index in 1..=3 {
+            let key = paths.image_key("nasri", &format!("{}.jpg", index));
+            let photo = format!("{}/knowledge/nasri/photo_{}.jpg", env!("CARGO_MANIFEST_DIR"), index);
+            images.put(&key, &std::fs::read(photo).unwrap()).await.unwrap();
+            db.insert_face_image(DEFAULT_TENANT, "nasri", &key).await.unwrap();
+        }
+        let mut model = FaceRecognitionModel::with_model_path(&config, &paths.model_path).unwrap();
+        model.train(&Gallery::load(&db, &images, DEFAULT_TENANT).await.unwrap()).unwrap();
+
+        // One second of the enrolled face, then four of an empty frame, at 10 fps
+        let source = format!("{}/clip.avi", dir);
+        let photo = imread(&format!("{}/knowledge/nasri/photo_1.jpg", env!("CARGO_MANIFEST_DIR")), IMREAD_COLOR).unwrap();
+        let blank = Mat::new_size_with_default(photo.size().unwrap(), CV_8UC3, Scalar::all(0.0)).unwrap();
+        let fourcc = VideoWriter::fourcc('M', 'J', 'P', 'G').unwrap();
+        let mut writer = VideoWriter::new(&source, fourcc, 10.0, photo.size().unwrap(), true).unwrap();
+        for index in 0..50 {
+            writer.write(if index < 10 { &photo } else { &blank }).unwrap();
+        }
+        writer.release().unwrap();
+
+        db.create_video_job("job", &source, 1, None).await.unwrap();
+        let options = AnalyzeOptions {
+            source: source.clone(),
+            frame_stride: 1,
+            output_path: None,
+            max_duration_secs: 3,
+        };
+        let model = Arc::new(Mutex::new(model));
+        let handle = Handle::current();
+        let job_db = db.clone();
+        tokio::task::spawn_blocking(move || run_job("job", &options, model, job_db, handle))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let job = db.get_video_job("job").await.unwrap().unwrap();
+        assert_eq!(job.progress.frames_read, 30);
+        assert_eq!(job.progress.frames_analyzed, 30);
+        let appearances = db.get_video_appearances("job").await.unwrap();
+        assert_eq!(appearances.len(), 1);
+        assert_eq!(appearances[0].user_id, "nasri");
+        assert_eq!(appearances[0].first_seen_ms, 0);
+        assert!(appearances[0].last_seen_ms <= 900);
+
+        std::fs::remove_dir_all(&*dir).unwrap();
+    }
+}
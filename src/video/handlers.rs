@@ -0,0 +1,217 @@
+use axum::{
+    body::Body,
+    extract::{Extension, Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use tokio::runtime::Handle;
+use tokio_util::io::ReaderStream;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::analyzer::{run_job, AnalyzeOptions};
+use super::resolve_source;
+use crate::audit::AuditContext;
+use crate::config::SharedConfig;
+use crate::database::{VideoAppearance, VideoJob};
+use crate::face_recognition::handlers::{ApiResponse, SharedDb, SharedModel};
+
+const DEFAULT_FRAME_STRIDE: i64 = 5;
+
+/// Request body for starting a video analysis job
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AnalyzeVideoRequest {
+    /// Video file path relative to `video.input_dir`, or a stream URL (e.g. `rtsp://...`) with
+    /// a scheme in `video.allowed_url_schemes` and a host in `video.allowed_hosts`
+    source: String,
+    /// Analyze every n-th frame (default 5)
+    frame_stride: Option<i64>,
+    /// Write an annotated copy of the video with boxes and names drawn on it
+    #[serde(default)]
+    annotate: bool,
+}
+
+/// Response for a newly created video analysis job
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AnalyzeVideoResponse {
+    /// ID used to poll `GET /videos/{job_id}`
+    job_id: String,
+    /// Initial job status
+    status: String,
+}
+
+/// Status and results of a video analysis job
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VideoJobResponse {
+    job: VideoJob,
+    /// Appearances of enrolled users, ordered by first sighting
+    appearances: Vec<VideoAppearance>,
+}
+
+/// Analyze a video file or stream
+///
+/// Starts a background job that reads the source, runs face detection and recognition on
+/// every `frame_stride`-th frame and records each continuous appearance of an enrolled user.
+/// Analysis stops after `video.max_duration_secs` of video.
+#[utoipa::path(
+    post,
+    path = "/videos/analyze",
+    request_body = AnalyzeVideoRequest,
+    responses(
+        (status = 200, description = "Job started", body = ApiResponse<AnalyzeVideoResponse>),
+        (status = 400, description = "Bad request - source not allowed or not found, or invalid frame stride"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Video"
+)]
+pub async fn analyze_video(
+    State((model, db)): State<(SharedModel, SharedDb)>,
+    Extension(config): Extension<SharedConfig>,
+    Extension(audit): Extension<AuditContext>,
+    Json(request): Json<AnalyzeVideoRequest>,
+) -> Result<Json<ApiResponse<AnalyzeVideoResponse>>, (StatusCode, String)> {
+    let frame_stride = request.frame_stride.unwrap_or(DEFAULT_FRAME_STRIDE);
+    if frame_stride < 1 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "frame_stride must be at least 1".to_string(),
+        ));
+    }
+
+    let source = resolve_source(&config.video, &request.source).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    let job_id = Uuid::new_v4().to_string();
+    audit.target(format!("video/{}", job_id));
+
+    let output_path = if request.annotate {
+        let output_dir = &config.paths.video_output_dir;
+        fs::create_dir_all(output_dir)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        Some(format!("{}/{}.avi", output_dir, job_id))
+    } else {
+        None
+    };
+
+    db.create_video_job(&job_id, &source, frame_stride, output_path.as_deref())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let options = AnalyzeOptions {
+        source,
+        frame_stride,
+        output_path,
+        max_duration_secs: config.video.max_duration_secs,
+    };
+    let handle = Handle::current();
+    let id = job_id.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let (status, error) = match run_job(&id, &options, model, db.clone(), handle.clone()) {
+            Ok(()) => ("completed", None),
+            Err(e) => {
+                eprintln!("Video job {} failed: {}", id, e);
+                ("failed", Some(e.to_string()))
+            }
+        };
+
+        if let Err(e) = handle.block_on(db.finish_video_job(&id, status, error.as_deref())) {
+            eprintln!("Failed to update video job {}: {}", id, e);
+        }
+    });
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: "Video analysis started".to_string(),
+        data: Some(AnalyzeVideoResponse {
+            job_id,
+            status: "queued".to_string(),
+        }),
+    }))
+}
+
+/// Get video analysis results
+///
+/// Returns the job status, progress counters and the appearances found so far.
+#[utoipa::path(
+    get,
+    path = "/videos/{job_id}",
+    params(("job_id" = String, Path, description = "Video job ID")),
+    responses(
+        (status = 200, description = "Job found", body = ApiResponse<VideoJobResponse>),
+        (status = 404, description = "Job not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Video"
+)]
+pub async fn get_video_job(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+    Path(job_id): Path<String>,
+) -> Result<Json<ApiResponse<VideoJobResponse>>, (StatusCode, String)> {
+    let job = db
+        .get_video_job(&job_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, format!("Video job not found: {}", job_id)))?;
+
+    let appearances = db
+        .get_video_appearances(&job_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Video job is {}", job.status),
+        data: Some(VideoJobResponse { job, appearances }),
+    }))
+}
+
+/// Download the annotated output video
+///
+/// Only available for jobs started with `annotate: true` once they have completed.
+#[utoipa::path(
+    get,
+    path = "/videos/{job_id}/output",
+    params(("job_id" = String, Path, description = "Video job ID")),
+    responses(
+        (status = 200, description = "Annotated video (MJPG in AVI container)", content_type = "video/x-msvideo"),
+        (status = 404, description = "Job or output not found"),
+        (status = 409, description = "Job has not completed yet"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Video"
+)]
+pub async fn get_video_output(
+    State((_model, db)): State<(SharedModel, SharedDb)>,
+    Path(job_id): Path<String>,
+) -> Result<Response, (StatusCode, String)> {
+    let job = db
+        .get_video_job(&job_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, format!("Video job not found: {}", job_id)))?;
+
+    let output_path = job.output_path.ok_or((
+        StatusCode::NOT_FOUND,
+        "Job was started without annotate=true".to_string(),
+    ))?;
+
+    if job.status != "completed" {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Video job is {}", job.status),
+        ));
+    }
+
+    let file = tokio::fs::File::open(&output_path)
+        .await
+        .map_err(|e| (StatusCode::NOT_FOUND, format!("Output video unavailable: {}", e)))?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "video/x-msvideo")],
+        Body::from_stream(ReaderStream::new(file)),
+    )
+        .into_response())
+}
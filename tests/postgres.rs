@@ -0,0 +1,196 @@
+//! The shared `$N` queries against a real PostgreSQL server, to catch drift between the
+//! SQLite and PostgreSQL migrations. Point `TEST_POSTGRES_URL` (or `DATABASE_URL`, when it is a
+//! `postgres://` URL) at a database the tests may migrate and write to, for example
+//! `postgres://postgres@localhost:5432/face_test`; without one the tests are skipped.
+//! Every run works in its own tenant and key prefix, so the database can be reused.
+
+// The service is a binary crate, so the database module is compiled in directly; most of its
+// queries are not used here
+#[allow(dead_code)]
+#[path = "../src/database/mod.rs"]
+mod database;
+
+// The part of the configuration the database module uses
+mod config {
+    pub struct DatabaseConfig {
+        pub url: String,
+        pub max_connections: u32,
+    }
+}
+
+use database::{Database, DetectionCursor, DetectionFilter, DetectionSort, SortOrder};
+use uuid::Uuid;
+
+async fn connect() -> Option<Database> {
+    let url = std::env::var("TEST_POSTGRES_URL")
+        .ok()
+        .or_else(|| std::env::var("DATABASE_URL").ok().filter(|url| url.starts_with("postgres")))?;
+    let config = config::DatabaseConfig { url, max_connections: 2 };
+
+    Some(Database::new(&config).await.expect("migrating the test database"))
+}
+
+fn filter(tenant: &str, sort: DetectionSort, after: Option<DetectionCursor>) -> DetectionFilter {
+    DetectionFilter {
+        tenant_id: tenant.to_string(),
+        user_id: None,
+        matched: None,
+        min_confidence: None,
+        max_confidence: None,
+        from: None,
+        to: None,
+        source: None,
+        sort,
+        order: SortOrder::Desc,
+        after,
+        limit: 2,
+    }
+}
+
+// One test, so the migrations never run concurrently against a fresh database
+#[tokio::test]
+async fn shared_queries() {
+    let Some(db) = connect().await else {
+        eprintln!("TEST_POSTGRES_URL is not set; skipping");
+        return;
+    };
+    let run = Uuid::new_v4().simple().to_string();
+
+    migrations_are_applied(&db).await;
+    tenants(&db, &run).await;
+    users(&db, &run).await;
+    detections(&db, &run).await;
+    image_blobs(&db, &run).await;
+}
+
+async fn migrations_are_applied(db: &Database) {
+    let status = db.migration_status().await.unwrap();
+    assert!(status.iter().all(|migration| migration.applied_at.is_some()));
+    assert!(db.migrate().await.unwrap().is_empty());
+}
+
+async fn tenants(db: &Database, run: &str) {
+    let tenant = format!("tenants-{}", run);
+
+    assert!(db.tenant_exists("default").await.unwrap());
+    assert!(!db.tenant_exists(&tenant).await.unwrap());
+    assert!(db.create_tenant(&tenant).await.unwrap());
+    assert!(!db.create_tenant(&tenant).await.unwrap());
+    assert!(db.tenant_exists(&tenant).await.unwrap());
+
+    let summary = db.get_tenants().await.unwrap().into_iter().find(|summary| summary.tenant_id == tenant).unwrap();
+    assert_eq!((summary.users, summary.images), (0, 0));
+}
+
+async fn users(db: &Database, run: &str) {
+    let tenant = format!("users-{}", run);
+
+    db.upsert_user(&tenant, "alice").await.unwrap();
+    db.upsert_user(&tenant, "alice").await.unwrap();
+    db.insert_face_image(&tenant, "alice", "knowledge/alice/1.jpg").await.unwrap();
+    db.upsert_user(&format!("{}-other", tenant), "alice").await.unwrap();
+
+    assert_eq!(db.get_all_users(&tenant).await.unwrap(), ["alice"]);
+    assert_eq!(
+        db.get_face_images(&tenant).await.unwrap(),
+        [("alice".to_string(), "knowledge/alice/1.jpg".to_string())]
+    );
+
+    assert!(db.delete_user(&tenant, "alice").await.unwrap());
+    assert!(db.get_face_images(&tenant).await.unwrap().is_empty());
+    assert_eq!(db.get_all_users(&format!("{}-other", tenant)).await.unwrap(), ["alice"]);
+}
+
+async fn detections(db: &Database, run: &str) {
+    let tenant = format!("detections-{}", run);
+
+    // A NULL confidence between two real ones exercises the float binding on PostgreSQL
+    let first = db.log_detection(&tenant, Some("alice"), Some(40.0), Some("temp/1.jpg"), Some("gate")).await.unwrap();
+    let second = db.log_detection(&tenant, None, None, Some("temp/2.jpg"), None).await.unwrap();
+    let third = db.log_detection(&tenant, Some("bob"), Some(55.5), Some("temp/3.jpg"), Some("gate")).await.unwrap();
+
+    // Feedback and single detections stay within their tenant
+    db.upsert_detection_feedback(first, "correct", Some("alice"), None).await.unwrap();
+    db.upsert_detection_feedback(second, "unknown_person", None, None).await.unwrap();
+    let samples = db.get_feedback_samples(&tenant).await.unwrap();
+    assert_eq!(samples.len(), 1);
+    assert_eq!(samples[0].confidence, 40.0);
+    assert!(db.get_feedback_samples(&format!("{}-other", tenant)).await.unwrap().is_empty());
+    assert_eq!(db.get_detection(&tenant, first).await.unwrap().map(|detection| detection.id), Some(first));
+    assert!(db.get_detection(&format!("{}-other", tenant), first).await.unwrap().is_none());
+
+    let page = db.get_detections(&filter(&tenant, DetectionSort::DetectedAt, None)).await.unwrap();
+    let ids: Vec<i64> = page.iter().map(|detection| detection.id).collect();
+    assert_eq!(ids, [third, second]);
+    let after = DetectionCursor::after(page.last().unwrap(), DetectionSort::DetectedAt);
+    let page = db.get_detections(&filter(&tenant, DetectionSort::DetectedAt, Some(after))).await.unwrap();
+    let ids: Vec<i64> = page.iter().map(|detection| detection.id).collect();
+    assert_eq!(ids, [first]);
+
+    let page = db.get_detections(&filter(&tenant, DetectionSort::Confidence, None)).await.unwrap();
+    let ids: Vec<i64> = page.iter().map(|detection| detection.id).collect();
+    assert_eq!(ids, [third, first]);
+    let after = DetectionCursor::after(page.last().unwrap(), DetectionSort::Confidence);
+    let page = db.get_detections(&filter(&tenant, DetectionSort::Confidence, Some(after.clone()))).await.unwrap();
+    let ids: Vec<i64> = page.iter().map(|detection| detection.id).collect();
+    assert_eq!(ids, [second]);
+
+    // The cursor carries its sort key, so deleting its row does not end the paging
+    assert_eq!(db.purge_detections(&tenant, None, Some(first), false).await.unwrap(), 1);
+    let page = db.get_detections(&filter(&tenant, DetectionSort::Confidence, Some(after))).await.unwrap();
+    let ids: Vec<i64> = page.iter().map(|detection| detection.id).collect();
+    assert_eq!(ids, [second]);
+
+    let mut matched = filter(&tenant, DetectionSort::DetectedAt, None);
+    matched.matched = Some(true);
+    matched.min_confidence = Some(50.0);
+    matched.source = Some("gate".to_string());
+    let page = db.get_detections(&matched).await.unwrap();
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].detected_user_id.as_deref(), Some("bob"));
+    assert_eq!(page[0].confidence, Some(55.5));
+
+    // Nothing is older than a cutoff in the past; the ID bound removes the second, as the
+    // first is already gone
+    assert_eq!(db.purge_detections(&tenant, Some("2000-01-01 00:00:00"), None, true).await.unwrap(), 0);
+    assert_eq!(db.purge_detections(&tenant, None, Some(second), true).await.unwrap(), 1);
+    assert_eq!(db.purge_detections(&tenant, None, Some(second), false).await.unwrap(), 1);
+    let remaining = db.get_detections(&filter(&tenant, DetectionSort::DetectedAt, None)).await.unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].id, third);
+}
+
+async fn image_blobs(db: &Database, run: &str) {
+    // `_` and `%` in the prefix must match literally, not as LIKE wildcards
+    let prefix = format!("knowledge_{}", run);
+    let lookalike = format!("knowledgeX{}", run);
+
+    db.put_image_blob(&format!("{}/alice/1.jpg", prefix), b"one").await.unwrap();
+    db.put_image_blob(&format!("{}/alice/1.jpg", prefix), b"two").await.unwrap();
+    db.put_image_blob(&format!("{}/100%/2.jpg", prefix), b"three").await.unwrap();
+    db.put_image_blob(&format!("{}/alice/1.jpg", lookalike), b"other").await.unwrap();
+    db.put_image_blob(&format!("{}-old/alice/1.jpg", prefix), b"other").await.unwrap();
+
+    assert_eq!(
+        db.get_image_blob(&format!("{}/alice/1.jpg", prefix)).await.unwrap().as_deref(),
+        Some(&b"two"[..])
+    );
+    assert_eq!(
+        db.get_image_blob_keys(&prefix).await.unwrap(),
+        [format!("{}/100%/2.jpg", prefix), format!("{}/alice/1.jpg", prefix)]
+    );
+    assert_eq!(
+        db.get_image_blob_keys(&format!("{}/100%", prefix)).await.unwrap(),
+        [format!("{}/100%/2.jpg", prefix)]
+    );
+
+    for key in [
+        format!("{}/alice/1.jpg", prefix),
+        format!("{}/100%/2.jpg", prefix),
+        format!("{}/alice/1.jpg", lookalike),
+        format!("{}-old/alice/1.jpg", prefix),
+    ] {
+        db.delete_image_blob(&key).await.unwrap();
+    }
+    assert!(db.get_image_blob_keys(&prefix).await.unwrap().is_empty());
+}